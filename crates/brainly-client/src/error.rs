@@ -0,0 +1,81 @@
+use std::{collections::HashMap, fmt};
+
+use brainly_types::content::ContentResponse;
+use reqwest::StatusCode;
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+// Everything a call can fail with
+#[derive(Debug)]
+pub enum Error {
+    // The request could not be sent or the response could not be read
+    Http(reqwest::Error),
+    // The API answered with an error envelope
+    Api {
+        status: StatusCode,
+        message: String,
+        // Validation failures (422) map each field to its error messages, empty otherwise
+        fields: HashMap<String, Vec<String>>,
+    },
+    // The page is already saved (409), with the saved item
+    Conflict(ContentResponse),
+    // The response was not the JSON the API documents, e.g. a proxy error page
+    Unexpected {
+        status: StatusCode,
+        body: String,
+    },
+}
+
+impl Error {
+    // HTTP status of the response, None when no response was received
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            Error::Http(e) => e.status(),
+            Error::Api { status, .. } | Error::Unexpected { status, .. } => Some(*status),
+            Error::Conflict(_) => Some(StatusCode::CONFLICT),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(e) => write!(f, "{}", e),
+            Error::Api {
+                status,
+                message,
+                fields,
+            } => {
+                write!(f, "{} ({})", message, status)?;
+                let mut fields: Vec<_> = fields.iter().collect();
+                fields.sort();
+                for (field, messages) in fields {
+                    write!(f, "\n  {}: {}", field, messages.join(", "))?;
+                }
+                Ok(())
+            }
+            Error::Conflict(existing) => write!(
+                f,
+                "Content with this URL is already saved as item {} ({})",
+                existing.id,
+                StatusCode::CONFLICT
+            ),
+            Error::Unexpected { status, body } => write!(f, "{}: {}", status, body.trim()),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(e: reqwest::Error) -> Error {
+        Error::Http(e)
+    }
+}
@@ -0,0 +1,541 @@
+// Async client for the brainly HTTP API.
+// Every method sends one request, unwraps the `SuccessResponse` envelope and returns the
+// typed payload, or an `Error` carrying the API's message and field errors.
+use std::collections::BTreeMap;
+
+use brainly_types::{
+    api_key::{ApiKey, CreateApiKey, CreatedApiKey},
+    brain::{BrainShare, ShareBrain, SharedContent},
+    content::{
+        BulkContent, BulkResult, Content, ContentResponse, ContentRow, ListOptions, OnDuplicate,
+        TrashedContent, UserContents,
+    },
+    export::Export,
+    health::CheckResult,
+    import::{ImportFormat, ImportJob},
+    subscription::{CreateSubscription, Subscription},
+    user::CreateUser,
+    SuccessResponse,
+};
+use reqwest::{
+    header::{COOKIE, SET_COOKIE},
+    multipart, Method, RequestBuilder, Response, StatusCode,
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::Value;
+
+mod error;
+pub use error::{Error, Result};
+
+// Re-exported so users of the SDK do not need a separate dependency
+pub use brainly_types as types;
+
+const SESSION_COOKIE: &str = "auth_token";
+const CSRF_COOKIE: &str = "csrf_token";
+const CSRF_HEADER: &str = "X-CSRF-Token";
+
+// How requests authenticate
+#[derive(Debug, Clone, Default)]
+pub enum Auth {
+    #[default]
+    None,
+    // Session token from sign in, sent as `Authorization: Bearer <token>`
+    Bearer(String),
+    // API key, sent in the `X-Api-Key` header
+    ApiKey(String),
+    // Session and CSRF cookies, as a browser would send them. Mutating requests also
+    // repeat the CSRF token in the X-CSRF-Token header.
+    Cookie {
+        session: String,
+        csrf: String,
+    },
+}
+
+// Result of signing in
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub token: String,        // Session token, valid for 2 hours
+    pub csrf: Option<String>, // CSRF token issued alongside the session cookie
+}
+
+impl Session {
+    // Authenticate with the token in a Bearer header
+    pub fn bearer(&self) -> Auth {
+        Auth::Bearer(self.token.clone())
+    }
+
+    // Authenticate with the session cookie, None when the server issued no CSRF token
+    pub fn cookie(&self) -> Option<Auth> {
+        self.csrf.as_ref().map(|csrf| Auth::Cookie {
+            session: self.token.clone(),
+            csrf: csrf.clone(),
+        })
+    }
+}
+
+// Outcome of the readiness probe. Not being ready is reported here rather than as an error.
+#[derive(Debug, Clone)]
+pub struct Readiness {
+    pub ready: bool,
+    pub message: String, // "ready", "not ready" or "shutting down"
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    base_url: String,
+    auth: Auth,
+}
+
+impl Client {
+    // Client for the server at `base_url`, e.g. "https://brainly.example.com"
+    pub fn new(base_url: impl Into<String>) -> Client {
+        Client::with_http_client(reqwest::Client::new(), base_url)
+    }
+
+    // Use a preconfigured reqwest client, e.g. with custom timeouts or proxies
+    pub fn with_http_client(http: reqwest::Client, base_url: impl Into<String>) -> Client {
+        Client {
+            http,
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            auth: Auth::None,
+        }
+    }
+
+    pub fn with_auth(mut self, auth: Auth) -> Client {
+        self.auth = auth;
+        self
+    }
+
+    pub fn set_auth(&mut self, auth: Auth) {
+        self.auth = auth;
+    }
+
+    pub fn auth(&self) -> &Auth {
+        &self.auth
+    }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    // Absolute URL of an API path
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url, path)
+    }
+
+    // --- Accounts ---
+
+    // Create an account, returning the new user's ID
+    pub async fn signup(&self, user: &CreateUser) -> Result<String> {
+        self.call(Method::POST, "/api/v1/signup", Some(user)).await
+    }
+
+    // Sign in with a username and password. The returned session can be turned into
+    // Bearer or cookie authentication with `Session::bearer` or `Session::cookie`.
+    pub async fn signin(&self, user: &CreateUser) -> Result<Session> {
+        let response = self
+            .request(Method::POST, "/api/v1/signin", Some(user))
+            .send()
+            .await?;
+        let headers = response.headers();
+        let csrf = headers
+            .get(CSRF_HEADER)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string)
+            .or_else(|| {
+                headers
+                    .get_all(SET_COOKIE)
+                    .iter()
+                    .filter_map(|value| value.to_str().ok())
+                    .find_map(|value| cookie_value(value, CSRF_COOKIE))
+            });
+        let token = envelope_data(response).await?;
+        Ok(Session { token, csrf })
+    }
+
+    // URL to open in a browser to sign in with the configured identity provider
+    pub fn oidc_login_url(&self) -> String {
+        self.url("/api/v1/auth/oidc/login")
+    }
+
+    // --- API keys (require a session, not another API key) ---
+
+    pub async fn create_api_key(&self, key: &CreateApiKey) -> Result<CreatedApiKey> {
+        self.call(Method::POST, "/api/v1/user/api-keys", Some(key))
+            .await
+    }
+
+    pub async fn list_api_keys(&self) -> Result<Vec<ApiKey>> {
+        self.call(Method::GET, "/api/v1/user/api-keys", None::<&()>)
+            .await
+    }
+
+    pub async fn delete_api_key(&self, id: i32) -> Result<()> {
+        let path = format!("/api/v1/user/api-keys/{}", id);
+        self.call_empty(Method::DELETE, &path).await
+    }
+
+    // --- Content ---
+
+    // Save an item. Saving a URL that is already saved fails with a 409 `Error::Api`.
+    pub async fn create_content(&self, content: &Content) -> Result<ContentResponse> {
+        self.create_content_with(content, OnDuplicate::Reject).await
+    }
+
+    // Save an item, choosing what happens when its URL is already saved
+    pub async fn create_content_with(
+        &self,
+        content: &Content,
+        on_duplicate: OnDuplicate,
+    ) -> Result<ContentResponse> {
+        let path = format!("/api/v1/content?on_duplicate={}", on_duplicate);
+        self.call(Method::POST, &path, Some(content)).await
+    }
+
+    // Every item saved by the signed in user, with tags
+    pub async fn list_content(&self) -> Result<Vec<UserContents>> {
+        self.call(Method::GET, "/api/v1/user/content", None::<&()>)
+            .await
+    }
+
+    // Items whose title, URL or a tag contains `query`, ignoring case
+    pub async fn search_content(&self, query: &str) -> Result<Vec<UserContents>> {
+        let options = ListOptions {
+            q: Some(query.to_string()),
+        };
+        let request = self
+            .request(Method::GET, "/api/v1/user/content", None::<&()>)
+            .query(&options);
+        envelope_data(request.send().await?).await
+    }
+
+    pub async fn get_content(&self, id: i32) -> Result<ContentRow> {
+        let path = format!("/api/v1/content/{}", id);
+        self.call(Method::GET, &path, None::<&()>).await
+    }
+
+    // Apply one action to many items in a single transaction. When `all_or_nothing` rolled
+    // the changes back, the per-item results are still returned, with `applied` false.
+    pub async fn bulk_content(&self, bulk: &BulkContent) -> Result<BulkResult> {
+        let response = self
+            .request(Method::POST, "/api/v1/content/bulk", Some(bulk))
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        match serde_json::from_str::<SuccessResponse<BulkResult>>(&body) {
+            Ok(SuccessResponse {
+                data: Some(result), ..
+            }) => Ok(result),
+            _ if !status.is_success() => Err(error_from(status, &body)),
+            _ => Err(Error::Unexpected { status, body }),
+        }
+    }
+
+    // Move an item to the trash, from which it can be restored until it is purged
+    pub async fn delete_content(&self, id: i32) -> Result<()> {
+        let path = format!("/api/v1/content/{}", id);
+        self.call_empty(Method::DELETE, &path).await
+    }
+
+    pub async fn restore_content(&self, id: i32) -> Result<()> {
+        let path = format!("/api/v1/content/{}/restore", id);
+        self.call_empty(Method::POST, &path).await
+    }
+
+    // Items in the trash, most recently deleted first
+    pub async fn list_trash(&self) -> Result<Vec<TrashedContent>> {
+        self.call(Method::GET, "/api/v1/trash", None::<&()>).await
+    }
+
+    // Permanently delete one trashed item
+    pub async fn purge_content(&self, id: i32) -> Result<()> {
+        let path = format!("/api/v1/trash/{}", id);
+        self.call_empty(Method::DELETE, &path).await
+    }
+
+    // Permanently delete everything in the trash, returning how many items were deleted
+    pub async fn empty_trash(&self) -> Result<u64> {
+        self.call(Method::DELETE, "/api/v1/trash", None::<&()>)
+            .await
+    }
+
+    // Look up one of the signed in user's items by its generated link
+    pub async fn get_content_by_link(&self, link: &str) -> Result<ContentRow> {
+        let path = format!("/api/v1/content/link/{}", link);
+        self.call(Method::GET, &path, None::<&()>).await
+    }
+
+    // Make the brain public, or private again. The share hash addresses its public feeds.
+    pub async fn share_brain(&self, share: bool) -> Result<BrainShare> {
+        self.call(
+            Method::POST,
+            "/api/v1/brain/share",
+            Some(&ShareBrain { share }),
+        )
+        .await
+    }
+
+    // URL at which anyone can open an item of a shared brain, see `get_shared_content`
+    pub fn shared_content_url(&self, share_hash: &str, link: &str) -> String {
+        self.url(&format!("/api/v1/brain/{}/content/{}", share_hash, link))
+    }
+
+    // An item of a shared brain. Needs no authentication.
+    pub async fn get_shared_content(&self, share_hash: &str, link: &str) -> Result<SharedContent> {
+        let path = format!("/api/v1/brain/{}/content/{}", share_hash, link);
+        self.call(Method::GET, &path, None::<&()>).await
+    }
+
+    // Subscribe to an RSS or Atom feed, saving its new entries with `tags`
+    pub async fn create_subscription(
+        &self,
+        subscription: &CreateSubscription,
+    ) -> Result<Subscription> {
+        self.call(Method::POST, "/api/v1/feeds", Some(subscription))
+            .await
+    }
+
+    pub async fn list_subscriptions(&self) -> Result<Vec<Subscription>> {
+        self.call(Method::GET, "/api/v1/feeds", None::<&()>).await
+    }
+
+    pub async fn delete_subscription(&self, id: i32) -> Result<()> {
+        let path = format!("/api/v1/feeds/{}", id);
+        self.call_empty(Method::DELETE, &path).await
+    }
+
+    // Everything the user saved, as one versioned document
+    pub async fn export(&self) -> Result<Export> {
+        let response = self
+            .request(Method::GET, "/api/v1/user/export", None::<&()>)
+            .send()
+            .await?;
+        // The export is the document itself, not wrapped in an envelope
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(error_from(status, &body));
+        }
+        serde_json::from_str(&body).map_err(|_| Error::Unexpected { status, body })
+    }
+
+    // Everything the user saved as a zip of Markdown notes, for Obsidian or Logseq
+    pub async fn export_markdown(&self) -> Result<Vec<u8>> {
+        let response = self
+            .request(Method::GET, "/api/v1/user/export/markdown", None::<&()>)
+            .send()
+            .await?;
+        let status = response.status();
+        if !status.is_success() {
+            return Err(error_from(status, &response.text().await?));
+        }
+        Ok(response.bytes().await?.to_vec())
+    }
+
+    // Upload a file to import in the background. The format is detected from the file name
+    // and contents when not given. Poll `import_job` for the report.
+    pub async fn import(
+        &self,
+        filename: &str,
+        data: Vec<u8>,
+        format: Option<ImportFormat>,
+    ) -> Result<ImportJob> {
+        let mut form = multipart::Form::new().part(
+            "file",
+            multipart::Part::bytes(data).file_name(filename.to_string()),
+        );
+        if let Some(format) = format {
+            form = form.text("format", format.to_string());
+        }
+        let request = self.request(Method::POST, "/api/v1/user/import", None::<&()>);
+        envelope_data(request.multipart(form).send().await?).await
+    }
+
+    pub async fn import_job(&self, id: i32) -> Result<ImportJob> {
+        let path = format!("/api/v1/user/import/{}", id);
+        self.call(Method::GET, &path, None::<&()>).await
+    }
+
+    // --- Operations ---
+
+    pub async fn liveness(&self) -> Result<()> {
+        self.call_empty(Method::GET, "/healthz").await
+    }
+
+    pub async fn readiness(&self) -> Result<Readiness> {
+        let response = self
+            .request(Method::GET, "/readyz", None::<&()>)
+            .send()
+            .await?;
+        // 503 still carries the checks, so both outcomes are parsed the same way
+        let status = response.status();
+        if status != StatusCode::OK && status != StatusCode::SERVICE_UNAVAILABLE {
+            return Err(error_from(status, &response.text().await?));
+        }
+
+        let body = response.text().await?;
+        let envelope: SuccessResponse<BTreeMap<String, CheckResult>> =
+            serde_json::from_str(&body).map_err(|_| Error::Unexpected { status, body })?;
+        Ok(Readiness {
+            ready: status == StatusCode::OK,
+            message: envelope.message,
+            checks: envelope.data.unwrap_or_default(),
+        })
+    }
+
+    // Prometheus metrics in the text exposition format
+    pub async fn metrics(&self) -> Result<String> {
+        let response = self
+            .request(Method::GET, "/metrics", None::<&()>)
+            .send()
+            .await?;
+        let status = response.status();
+        let body = response.text().await?;
+        if !status.is_success() {
+            return Err(error_from(status, &body));
+        }
+        Ok(body)
+    }
+
+    // --- Plumbing ---
+
+    // Build a request with the configured authentication and an optional JSON body
+    fn request<B: Serialize + ?Sized>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&B>,
+    ) -> RequestBuilder {
+        let mutating = !matches!(method, Method::GET | Method::HEAD | Method::OPTIONS);
+        let mut request = self.http.request(method, self.url(path));
+        request = match &self.auth {
+            Auth::None => request,
+            Auth::Bearer(token) => request.bearer_auth(token),
+            Auth::ApiKey(key) => request.header("X-Api-Key", key),
+            Auth::Cookie { session, csrf } => {
+                let request = request.header(
+                    COOKIE,
+                    format!("{}={}; {}={}", SESSION_COOKIE, session, CSRF_COOKIE, csrf),
+                );
+                if mutating {
+                    request.header(CSRF_HEADER, csrf)
+                } else {
+                    request
+                }
+            }
+        };
+        match body {
+            Some(body) => request.json(body),
+            None => request,
+        }
+    }
+
+    // Send a request and return the envelope's data
+    async fn call<B, T>(&self, method: Method, path: &str, body: Option<&B>) -> Result<T>
+    where
+        B: Serialize + ?Sized,
+        T: DeserializeOwned,
+    {
+        envelope_data(self.request(method, path, body).send().await?).await
+    }
+
+    // Send a request whose envelope carries no data
+    async fn call_empty(&self, method: Method, path: &str) -> Result<()> {
+        let response = self.request(method, path, None::<&()>).send().await?;
+        envelope_data::<Option<Value>>(response).await.map(|_| ())
+    }
+}
+
+// Unwrap the envelope of a successful response, or turn a failed one into an error
+async fn envelope_data<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let status = response.status();
+    let body = response.text().await?;
+    if !status.is_success() {
+        return Err(error_from(status, &body));
+    }
+
+    let envelope: SuccessResponse<Value> = match serde_json::from_str(&body) {
+        Ok(envelope) => envelope,
+        Err(_) => return Err(Error::Unexpected { status, body }),
+    };
+    serde_json::from_value(envelope.data.unwrap_or(Value::Null))
+        .map_err(|_| Error::Unexpected { status, body })
+}
+
+// Build the error for a failed response, keeping validation field errors when present
+fn error_from(status: StatusCode, body: &str) -> Error {
+    let envelope = match serde_json::from_str::<SuccessResponse<Value>>(body) {
+        Ok(envelope) => envelope,
+        Err(_) => {
+            return Error::Unexpected {
+                status,
+                body: body.to_string(),
+            }
+        }
+    };
+    let data = envelope.data.unwrap_or(Value::Null);
+
+    // Saving a page twice answers with the saved item
+    if status == StatusCode::CONFLICT {
+        if let Ok(existing) = serde_json::from_value::<ContentResponse>(data.clone()) {
+            return Error::Conflict(existing);
+        }
+    }
+    Error::Api {
+        status,
+        message: envelope.message,
+        fields: serde_json::from_value(data).unwrap_or_default(),
+    }
+}
+
+// Value of cookie `name` in a Set-Cookie header, e.g. "csrf_token=abc; Path=/; ..."
+fn cookie_value(set_cookie: &str, name: &str) -> Option<String> {
+    let (key, value) = set_cookie.split(';').next()?.split_once('=')?;
+    (key.trim() == name).then(|| value.trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conflict_carries_the_saved_item() {
+        let body = r#"{"success":false,"message":"Content with this URL is already saved","data":
+            {"id":7,"type_":"Article","title":"Rust","link":"abc","url":"https://rust-lang.org",
+            "tags":["lang"],"read":false}}"#;
+        match error_from(StatusCode::CONFLICT, body) {
+            Error::Conflict(existing) => {
+                assert_eq!(existing.id, 7);
+                assert_eq!(existing.url.as_deref(), Some("https://rust-lang.org"));
+            }
+            other => panic!("expected a conflict, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn conflict_without_item_is_an_api_error() {
+        let body = r#"{"success":false,"message":"Username already exists","data":null}"#;
+        let error = error_from(StatusCode::CONFLICT, body);
+        assert!(
+            matches!(error, Error::Api { ref message, .. } if message == "Username already exists")
+        );
+    }
+
+    #[test]
+    fn validation_errors_keep_their_fields() {
+        let body = r#"{"success":false,"message":"Invalid input","data":{"title":["too long"]}}"#;
+        match error_from(StatusCode::UNPROCESSABLE_ENTITY, body) {
+            Error::Api { fields, .. } => assert_eq!(fields["title"], vec!["too long"]),
+            other => panic!("expected an API error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn non_json_body_is_unexpected() {
+        let error = error_from(StatusCode::BAD_GATEWAY, "<html>Bad Gateway</html>");
+        assert!(matches!(error, Error::Unexpected { .. }));
+    }
+}
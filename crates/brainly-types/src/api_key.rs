@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+// Permissions that can be granted to an API key
+#[derive(Serialize, Deserialize, Debug, Display, EnumString, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub enum Scope {
+    #[serde(rename = "content:read")]
+    #[strum(serialize = "content:read")]
+    ContentRead,
+    #[serde(rename = "content:write")]
+    #[strum(serialize = "content:write")]
+    ContentWrite,
+}
+
+// Request payload for creating an API key
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+pub struct CreateApiKey {
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(
+            min = 1,
+            max = 100,
+            message = "Name must be between 1 and 100 characters"
+        ))
+    )]
+    pub name: String, // Human readable label, e.g. "laptop script"
+    pub scopes: Vec<Scope>,                // Permissions granted to the key
+    pub expires_at: Option<DateTime<Utc>>, // Optional expiry, the key never expires when omitted
+}
+
+// API key metadata (the key itself is only stored hashed)
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ApiKey {
+    pub id: i32,
+    pub name: String,
+    pub prefix: String, // First characters of the key, to help users recognise it
+    pub scopes: String, // Comma separated list of scopes
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+// Response returned once on creation, the only time the plain key is revealed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CreatedApiKey {
+    pub key: String,
+    #[serde(flatten)]
+    pub metadata: ApiKey,
+}
@@ -0,0 +1,31 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::content::ContentType;
+
+// Turn public sharing of the user's brain on or off
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ShareBrain {
+    pub share: bool,
+}
+
+// Sharing state of a brain. Anyone with the hash can follow the brain's public feeds at
+// /api/v1/brain/{share_hash}/feed.atom and /api/v1/brain/{share_hash}/feed.rss, and open
+// single items at /api/v1/brain/{share_hash}/content/{link}.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BrainShare {
+    pub share_hash: Option<String>, // None while the brain is private
+}
+
+// An item of a shared brain, as anyone with its link sees it
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SharedContent {
+    pub type_: ContentType,        // Type of content
+    pub title: String,             // Title of the content
+    pub url: Option<String>,       // Source URL of the saved item
+    pub tags: Vec<String>,         // Labels for the item
+    pub created_at: DateTime<Utc>, // When the item was saved
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+#[cfg(feature = "validate")]
+use crate::validation::{validate_collection, validate_http_url, validate_tags, validate_title};
+
+// Define the possible content types using an enum
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[strum(serialize_all = "PascalCase")] // PascalCase serialization for compatibility with external systems
+pub enum ContentType {
+    Image,
+    Video,
+    Article,
+    Audio,
+}
+
+// Struct representing the content creation request payload
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+pub struct Content {
+    pub type_: ContentType, // Type of content (e.g., Image, Video)
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(
+                min = 1,
+                max = 256,
+                message = "Title must be between 1 and 256 characters"
+            ),
+            custom(function = "validate_title")
+        )
+    )]
+    pub title: String, // Title of the content
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(max = 2048, message = "URL must be at most 2048 characters"),
+            custom(function = "validate_http_url")
+        )
+    )]
+    pub url: Option<String>, // Source URL of the saved item, if any
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(max = 20, message = "At most 20 tags are allowed"),
+            custom(function = "validate_tags")
+        )
+    )]
+    pub tags: Vec<String>, // Labels for the item, matched case-insensitively
+    #[serde(default)]
+    pub read: bool, // Whether the item has been read, new items are unread
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(custom(function = "validate_collection"))
+    )]
+    pub collection: Option<String>, // Collection to file the item under, none by default
+    #[serde(default)]
+    pub visibility: Visibility, // Whether the shared brain shows the item, shared by default
+}
+
+// What creating content does when the user already saved the same page
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum OnDuplicate {
+    #[default]
+    Reject, // Answer 409 with the existing item
+    Merge, // Add the new tags to the existing item and return it
+}
+
+// Who can see an item
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Display, EnumString)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum Visibility {
+    #[default]
+    Shared, // Listed in the shared brain and its feeds, while the brain is shared
+    Private, // Only seen by its owner
+}
+
+// Query parameters of content creation
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct CreateOptions {
+    #[serde(default)]
+    pub on_duplicate: OnDuplicate,
+}
+
+// Query parameters of content listing
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct ListOptions {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub q: Option<String>, // Only items whose title, URL or a tag contains this text, ignoring case
+}
+
+// Struct representing the response for a single content item
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ContentResponse {
+    pub id: i32,             // ID of the new content
+    pub type_: ContentType,  // Type of content
+    pub title: String,       // Title of the content
+    pub link: String,        // Generated unique link for the content
+    pub url: Option<String>, // Source URL of the saved item
+    pub tags: Vec<String>,   // Labels for the item
+    pub read: bool,          // Whether the item has been read
+}
+
+// Struct representing content data stored in the database
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct UserContents {
+    pub id: i32,             // Unique ID of the content
+    pub title: String,       // Title of the content
+    pub type_: ContentType,  // Type of content
+    pub link: String,        // Link to access the content
+    pub url: Option<String>, // Source URL of the saved item
+    #[serde(default)]
+    pub tags: Vec<String>, // Labels for the item
+    #[serde(default)]
+    pub read: bool, // Whether the item has been read
+    #[serde(default)]
+    pub collection: Option<String>, // Collection the item is filed under
+    #[serde(default)]
+    pub visibility: Visibility, // Whether the shared brain shows the item
+}
+
+// An item in the trash, purged for good once the retention period has passed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct TrashedContent {
+    #[serde(flatten)]
+    pub content: UserContents,
+    pub deleted_at: DateTime<Utc>, // When the item was moved to the trash
+    pub purge_at: DateTime<Utc>,   // When the item will be deleted permanently
+}
+
+// Change made by a bulk content request
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum BulkAction {
+    Create,        // Save every entry of `items`
+    Delete,        // Move every item of `ids` to the trash
+    Tag,           // Add `tags` to every item of `ids`
+    Untag,         // Remove `tags` from every item of `ids`
+    Move,          // File every item of `ids` under `collection`, or under none when it is null
+    SetVisibility, // Give every item of `ids` the `visibility`
+}
+
+// Request payload for applying one action to many items in a single transaction
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+pub struct BulkContent {
+    pub action: BulkAction,
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(max = 100, message = "At most 100 items are allowed per request"))
+    )]
+    pub ids: Vec<i32>, // Items to delete, tag, untag, move or change the visibility of
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(length(max = 100, message = "At most 100 items are allowed per request"))
+    )]
+    pub items: Vec<Content>, // Items to create, each validated on its own
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(max = 20, message = "At most 20 tags are allowed"),
+            custom(function = "validate_tags")
+        )
+    )]
+    pub tags: Vec<String>, // Tags to add or remove
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(custom(function = "validate_collection"))
+    )]
+    pub collection: Option<String>, // Collection to move the items to
+    #[serde(default)]
+    pub visibility: Option<Visibility>, // Visibility to give the items
+    #[serde(default)]
+    pub all_or_nothing: bool, // Roll every change back when any item fails
+}
+
+// What happened to one item of a bulk request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkItemResult {
+    pub index: usize,    // 0-based position of the item in `ids` or `items`
+    pub id: Option<i32>, // ID of the item, for created items the new one
+    pub success: bool,   // Whether the action applied to the item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub link: Option<String>, // Generated link of a created item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // Why the action failed for the item
+}
+
+// Outcome of a bulk request
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct BulkResult {
+    pub applied: bool, // False when `all_or_nothing` rolled the changes back
+    pub succeeded: u32,
+    pub failed: u32,
+    pub results: Vec<BulkItemResult>, // One per item, in request order
+}
+
+// Single content lookups return the raw row as a JSON array: (id, title, type_, link, url)
+pub type ContentRow = (i32, String, String, String, Option<String>);
+
+// Implement helper functions for ContentType
+impl ContentType {
+    // Convert enum variant to its string representation
+    pub fn enum_to_string(&self) -> String {
+        match self {
+            ContentType::Image => "Image".to_owned(),
+            ContentType::Video => "Video".to_owned(),
+            ContentType::Article => "Article".to_owned(),
+            ContentType::Audio => "Audio".to_owned(),
+        }
+    }
+
+    // Convert a string to an enum variant, if valid
+    pub fn enum_from_string(value: &str) -> Option<ContentType> {
+        match value {
+            "Image" => Some(ContentType::Image),
+            "Video" => Some(ContentType::Video),
+            "Article" => Some(ContentType::Article),
+            "Audio" => Some(ContentType::Audio),
+            _ => None,
+        }
+    }
+}
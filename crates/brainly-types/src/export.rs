@@ -0,0 +1,47 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::content::{ContentType, Visibility};
+
+// Format version of `Export`, bumped whenever a field changes meaning or is removed
+pub const EXPORT_VERSION: u32 = 1;
+
+// Everything a user saved, as returned by GET /api/v1/user/export
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Export {
+    pub version: u32,               // Format version, see EXPORT_VERSION
+    pub exported_at: DateTime<Utc>, // When the export was generated
+    pub user: ExportedUser,         // Owner of the content
+    #[serde(default)]
+    pub collections: Vec<String>, // Names of the collections items are filed under, sorted
+    pub contents: Vec<ExportedContent>, // Every saved item, oldest first
+}
+
+// Profile of the exporting user
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExportedUser {
+    pub username: String,
+    pub created_at: DateTime<Utc>,
+}
+
+// A saved item with all of its fields
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ExportedContent {
+    pub id: i32,             // ID on the exporting server, not kept on import
+    pub type_: ContentType,  // Type of content
+    pub title: String,       // Title of the content
+    pub url: Option<String>, // Source URL of the saved item
+    pub link: String,        // Shareable link on the exporting server
+    #[serde(default)]
+    pub tags: Vec<String>, // Labels for the item
+    #[serde(default)]
+    pub read: bool, // Whether the item has been read, absent in older exports
+    #[serde(default)]
+    pub collection: Option<String>, // Collection the item is filed under
+    #[serde(default)]
+    pub visibility: Visibility, // Whether the shared brain shows the item
+    pub created_at: DateTime<Utc>, // When the item was saved
+}
@@ -0,0 +1,11 @@
+use serde::{Deserialize, Serialize};
+
+// Result of probing a single dependency, as reported by /readyz
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct CheckResult {
+    pub status: String, // "up" or "down"
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
@@ -0,0 +1,68 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use strum_macros::{Display, EnumString};
+
+// File formats accepted by POST /api/v1/user/import
+#[derive(Serialize, Deserialize, Debug, Display, EnumString, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ImportFormat {
+    Json,      // brainly's own export, see `export::Export`
+    Csv,       // Header row with a `url` column and optional `title`, `type`, `tags`, `folder`
+    Bookmarks, // Netscape bookmark file, as exported by every major browser
+    Pocket,    // Pocket export, CSV or the older HTML file
+    Raindrop,  // Raindrop.io CSV export
+    Pinboard,  // Pinboard JSON export
+}
+
+// Progress of an import job
+#[derive(Serialize, Deserialize, Debug, Display, EnumString, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum ImportStatus {
+    Pending,
+    Running,
+    Completed,
+    Failed, // The job stopped before finishing, see `ImportJob::error`
+}
+
+// What happened to a single row of the uploaded file
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum ImportOutcome {
+    Created,
+    Skipped, // Already saved, or repeated earlier in the file
+    Failed,  // Invalid row or database error
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportRowReport {
+    pub row: usize,              // 1-based position of the item in the file
+    pub url: Option<String>,     // URL of the row, when it had one
+    pub outcome: ImportOutcome,  // Created, skipped or failed
+    pub content_id: Option<i32>, // ID of the created item
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>, // Why the row was skipped or failed
+}
+
+// An import job and, once it has finished, its report
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct ImportJob {
+    pub id: i32,
+    pub format: ImportFormat,
+    pub status: ImportStatus,
+    pub created: u32, // Rows saved as new content
+    pub skipped: u32, // Rows whose URL was already saved
+    pub failed: u32,  // Rows that could not be saved
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // Why the whole job failed
+    #[serde(default)]
+    pub rows: Vec<ImportRowReport>, // Outcome of every row, empty until the job has finished
+    pub created_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,23 @@
+// Request and response types of the brainly HTTP API, shared by the server,
+// the `brainly-client` SDK and the command-line tools
+use serde::{Deserialize, Serialize};
+
+pub mod api_key;
+pub mod brain;
+pub mod content;
+pub mod export;
+pub mod health;
+pub mod import;
+pub mod subscription;
+pub mod user;
+#[cfg(feature = "validate")]
+pub mod validation;
+
+// Envelope wrapping every JSON response
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct SuccessResponse<T> {
+    pub success: bool,
+    pub message: String,
+    pub data: Option<T>,
+}
@@ -0,0 +1,44 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "validate")]
+use crate::validation::{validate_http_url, validate_tags};
+
+// Request payload for subscribing to an RSS or Atom feed
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+pub struct CreateSubscription {
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(max = 2048, message = "URL must be at most 2048 characters"),
+            custom(function = "validate_http_url")
+        )
+    )]
+    pub url: String, // URL of the feed document
+    #[serde(default)]
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(max = 20, message = "At most 20 tags are allowed"),
+            custom(function = "validate_tags")
+        )
+    )]
+    pub tags: Vec<String>, // Added to every item saved from the feed
+}
+
+// A feed subscription and how polling it is going
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+pub struct Subscription {
+    pub id: i32,
+    pub url: String,
+    pub title: Option<String>, // Read from the feed once it has been fetched
+    pub tags: Vec<String>,
+    pub error_count: u32, // Failed fetches in a row, polling backs off while non-zero
+    pub last_error: Option<String>, // Why the latest fetch failed
+    pub last_fetched_at: Option<DateTime<Utc>>, // Latest successful fetch
+    pub next_fetch_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "validate")]
+use crate::validation::{validate_password, validate_username};
+
+// Credentials sent to sign up and sign in
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[cfg_attr(feature = "openapi", derive(utoipa::ToSchema))]
+#[cfg_attr(feature = "validate", derive(validator::Validate))]
+pub struct CreateUser {
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(
+                min = 3,
+                max = 50,
+                message = "Username must be between 3 and 50 characters"
+            ),
+            custom(function = "validate_username")
+        )
+    )]
+    pub username: String,
+    #[cfg_attr(
+        feature = "validate",
+        validate(
+            length(
+                min = 8,
+                max = 128,
+                message = "Password must be between 8 and 128 characters"
+            ),
+            custom(function = "validate_password")
+        )
+    )]
+    pub password: String,
+}
+
+impl CreateUser {
+    // Build a payload outside of a request, e.g. from the admin CLI, so it gets the same validation
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> CreateUser {
+        CreateUser {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+}
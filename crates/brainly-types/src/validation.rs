@@ -0,0 +1,108 @@
+// Input rules shared by the server and clients that want to validate before sending
+use url::Url;
+use validator::ValidationError;
+
+// Build a validation error with a human readable message
+fn invalid(code: &'static str, message: &'static str) -> ValidationError {
+    ValidationError::new(code).with_message(message.into())
+}
+
+// Usernames may only contain ASCII letters, digits, '_', '-' and '.'
+pub fn validate_username(username: &str) -> Result<(), ValidationError> {
+    let allowed = |c: char| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.');
+    if !username.chars().all(allowed) {
+        return Err(invalid(
+            "charset",
+            "Username may only contain letters, digits, '_', '-' and '.'",
+        ));
+    }
+    Ok(())
+}
+
+// Passwords must mix letters and digits, length is checked separately
+pub fn validate_password(password: &str) -> Result<(), ValidationError> {
+    let has_letter = password.chars().any(|c| c.is_alphabetic());
+    let has_digit = password.chars().any(|c| c.is_ascii_digit());
+    if !has_letter || !has_digit {
+        return Err(invalid(
+            "strength",
+            "Password must contain at least one letter and one digit",
+        ));
+    }
+    if password.trim().len() != password.len() {
+        return Err(invalid(
+            "whitespace",
+            "Password must not start or end with whitespace",
+        ));
+    }
+    Ok(())
+}
+
+// Only absolute http(s) URLs with a host are accepted
+pub fn validate_http_url(url: &str) -> Result<(), ValidationError> {
+    match Url::parse(url) {
+        Ok(parsed) if matches!(parsed.scheme(), "http" | "https") && parsed.host().is_some() => {
+            Ok(())
+        }
+        _ => Err(invalid("url", "Must be a valid http or https URL")),
+    }
+}
+
+// Titles need some visible text, whitespace alone is not a title
+pub fn validate_title(title: &str) -> Result<(), ValidationError> {
+    if title.trim().is_empty() {
+        return Err(invalid("blank", "Title must not be blank"));
+    }
+    Ok(())
+}
+
+// Tags must be non-empty, at most 64 characters and free of surrounding whitespace
+pub fn validate_tags(tags: &[String]) -> Result<(), ValidationError> {
+    for tag in tags {
+        if tag.is_empty() || tag.chars().count() > 64 {
+            return Err(invalid("tag", "Tags must be between 1 and 64 characters"));
+        }
+        if tag.trim().len() != tag.len() {
+            return Err(invalid("tag", "Tags must not start or end with whitespace"));
+        }
+    }
+    Ok(())
+}
+
+// Collection names follow the tag rules: 1 to 64 characters, no surrounding whitespace
+pub fn validate_collection(collection: &str) -> Result<(), ValidationError> {
+    if collection.is_empty() || collection.chars().count() > 64 {
+        return Err(invalid(
+            "collection",
+            "Collection names must be between 1 and 64 characters",
+        ));
+    }
+    if collection.trim().len() != collection.len() {
+        return Err(invalid(
+            "collection",
+            "Collection names must not start or end with whitespace",
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_blank_titles() {
+        assert!(validate_title("Rust 2024").is_ok());
+        assert!(validate_title(" padded ").is_ok());
+        assert!(validate_title("   ").is_err());
+        assert!(validate_title("\t\n").is_err());
+    }
+
+    #[test]
+    fn checks_collection_names() {
+        assert!(validate_collection("Reading list").is_ok());
+        assert!(validate_collection("").is_err());
+        assert!(validate_collection(" padded").is_err());
+        assert!(validate_collection(&"x".repeat(65)).is_err());
+    }
+}
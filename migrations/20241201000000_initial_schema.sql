@@ -0,0 +1,16 @@
+-- The schema of tables.sql before migrations existed. Databases created from it already
+-- have these tables, so the migration leaves them alone.
+CREATE TABLE IF NOT EXISTS `users`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `username` VARCHAR(50) UNIQUE NOT NULL,
+    `password` VARCHAR(256) NOT NULL
+);
+
+CREATE TABLE IF NOT EXISTS `contents`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `link` VARCHAR(256) NOT NULL,
+    `type_` VARCHAR(50) NOT NULL,
+    `title` VARCHAR(256) NOT NULL,
+    `user_id` INT NOT NULL,
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+);
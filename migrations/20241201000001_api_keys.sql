@@ -0,0 +1,14 @@
+-- Long-lived keys for scripts and integrations. Only the SHA-256 of a key is stored, the
+-- prefix is kept so users can tell their keys apart.
+CREATE TABLE `api_keys`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `name` VARCHAR(100) NOT NULL,
+    `prefix` VARCHAR(16) NOT NULL,
+    `key_hash` CHAR(64) UNIQUE NOT NULL,
+    `scopes` VARCHAR(256) NOT NULL,
+    `expires_at` TIMESTAMP NULL,
+    `last_used_at` TIMESTAMP NULL,
+    `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,11 @@
+-- Accounts at OpenID Connect providers that sign users in, identified by issuer and subject
+CREATE TABLE `user_identities`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `issuer` VARCHAR(256) NOT NULL,
+    `subject` VARCHAR(256) NOT NULL,
+    `email` VARCHAR(256) NULL,
+    `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (`issuer`, `subject`),
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+);
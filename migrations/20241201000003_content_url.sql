@@ -0,0 +1,3 @@
+-- Source URL of a saved item, NULL for items saved without one
+ALTER TABLE `contents`
+    ADD COLUMN `url` VARCHAR(2048) NULL;
@@ -0,0 +1,4 @@
+-- Account creation time, and when an operator disabled the account (NULL while active)
+ALTER TABLE `users`
+    ADD COLUMN `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    ADD COLUMN `disabled_at` TIMESTAMP NULL;
@@ -0,0 +1,8 @@
+-- Free-form labels attached to saved content
+CREATE TABLE `content_tags`(
+    `content_id` INT NOT NULL,
+    `tag` VARCHAR(64) NOT NULL,
+    PRIMARY KEY (`content_id`, `tag`),
+    INDEX (`tag`),
+    FOREIGN KEY (`content_id`) REFERENCES `contents`(`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- When each item was saved. Existing rows get the time of the migration.
+ALTER TABLE `contents`
+    ADD COLUMN `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
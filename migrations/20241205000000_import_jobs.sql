@@ -0,0 +1,16 @@
+-- Background imports of uploaded files. `report` holds the per-row outcome as JSON once done.
+CREATE TABLE `import_jobs`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `format` VARCHAR(32) NOT NULL,
+    `status` VARCHAR(16) NOT NULL DEFAULT 'pending',
+    `created` INT NOT NULL DEFAULT 0,
+    `skipped` INT NOT NULL DEFAULT 0,
+    `failed` INT NOT NULL DEFAULT 0,
+    `error` VARCHAR(1024) NULL,
+    `report` MEDIUMTEXT NULL,
+    `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `finished_at` TIMESTAMP NULL,
+    INDEX (`user_id`, `created_at`),
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+);
@@ -0,0 +1,3 @@
+-- Whether the user has read the item, carried over from read-later services on import
+ALTER TABLE `contents`
+    ADD COLUMN `is_read` BOOLEAN NOT NULL DEFAULT FALSE;
@@ -0,0 +1,4 @@
+-- Public sharing of a user's brain: the unguessable hash in its share URLs, NULL while private
+ALTER TABLE `users`
+    ADD COLUMN `share_hash` VARCHAR(32) NULL UNIQUE,
+    ADD COLUMN `shared_at` TIMESTAMP NULL;
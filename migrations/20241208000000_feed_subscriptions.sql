@@ -0,0 +1,39 @@
+-- RSS and Atom feeds users subscribe to. The poller fetches a feed once `next_fetch_at` has
+-- passed, and pushes it further out while fetches keep failing.
+CREATE TABLE `feeds`(
+    `id` INT NOT NULL AUTO_INCREMENT PRIMARY KEY,
+    `user_id` INT NOT NULL,
+    `url` VARCHAR(2048) NOT NULL,
+    `url_hash` CHAR(64) NOT NULL, -- SHA-256 of the URL, the URL itself is too long to index
+    `title` VARCHAR(256) NULL,
+    `etag` VARCHAR(256) NULL,         -- Validators of the last response, for conditional requests
+    `last_modified` VARCHAR(64) NULL,
+    `error_count` INT NOT NULL DEFAULT 0,
+    `last_error` VARCHAR(1024) NULL,
+    `last_fetched_at` TIMESTAMP NULL,
+    `next_fetch_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (`user_id`, `url_hash`),
+    INDEX (`next_fetch_at`),
+    FOREIGN KEY (`user_id`) REFERENCES `users`(`id`) ON DELETE CASCADE
+);
+
+-- Tags added to every item saved from a feed
+CREATE TABLE `feed_tags`(
+    `feed_id` INT NOT NULL,
+    `tag` VARCHAR(64) NOT NULL,
+    PRIMARY KEY (`feed_id`, `tag`),
+    FOREIGN KEY (`feed_id`) REFERENCES `feeds`(`id`) ON DELETE CASCADE
+);
+
+-- Entries the poller has seen, so each is saved at most once. `content_id` is NULL for
+-- entries that were not saved: already in the feed when subscribing, or already saved.
+CREATE TABLE `feed_entries`(
+    `feed_id` INT NOT NULL,
+    `entry_hash` CHAR(64) NOT NULL, -- SHA-256 of the entry's ID, or of its link without one
+    `content_id` INT NULL,
+    `created_at` TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (`feed_id`, `entry_hash`),
+    FOREIGN KEY (`feed_id`) REFERENCES `feeds`(`id`) ON DELETE CASCADE,
+    FOREIGN KEY (`content_id`) REFERENCES `contents`(`id`) ON DELETE SET NULL
+);
@@ -0,0 +1,5 @@
+-- When an item was moved to the trash, NULL while it is live. Trashed items are hidden
+-- everywhere but the trash, and purged for good once the retention period has passed.
+ALTER TABLE `contents`
+    ADD COLUMN `deleted_at` TIMESTAMP NULL,
+    ADD INDEX (`deleted_at`);
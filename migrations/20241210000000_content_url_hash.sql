@@ -0,0 +1,11 @@
+-- Hash of the canonical form of an item's URL (see src/canonical_url.rs), NULL for items
+-- without a URL. Items saved before this migration are hashed on startup.
+ALTER TABLE `contents`
+    ADD COLUMN `url_hash` CHAR(64) NULL;
+
+-- A page can be saved once per user. Trashed items are left out, so saving a page again
+-- after deleting it works, while restoring the old copy then conflicts.
+ALTER TABLE `contents`
+    ADD COLUMN `live_url_hash` CHAR(64)
+        GENERATED ALWAYS AS (IF(`deleted_at` IS NULL, `url_hash`, NULL)) STORED,
+    ADD UNIQUE INDEX `contents_user_url_hash` (`user_id`, `live_url_hash`);
@@ -0,0 +1,5 @@
+-- Collection an item is filed under, NULL for none, and whether it appears in the shared
+-- brain ('shared') or only to its owner ('private')
+ALTER TABLE `contents`
+    ADD COLUMN `collection` VARCHAR(64) NULL,
+    ADD COLUMN `visibility` VARCHAR(16) NOT NULL DEFAULT 'shared';
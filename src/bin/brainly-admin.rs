@@ -0,0 +1,291 @@
+// Operator tool for inspecting and fixing accounts without writing raw SQL.
+// Reads the same environment variables as the server (DATABASE_URL, ARGON2_*, ...).
+use std::error::Error;
+
+use brainly::config::Config;
+use brainly::database::{database_connetion, MIGRATOR};
+use brainly::routes::{
+    jwt::generate_token,
+    utils::{encrypt_password, generate_random_string},
+};
+use brainly_types::user::CreateUser;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand};
+use sqlx::MySqlPool;
+use validator::Validate;
+
+type AdminResult<T> = Result<T, Box<dyn Error>>;
+
+// User row as listed: (id, username, created_at, disabled_at, content count)
+type UserRow = (i32, String, DateTime<Utc>, Option<DateTime<Utc>>, i64);
+
+#[derive(Parser)]
+#[command(name = "brainly-admin", about = "Manage brainly users and data")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Manage user accounts
+    #[command(subcommand)]
+    User(UserCommand),
+    /// Apply pending database migrations
+    Migrate,
+    /// Delete every content item saved by a user
+    PurgeContent {
+        username: String,
+        /// Confirm the deletion, nothing is deleted without it
+        #[arg(long)]
+        yes: bool,
+    },
+    /// Issue a session token for a user, for debugging
+    Token { username: String },
+    /// Print row counts for users, content and credentials
+    Stats,
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Create a user, generating a password when none is given
+    Create {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+    /// List every user with their content count
+    List,
+    /// Block sign in and every existing session and API key of a user
+    Disable { username: String },
+    /// Re-enable a disabled user
+    Enable { username: String },
+    /// Set a new password, generating one when none is given
+    ResetPassword {
+        username: String,
+        #[arg(long)]
+        password: Option<String>,
+    },
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli.command).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(command: Command) -> AdminResult<()> {
+    let config = Config::from_env();
+    let db = database_connetion(&config.database).await?;
+
+    match command {
+        Command::User(UserCommand::Create { username, password }) => {
+            let (password, generated) = password_or_generated(password);
+            // Same rules as the signup endpoint
+            CreateUser::new(username.clone(), password.clone()).validate()?;
+            if find_user(&db, &username).await.is_ok() {
+                return Err(format!("user {} already exists", username).into());
+            }
+
+            let hash = encrypt_password(&password, &config.password).await?;
+            let id = sqlx::query("INSERT INTO users (username, password) VALUES (?, ?)")
+                .bind(&username)
+                .bind(hash)
+                .execute(&db)
+                .await?
+                .last_insert_id();
+            println!("Created user {} with ID {}", username, id);
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        Command::User(UserCommand::List) => {
+            let users: Vec<UserRow> = sqlx::query_as(
+                "SELECT u.id, u.username, u.created_at, u.disabled_at, COUNT(c.id)
+                 FROM users u LEFT JOIN contents c ON c.user_id = u.id
+                 GROUP BY u.id, u.username, u.created_at, u.disabled_at
+                 ORDER BY u.id",
+            )
+            .fetch_all(&db)
+            .await?;
+
+            println!(
+                "{:>6}  {:<50}  {:<20}  {:<8}  {:>7}",
+                "ID", "USERNAME", "CREATED", "STATUS", "CONTENT"
+            );
+            for (id, username, created_at, disabled_at, contents) in users {
+                let status = if disabled_at.is_some() {
+                    "disabled"
+                } else {
+                    "active"
+                };
+                println!(
+                    "{:>6}  {:<50}  {:<20}  {:<8}  {:>7}",
+                    id,
+                    username,
+                    created_at.format("%Y-%m-%d %H:%M:%S"),
+                    status,
+                    contents
+                );
+            }
+        }
+        Command::User(UserCommand::Disable { username }) => {
+            let (id, _) = find_user(&db, &username).await?;
+            sqlx::query("UPDATE users SET disabled_at = CURRENT_TIMESTAMP WHERE id = ? AND disabled_at IS NULL")
+                .bind(id)
+                .execute(&db)
+                .await?;
+            println!(
+                "Disabled user {}, open sessions end within 30 seconds",
+                username
+            );
+        }
+        Command::User(UserCommand::Enable { username }) => {
+            let (id, _) = find_user(&db, &username).await?;
+            sqlx::query("UPDATE users SET disabled_at = NULL WHERE id = ?")
+                .bind(id)
+                .execute(&db)
+                .await?;
+            println!("Enabled user {}", username);
+        }
+        Command::User(UserCommand::ResetPassword { username, password }) => {
+            let (id, _) = find_user(&db, &username).await?;
+            let (password, generated) = password_or_generated(password);
+            // Accounts may predate the username rules, so only the new password is checked
+            if let Err(mut errors) = CreateUser::new(username.clone(), password.clone()).validate()
+            {
+                errors.errors_mut().remove("username");
+                if !errors.is_empty() {
+                    return Err(errors.into());
+                }
+            }
+
+            let hash = encrypt_password(&password, &config.password).await?;
+            sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+                .bind(hash)
+                .bind(id)
+                .execute(&db)
+                .await?;
+            println!("Password reset for {}", username);
+            if generated {
+                println!("Password: {}", password);
+            }
+        }
+        Command::Migrate => {
+            let before = applied_migrations(&db).await?;
+            MIGRATOR.run(&db).await?;
+            let applied = applied_migrations(&db).await? - before;
+            println!("Applied {} migration(s)", applied);
+        }
+        Command::PurgeContent { username, yes } => {
+            let (id, _) = find_user(&db, &username).await?;
+            if !yes {
+                let count: i64 =
+                    sqlx::query_scalar("SELECT COUNT(*) FROM contents WHERE user_id = ?")
+                        .bind(id)
+                        .fetch_one(&db)
+                        .await?;
+                return Err(format!(
+                    "this would delete {} content item(s) of {}, re-run with --yes to confirm",
+                    count, username
+                )
+                .into());
+            }
+
+            let deleted = sqlx::query("DELETE FROM contents WHERE user_id = ?")
+                .bind(id)
+                .execute(&db)
+                .await?
+                .rows_affected();
+            println!("Deleted {} content item(s) of {}", deleted, username);
+        }
+        Command::Token { username } => {
+            let (id, disabled_at) = find_user(&db, &username).await?;
+            if disabled_at.is_some() {
+                return Err(
+                    format!("user {} is disabled, the token would be rejected", username).into(),
+                );
+            }
+            // Printed alone so it can be captured, e.g. TOKEN=$(brainly-admin token alice)
+            println!("{}", generate_token(id));
+            eprintln!("Valid for 2 hours, send it as `Authorization: Bearer <token>`");
+        }
+        Command::Stats => {
+            let count = |sql: &'static str| {
+                let db = db.clone();
+                async move { sqlx::query_scalar::<_, i64>(sql).fetch_one(&db).await }
+            };
+
+            println!(
+                "users            {}",
+                count("SELECT COUNT(*) FROM users").await?
+            );
+            println!(
+                "  disabled       {}",
+                count("SELECT COUNT(*) FROM users WHERE disabled_at IS NOT NULL").await?
+            );
+            println!(
+                "contents         {}",
+                count("SELECT COUNT(*) FROM contents").await?
+            );
+            let by_type: Vec<(String, i64)> = sqlx::query_as(
+                "SELECT type_, COUNT(*) FROM contents GROUP BY type_ ORDER BY type_",
+            )
+            .fetch_all(&db)
+            .await?;
+            for (type_, contents) in by_type {
+                println!("  {:<15}{}", type_, contents);
+            }
+            println!(
+                "api keys         {}",
+                count("SELECT COUNT(*) FROM api_keys WHERE expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP").await?
+            );
+            println!(
+                "oidc identities  {}",
+                count("SELECT COUNT(*) FROM user_identities").await?
+            );
+        }
+    }
+
+    db.close().await;
+    Ok(())
+}
+
+// Look up a user's ID and disabled time by username
+async fn find_user(db: &MySqlPool, username: &str) -> AdminResult<(i32, Option<DateTime<Utc>>)> {
+    sqlx::query_as("SELECT id, disabled_at FROM users WHERE username = ?")
+        .bind(username)
+        .fetch_optional(db)
+        .await?
+        .ok_or_else(|| format!("user {} not found", username).into())
+}
+
+// Use the given password, or generate one that satisfies the password rules
+fn password_or_generated(password: Option<String>) -> (String, bool) {
+    match password {
+        Some(password) => (password, false),
+        // Random alphanumerics may lack a letter or a digit, so append one of each
+        None => (format!("{}a1", generate_random_string(22)), true),
+    }
+}
+
+// Number of migrations recorded as applied, zero before the first run
+async fn applied_migrations(db: &MySqlPool) -> AdminResult<i64> {
+    let exists: bool = sqlx::query_scalar(
+        "SELECT EXISTS(SELECT 1 FROM information_schema.tables
+         WHERE table_schema = DATABASE() AND table_name = '_sqlx_migrations')",
+    )
+    .fetch_one(db)
+    .await?;
+    if !exists {
+        return Ok(0);
+    }
+    Ok(
+        sqlx::query_scalar("SELECT COUNT(*) FROM _sqlx_migrations WHERE success = TRUE")
+            .fetch_one(db)
+            .await?,
+    )
+}
@@ -0,0 +1,354 @@
+// Command-line client for the brainly HTTP API.
+// `brainly login` stores an API key in the user's config directory, every other command
+// sends it in the X-Api-Key header. BRAINLY_SERVER and BRAINLY_API_KEY override the
+// stored values.
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, BufRead, Write},
+    path::PathBuf,
+};
+
+use brainly_client::{
+    types::{
+        api_key::{CreateApiKey, Scope},
+        content::{self, UserContents},
+        user::CreateUser,
+    },
+    Auth, Client,
+};
+use clap::{Parser, Subcommand, ValueEnum};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+
+type CliResult<T> = Result<T, Box<dyn Error>>;
+
+const DEFAULT_SERVER: &str = "http://127.0.0.1:8080";
+
+#[derive(Parser)]
+#[command(name = "brainly", about = "Save and find links from the terminal")]
+struct Cli {
+    /// Print JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sign in and store an API key for the other commands
+    Login {
+        username: String,
+        /// Base URL of the brainly server
+        #[arg(long, default_value = DEFAULT_SERVER)]
+        server: String,
+        /// Read the password from stdin instead of prompting for it
+        #[arg(long)]
+        password_stdin: bool,
+    },
+    /// Forget the stored API key
+    Logout,
+    /// Save a link
+    Add {
+        url: String,
+        #[arg(long = "type", value_enum, default_value_t = ContentType::Article)]
+        type_: ContentType,
+        /// Defaults to the URL
+        #[arg(long)]
+        title: Option<String>,
+        /// Repeat to attach several tags
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+        /// When the URL is already saved, add the tags to that item instead of failing
+        #[arg(long)]
+        merge: bool,
+    },
+    /// List saved content
+    Ls,
+    /// Show one item
+    Show { id: i32 },
+    /// Move an item to the trash
+    Rm { id: i32 },
+    /// Find items whose title, URL or tags contain the query
+    Search { query: String },
+    /// Print a link anyone can open to an item. This shares the whole brain publicly.
+    Share {
+        id: i32,
+        /// Confirm that the brain, with its feeds and every item, may be made public
+        #[arg(long)]
+        public: bool,
+    },
+}
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum ContentType {
+    Image,
+    Video,
+    Article,
+    Audio,
+}
+
+impl From<ContentType> for content::ContentType {
+    fn from(type_: ContentType) -> content::ContentType {
+        match type_ {
+            ContentType::Image => content::ContentType::Image,
+            ContentType::Video => content::ContentType::Video,
+            ContentType::Article => content::ContentType::Article,
+            ContentType::Audio => content::ContentType::Audio,
+        }
+    }
+}
+
+// Stored in <config dir>/brainly/config.json, readable by the owner only
+#[derive(Serialize, Deserialize)]
+struct Credentials {
+    server: String,
+    api_key: String,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+    if let Err(e) = run(cli).await {
+        eprintln!("error: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> CliResult<()> {
+    match cli.command {
+        Command::Login {
+            username,
+            server,
+            password_stdin,
+        } => {
+            let password = if password_stdin {
+                let mut line = String::new();
+                io::stdin().lock().read_line(&mut line)?;
+                line.trim_end_matches(['\r', '\n']).to_string()
+            } else {
+                rpassword::prompt_password("Password: ")?
+            };
+
+            // Sign in for a short-lived session, then trade it for a long-lived API key
+            let mut client = Client::new(server);
+            let session = client
+                .signin(&CreateUser::new(username.clone(), password))
+                .await?;
+            client.set_auth(session.bearer());
+            let key = client
+                .create_api_key(&CreateApiKey {
+                    name: "brainly CLI".to_string(),
+                    scopes: vec![Scope::ContentRead, Scope::ContentWrite],
+                    expires_at: None,
+                })
+                .await?;
+
+            let path = save_credentials(&Credentials {
+                server: client.base_url().to_string(),
+                api_key: key.key,
+            })?;
+            println!(
+                "Signed in as {}, API key stored in {}",
+                username,
+                path.display()
+            );
+        }
+        Command::Logout => {
+            let path = credentials_path()?;
+            if path.exists() {
+                fs::remove_file(&path)?;
+            }
+            // The key stays valid on the server until it is revoked there
+            println!("Signed out, revoke the \"brainly CLI\" API key to invalidate it everywhere");
+        }
+        Command::Add {
+            url,
+            type_,
+            title,
+            tags,
+            merge,
+        } => {
+            let on_duplicate = if merge {
+                content::OnDuplicate::Merge
+            } else {
+                content::OnDuplicate::Reject
+            };
+            let created = client_from_credentials()?
+                .create_content_with(
+                    &content::Content {
+                        type_: type_.into(),
+                        title: title.unwrap_or_else(|| url.clone()),
+                        url: Some(url),
+                        tags,
+                        read: false,
+                        collection: None,
+                        visibility: content::Visibility::default(),
+                    },
+                    on_duplicate,
+                )
+                .await?;
+            if cli.json {
+                println!("{}", serde_json::to_string_pretty(&created)?);
+            } else {
+                println!("Saved as {}", created.id);
+            }
+        }
+        Command::Ls => {
+            let items = client_from_credentials()?.list_content().await?;
+            print_items(&items, cli.json)?;
+        }
+        Command::Show { id } => {
+            let (id, title, type_, link, url) = client_from_credentials()?.get_content(id).await?;
+            if cli.json {
+                let item =
+                    json!({ "id": id, "title": title, "type_": type_, "link": link, "url": url });
+                println!("{}", serde_json::to_string_pretty(&item)?);
+            } else {
+                println!("ID     {}", id);
+                println!("Title  {}", title);
+                println!("Type   {}", type_);
+                println!("URL    {}", url.as_deref().unwrap_or("-"));
+                println!("Link   {}", link);
+            }
+        }
+        Command::Rm { id } => {
+            client_from_credentials()?.delete_content(id).await?;
+            if cli.json {
+                println!("{}", json!({ "id": id, "deleted": true }));
+            } else {
+                println!("Moved {} to the trash", id);
+            }
+        }
+        Command::Search { query } => {
+            let items = client_from_credentials()?.search_content(&query).await?;
+            print_items(&items, cli.json)?;
+        }
+        Command::Share { id, public } => {
+            let client = client_from_credentials()?;
+            let (_, _, _, link, _) = client.get_content(id).await?;
+            if !public {
+                return Err(
+                    "sharing an item makes your whole brain public, including its \
+                            feeds; re-run with --public to confirm"
+                        .into(),
+                );
+            }
+            let share_hash = client
+                .share_brain(true)
+                .await?
+                .share_hash
+                .ok_or("the server did not share the brain")?;
+            let url = client.shared_content_url(&share_hash, &link);
+            if cli.json {
+                println!("{}", json!({ "id": id, "url": url }));
+            } else {
+                println!("{}", url);
+            }
+        }
+    }
+    Ok(())
+}
+
+// Build a client from the environment or the stored credentials
+fn client_from_credentials() -> CliResult<Client> {
+    let stored = load_credentials();
+    let server = env::var("BRAINLY_SERVER")
+        .ok()
+        .or_else(|| stored.as_ref().map(|c| c.server.clone()))
+        .unwrap_or_else(|| DEFAULT_SERVER.to_string());
+    let api_key = env::var("BRAINLY_API_KEY")
+        .ok()
+        .or_else(|| stored.map(|c| c.api_key))
+        .ok_or("not signed in, run `brainly login <username>` first")?;
+    Ok(Client::new(server).with_auth(Auth::ApiKey(api_key)))
+}
+
+fn credentials_path() -> CliResult<PathBuf> {
+    let dir = dirs::config_dir().ok_or("could not determine the config directory")?;
+    Ok(dir.join("brainly").join("config.json"))
+}
+
+fn load_credentials() -> Option<Credentials> {
+    let text = fs::read_to_string(credentials_path().ok()?).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+// Write the credentials so only the current user can read them
+fn save_credentials(credentials: &Credentials) -> CliResult<PathBuf> {
+    let path = credentials_path()?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode above only applies to new files
+        if path.exists() {
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    let mut file = options.open(&path)?;
+    file.write_all(serde_json::to_string_pretty(credentials)?.as_bytes())?;
+    Ok(path)
+}
+
+fn print_items(items: &[UserContents], json: bool) -> CliResult<()> {
+    if json {
+        println!("{}", serde_json::to_string_pretty(items)?);
+        return Ok(());
+    }
+
+    let rows: Vec<[String; 5]> = items
+        .iter()
+        .map(|item| {
+            [
+                item.id.to_string(),
+                item.type_.to_string(),
+                truncate(&item.title, 40),
+                truncate(item.url.as_deref().unwrap_or("-"), 50),
+                item.tags.join(","),
+            ]
+        })
+        .collect();
+    print_table(["ID", "TYPE", "TITLE", "URL", "TAGS"], &rows);
+    Ok(())
+}
+
+// Print left aligned columns sized to their widest cell
+fn print_table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) {
+    let mut widths = headers.map(|header| header.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: [&str; N]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+    print_row(headers);
+    for row in rows {
+        print_row(row.each_ref().map(String::as_str));
+    }
+}
+
+fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_string();
+    }
+    let mut truncated: String = text.chars().take(max - 1).collect();
+    truncated.push('…');
+    truncated
+}
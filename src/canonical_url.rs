@@ -0,0 +1,123 @@
+// Reduce the many spellings of a page's URL to one, so saving the same page twice can be
+// detected. Only the hash of the canonical form is stored; items keep the URL as submitted.
+//
+//   HTTP://WWW.Example.com:80/post/?utm_source=x&b=2&a=1#top  ->  http://example.com/post?a=1&b=2
+//   https://youtu.be/abc?si=xyz                              ->  https://youtube.com/watch?v=abc
+//   https://x.com/user/status/1?s=20                         ->  https://twitter.com/user/status/1
+use sha2::{Digest, Sha256};
+use url::Url;
+
+// Query parameters that only say where a visitor came from, dropped on every site
+const TRACKING_PARAMS: [&str; 12] = [
+    "fbclid", "gclid", "dclid", "gbraid", "wbraid", "msclkid", "yclid", "igshid", "mc_cid",
+    "mc_eid", "_hsenc", "_hsmi",
+];
+
+// Hex SHA-256 of the canonical form of `url`, which identifies the page it points to
+pub fn url_hash(url: &str) -> String {
+    hex::encode(Sha256::digest(canonical_url(url).as_bytes()))
+}
+
+// Canonical form of `url`. Text that does not parse as a URL is its own canonical form.
+pub fn canonical_url(url: &str) -> String {
+    let Ok(mut parsed) = Url::parse(url.trim()) else {
+        return url.trim().to_string();
+    };
+    // Scheme and host are lower-cased and default ports dropped by the parser
+    parsed.set_fragment(None);
+
+    let host = parsed.host_str().unwrap_or_default();
+    let host = host.strip_prefix("www.").unwrap_or(host).to_string();
+    let mut params: Vec<(String, String)> = parsed
+        .query_pairs()
+        .into_owned()
+        .filter(|(name, _)| !is_tracking_param(name))
+        .collect();
+
+    let host = match host.as_str() {
+        // Short links and the mobile site lead to the same watch page
+        "youtu.be" => {
+            let id = parsed.path().trim_matches('/').to_string();
+            parsed.set_path("/watch");
+            params.retain(|(name, _)| name == "t");
+            params.push(("v".to_string(), id));
+            "youtube.com"
+        }
+        "youtube.com" | "m.youtube.com" => {
+            if parsed.path() == "/watch" {
+                params.retain(|(name, _)| matches!(name.as_str(), "v" | "t" | "list"));
+            }
+            "youtube.com"
+        }
+        // `s` and `t` only identify the share
+        "x.com" | "twitter.com" | "mobile.twitter.com" | "mobile.x.com" => {
+            params.retain(|(name, _)| !matches!(name.as_str(), "s" | "t"));
+            "twitter.com"
+        }
+        host => host,
+    };
+    if parsed.set_host(Some(host)).is_err() {
+        return parsed.to_string();
+    }
+
+    let path = parsed.path();
+    if path.len() > 1 && path.ends_with('/') {
+        let trimmed = path.trim_end_matches('/').to_string();
+        parsed.set_path(&trimmed);
+    }
+
+    // Parameter order carries no meaning
+    params.sort();
+    if params.is_empty() {
+        parsed.set_query(None);
+    } else {
+        parsed.query_pairs_mut().clear().extend_pairs(params);
+    }
+    parsed.to_string()
+}
+
+fn is_tracking_param(name: &str) -> bool {
+    let name = name.to_ascii_lowercase();
+    name.starts_with("utm_") || TRACKING_PARAMS.contains(&name.as_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn drops_case_tracking_and_trailing_slash() {
+        assert_eq!(
+            canonical_url("HTTP://WWW.Example.com:80/post/?utm_source=x&b=2&fbclid=1&a=1#top"),
+            "http://example.com/post?a=1&b=2"
+        );
+        assert_eq!(
+            canonical_url("https://example.com/"),
+            "https://example.com/"
+        );
+        assert_eq!(
+            url_hash("https://example.com/a/?utm_medium=email"),
+            url_hash("https://Example.com/a")
+        );
+    }
+
+    #[test]
+    fn joins_site_aliases() {
+        assert_eq!(
+            canonical_url("https://youtu.be/abc?si=xyz&t=42"),
+            "https://youtube.com/watch?t=42&v=abc"
+        );
+        assert_eq!(
+            canonical_url("https://m.youtube.com/watch?feature=share&v=abc&t=42"),
+            "https://youtube.com/watch?t=42&v=abc"
+        );
+        assert_eq!(
+            canonical_url("https://x.com/user/status/1?s=20"),
+            "https://twitter.com/user/status/1"
+        );
+        assert_eq!(
+            canonical_url("https://mobile.twitter.com/user/status/1"),
+            "https://twitter.com/user/status/1"
+        );
+    }
+}
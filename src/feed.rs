@@ -0,0 +1,257 @@
+// Render a shared brain as a feed, so colleagues can follow it from any feed reader:
+// Atom 1.0 (RFC 4287) or RSS 2.0. Items go newest first. Each entry links to the saved URL,
+// is identified by the item's public URL under the share hash, and carries the item's tags
+// as categories.
+use brainly_types::export::ExportedContent;
+use chrono::{DateTime, SecondsFormat, Utc};
+
+// What describes the feed as a whole
+pub struct FeedInfo {
+    pub title: String,
+    pub author: String,
+    pub base_url: String,  // The server's public URL
+    pub share_url: String, // Public URL of the shared brain, which items are served under
+    pub self_url: String,  // Where the feed itself is served
+    pub updated: DateTime<Utc>,
+}
+
+// Atom 1.0 document
+pub fn atom(feed: &FeedInfo, items: &[ExportedContent]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str(&format!("  <id>{}</id>\n", escape(&feed.self_url)));
+    xml.push_str(&format!("  <title>{}</title>\n", escape(&feed.title)));
+    xml.push_str(&format!(
+        "  <updated>{}</updated>\n",
+        timestamp(feed.updated)
+    ));
+    xml.push_str(&format!(
+        "  <author><name>{}</name></author>\n",
+        escape(&feed.author)
+    ));
+    xml.push_str(&format!(
+        "  <link rel=\"self\" type=\"application/atom+xml\" href=\"{}\"/>\n",
+        escape(&feed.self_url)
+    ));
+    xml.push_str("  <generator>brainly</generator>\n");
+
+    for item in items {
+        let share_link = share_link(feed, item);
+        xml.push_str("  <entry>\n");
+        xml.push_str(&format!("    <id>{}</id>\n", escape(&share_link)));
+        xml.push_str(&format!("    <title>{}</title>\n", escape(&item.title)));
+        xml.push_str(&format!(
+            "    <link rel=\"alternate\" href=\"{}\"/>\n",
+            escape(item.url.as_deref().unwrap_or(&share_link))
+        ));
+        xml.push_str(&format!(
+            "    <published>{}</published>\n",
+            timestamp(item.created_at)
+        ));
+        xml.push_str(&format!(
+            "    <updated>{}</updated>\n",
+            timestamp(item.created_at)
+        ));
+        for tag in &item.tags {
+            xml.push_str(&format!("    <category term=\"{}\"/>\n", escape(tag)));
+        }
+        xml.push_str(&format!(
+            "    <summary type=\"text\">{}</summary>\n",
+            escape(&summary(item))
+        ));
+        xml.push_str("  </entry>\n");
+    }
+    xml.push_str("</feed>\n");
+    xml
+}
+
+// RSS 2.0 document, with an Atom self link as feed validators recommend
+pub fn rss(feed: &FeedInfo, items: &[ExportedContent]) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<rss version=\"2.0\" xmlns:atom=\"http://www.w3.org/2005/Atom\">\n");
+    xml.push_str("  <channel>\n");
+    xml.push_str(&format!("    <title>{}</title>\n", escape(&feed.title)));
+    xml.push_str(&format!("    <link>{}</link>\n", escape(&feed.base_url)));
+    xml.push_str(&format!(
+        "    <description>Content saved by {}</description>\n",
+        escape(&feed.author)
+    ));
+    xml.push_str(&format!(
+        "    <atom:link rel=\"self\" type=\"application/rss+xml\" href=\"{}\"/>\n",
+        escape(&feed.self_url)
+    ));
+    xml.push_str(&format!(
+        "    <lastBuildDate>{}</lastBuildDate>\n",
+        feed.updated.to_rfc2822()
+    ));
+    xml.push_str("    <generator>brainly</generator>\n");
+
+    for item in items {
+        let share_link = share_link(feed, item);
+        xml.push_str("    <item>\n");
+        xml.push_str(&format!("      <title>{}</title>\n", escape(&item.title)));
+        xml.push_str(&format!(
+            "      <link>{}</link>\n",
+            escape(item.url.as_deref().unwrap_or(&share_link))
+        ));
+        xml.push_str(&format!(
+            "      <guid isPermaLink=\"false\">{}</guid>\n",
+            escape(&share_link)
+        ));
+        xml.push_str(&format!(
+            "      <pubDate>{}</pubDate>\n",
+            item.created_at.to_rfc2822()
+        ));
+        for tag in &item.tags {
+            xml.push_str(&format!("      <category>{}</category>\n", escape(tag)));
+        }
+        xml.push_str(&format!(
+            "      <description>{}</description>\n",
+            escape(&summary(item))
+        ));
+        xml.push_str("    </item>\n");
+    }
+    xml.push_str("  </channel>\n</rss>\n");
+    xml
+}
+
+// Stable public address of an item, whatever URL it points to. Readers open it without
+// signing in, as long as the brain stays shared.
+fn share_link(feed: &FeedInfo, item: &ExportedContent) -> String {
+    format!("{}/content/{}", feed.share_url, item.link)
+}
+
+// Plain text shown by readers under the title: the kind of content and its tags
+fn summary(item: &ExportedContent) -> String {
+    if item.tags.is_empty() {
+        item.type_.to_string()
+    } else {
+        format!("{} tagged {}", item.type_, item.tags.join(", "))
+    }
+}
+
+fn timestamp(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+// Escape text for XML element content and double-quoted attributes
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            // Control characters other than tab and line breaks are not allowed in XML 1.0,
+            // and neither are these two noncharacters
+            c if c.is_control() && !matches!(c, '\t' | '\n' | '\r') => {}
+            '\u{FFFE}' | '\u{FFFF}' => {}
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use brainly_types::content::{ContentType, Visibility};
+
+    use super::*;
+    use crate::subscriptions::parse::{parse, FeedEntry};
+
+    fn info() -> FeedInfo {
+        FeedInfo {
+            title: "Tom & Jerry's brain".to_string(),
+            author: "Tom & Jerry".to_string(),
+            base_url: "https://brainly.test".to_string(),
+            share_url: "https://brainly.test/api/v1/brain/HASH".to_string(),
+            self_url: "https://brainly.test/api/v1/brain/HASH/feed.atom".to_string(),
+            updated: DateTime::from_timestamp(1700000000, 0).unwrap(),
+        }
+    }
+
+    fn items() -> Vec<ExportedContent> {
+        vec![
+            ExportedContent {
+                id: 2,
+                type_: ContentType::Video,
+                title: "<b>Bold</b> & \"quoted\"\u{0}\u{7}\u{1b}\tend".to_string(),
+                url: Some("https://example.com/?a=1&b=2".to_string()),
+                link: "link2".to_string(),
+                tags: vec!["R&D".to_string(), "\"quoted\"".to_string()],
+                read: false,
+                collection: None,
+                visibility: Visibility::Shared,
+                created_at: DateTime::from_timestamp(1700000000, 0).unwrap(),
+            },
+            ExportedContent {
+                id: 1,
+                type_: ContentType::Article,
+                title: "A note\u{FFFF}".to_string(),
+                url: None,
+                link: "link1".to_string(),
+                tags: Vec::new(),
+                read: true,
+                collection: None,
+                visibility: Visibility::Shared,
+                created_at: DateTime::from_timestamp(1690000000, 0).unwrap(),
+            },
+        ]
+    }
+
+    // Entries as the feed poller reads them
+    fn expected_entries() -> Vec<FeedEntry> {
+        vec![
+            FeedEntry {
+                key: "https://brainly.test/api/v1/brain/HASH/content/link2".to_string(),
+                url: Some("https://example.com/?a=1&b=2".to_string()),
+                title: Some("<b>Bold</b> & \"quoted\"\tend".to_string()),
+            },
+            FeedEntry {
+                key: "https://brainly.test/api/v1/brain/HASH/content/link1".to_string(),
+                url: Some("https://brainly.test/api/v1/brain/HASH/content/link1".to_string()),
+                title: Some("A note".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn atom_parses_back_with_public_links() {
+        let xml = atom(&info(), &items());
+        let feed = parse(&xml).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Tom & Jerry's brain"));
+        assert_eq!(feed.entries, expected_entries());
+        assert!(xml.contains("<category term=\"R&amp;D\"/>"));
+        assert!(xml.contains("<category term=\"&quot;quoted&quot;\"/>"));
+        assert!(xml.contains("<published>2023-11-14T22:13:20Z</published>"));
+        assert!(!xml.contains("/api/v1/content/link/"));
+    }
+
+    #[test]
+    fn rss_parses_back_with_public_links() {
+        let xml = rss(&info(), &items());
+        let feed = parse(&xml).unwrap();
+        assert_eq!(feed.title.as_deref(), Some("Tom & Jerry's brain"));
+        assert_eq!(feed.entries, expected_entries());
+        assert!(xml.contains("<category>R&amp;D</category>"));
+        assert!(xml.contains("<pubDate>Tue, 14 Nov 2023 22:13:20 +0000</pubDate>"));
+        assert!(xml.contains("<description>Video tagged R&amp;D, &quot;quoted&quot;</description>"));
+        assert!(!xml.contains("/api/v1/content/link/"));
+    }
+
+    #[test]
+    fn escapes_markup_and_drops_invalid_characters() {
+        assert_eq!(
+            escape("<a href=\"x\">'&'</a>"),
+            "&lt;a href=&quot;x&quot;&gt;&apos;&amp;&apos;&lt;/a&gt;"
+        );
+        assert_eq!(escape("a\u{0}b\u{8}c\u{1f}d\u{7f}e"), "abcde");
+        assert_eq!(
+            escape("keep\ttab\nand\r\nbreaks"),
+            "keep\ttab\nand\r\nbreaks"
+        );
+        assert_eq!(escape("x\u{FFFE}\u{FFFF}y"), "xy");
+    }
+}
@@ -0,0 +1,145 @@
+use std::{
+    collections::{BTreeMap, HashSet},
+    future::Future,
+    sync::atomic::{AtomicBool, Ordering},
+    time::{Duration, Instant},
+};
+
+use actix_web::{web::Data, HttpResponse, Responder};
+use sqlx::{migrate::Migrate, MySqlPool};
+
+use brainly_types::health::CheckResult;
+
+use crate::database::MIGRATOR;
+use crate::openapi::NoData;
+use crate::routes::SuccessResponse;
+
+// Upper bound for each dependency check, so a hung database cannot hang the probe
+const CHECK_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Process wide readiness state, flipped when graceful shutdown begins
+#[derive(Default)]
+pub struct Health {
+    shutting_down: AtomicBool,
+}
+
+// Run a check under the timeout, timing it and mapping failures to "down"
+async fn run_check<F>(check: F) -> CheckResult
+where
+    F: Future<Output = Result<Option<String>, String>>,
+{
+    let started = Instant::now();
+    let result = tokio::time::timeout(CHECK_TIMEOUT, check).await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(Ok(detail)) => CheckResult {
+            status: "up".to_string(),
+            latency_ms,
+            detail,
+        },
+        Ok(Err(e)) => CheckResult {
+            status: "down".to_string(),
+            latency_ms,
+            detail: Some(e),
+        },
+        Err(_) => CheckResult {
+            status: "down".to_string(),
+            latency_ms,
+            detail: Some("timed out".to_string()),
+        },
+    }
+}
+
+// The database answers a trivial query
+async fn check_database(db: &MySqlPool) -> Result<Option<String>, String> {
+    sqlx::query("SELECT 1")
+        .execute(db)
+        .await
+        .map(|_| None)
+        .map_err(|e| e.to_string())
+}
+
+// Every embedded migration has been applied successfully
+async fn check_migrations(db: &MySqlPool) -> Result<Option<String>, String> {
+    let mut conn = db.acquire().await.map_err(|e| e.to_string())?;
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    let pending = MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .filter(|migration| !applied.contains(&migration.version))
+        .count();
+
+    if pending == 0 {
+        Ok(None)
+    } else {
+        Err(format!("{} pending migration(s)", pending))
+    }
+}
+
+impl Health {
+    pub fn new() -> Health {
+        Health::default()
+    }
+
+    // Mark the process as shutting down so readiness fails and traffic drains away
+    pub fn begin_shutdown(&self) {
+        self.shutting_down.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_shutting_down(&self) -> bool {
+        self.shutting_down.load(Ordering::SeqCst)
+    }
+}
+
+// Liveness: the process is up and serving requests
+#[utoipa::path(get, path = "/healthz", tag = "operations",
+    responses((status = 200, description = "The process is alive", body = SuccessResponse<NoData>)))]
+pub async fn liveness() -> impl Responder {
+    HttpResponse::Ok().json(SuccessResponse::<()> {
+        success: true,
+        message: "alive".to_string(),
+        data: None,
+    })
+}
+
+// Readiness: dependencies are reachable, the schema is current and we are not shutting down
+#[utoipa::path(get, path = "/readyz", tag = "operations",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = SuccessResponse<BTreeMap<String, CheckResult>>),
+        (status = 503, description = "A dependency is down or the server is shutting down", body = SuccessResponse<BTreeMap<String, CheckResult>>),
+    ))]
+pub async fn readiness(health: Data<Health>, db: Data<MySqlPool>) -> impl Responder {
+    let mut checks = BTreeMap::new();
+    checks.insert("database", run_check(check_database(&db)).await);
+    checks.insert("migrations", run_check(check_migrations(&db)).await);
+
+    let shutting_down = health.is_shutting_down();
+    let ready = !shutting_down && checks.values().all(|check| check.status == "up");
+
+    let message = if shutting_down {
+        "shutting down"
+    } else if ready {
+        "ready"
+    } else {
+        "not ready"
+    };
+
+    let body = SuccessResponse {
+        success: ready,
+        message: message.to_string(),
+        data: Some(checks),
+    };
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
@@ -0,0 +1,260 @@
+use super::{collection_for, parse_timestamp, split_tags, ImportItem, ParsedRow};
+
+// Read a Netscape bookmark file, the HTML format every browser exports:
+//
+//   <DL><p>
+//       <DT><H3>Rust</H3>
+//       <DL><p>
+//           <DT><A HREF="https://www.rust-lang.org" ADD_DATE="1700000000" TAGS="lang">Rust</A>
+//       </DL><p>
+//   </DL><p>
+//
+// Every folder a bookmark sits in becomes one of its tags, except the browser's own root
+// folders (bookmarks bar, other bookmarks, ...), and the folder path its collection. The format is loose HTML, so this scans
+// for the few tags it cares about instead of building a document tree.
+pub fn parse(text: &str) -> Vec<ParsedRow> {
+    let mut rows = Vec::new();
+    let mut folders: Vec<Option<String>> = Vec::new(); // One entry per open <DL>
+    let mut pending_folder: Option<String> = None; // Last <H3>, waiting for its <DL>
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.to_ascii_uppercase().as_str() {
+            "DL" => folders.push(pending_folder.take()),
+            "/DL" => {
+                folders.pop();
+            }
+            "H3" => {
+                let (title, after) = element_text(rest, "</H3");
+                rest = after;
+                // Browser root folders carry one of these markers
+                let root = ["PERSONAL_TOOLBAR_FOLDER", "UNFILED_BOOKMARKS_FOLDER"]
+                    .iter()
+                    .any(|marker| attribute(attributes, marker).is_some());
+                pending_folder = (!root && !title.is_empty()).then_some(title);
+            }
+            "A" => {
+                let (title, after) = element_text(rest, "</A");
+                rest = after;
+                rows.push(bookmark(attributes, title, &folders));
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+// Build the item for an <A> element inside the given folders
+fn bookmark(attributes: &str, title: String, folders: &[Option<String>]) -> ParsedRow {
+    let url = attribute(attributes, "HREF").ok_or("Bookmark without a URL")?;
+    // Bookmarklets and browser internal pages cannot be saved
+    if !url.starts_with("http://") && !url.starts_with("https://") {
+        return Err(format!("Unsupported bookmark URL {}", url));
+    }
+
+    let folders: Vec<&str> = folders.iter().flatten().map(String::as_str).collect();
+    let mut tags: Vec<String> = folders.iter().map(|folder| folder.to_string()).collect();
+    if let Some(value) = attribute(attributes, "TAGS") {
+        tags.extend(split_tags(&value));
+    }
+    Ok(ImportItem {
+        url: Some(url),
+        title: (!title.is_empty()).then_some(title),
+        type_: None,
+        tags,
+        read: false,
+        collection: collection_for(&folders),
+        created_at: attribute(attributes, "ADD_DATE").and_then(|value| parse_timestamp(&value)),
+        ..ImportItem::default()
+    })
+}
+
+// Text up to the closing tag (matched case-insensitively), and the input after it
+pub(super) fn element_text<'a>(rest: &'a str, closing: &str) -> (String, &'a str) {
+    let end = find_ignore_case(rest, closing).unwrap_or(rest.len());
+    let after = rest[end..]
+        .find('>')
+        .map_or("", |close| &rest[end + close + 1..]);
+    (decode_entities(rest[..end].trim()), after)
+}
+
+// Position of the first occurrence of an ASCII `needle` in `haystack`, ignoring case.
+// Compares in place, as a copy of the rest of the document per element would make
+// parsing quadratic.
+fn find_ignore_case(haystack: &str, needle: &str) -> Option<usize> {
+    haystack
+        .as_bytes()
+        .windows(needle.len())
+        .position(|window| window.eq_ignore_ascii_case(needle.as_bytes()))
+}
+
+// Value of an attribute, quoted with double or single quotes or unquoted
+pub(super) fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let mut rest = attributes;
+    while !rest.is_empty() {
+        rest = rest.trim_start();
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = rest[key_end..].trim_start();
+
+        let value = match rest.strip_prefix('=') {
+            Some(after) => {
+                let after = after.trim_start();
+                let (value, remaining) = match after.chars().next() {
+                    Some(quote @ ('"' | '\'')) => {
+                        let body = &after[1..];
+                        let close = body.find(quote).unwrap_or(body.len());
+                        (&body[..close], body.get(close + 1..).unwrap_or(""))
+                    }
+                    _ => {
+                        let close = after.find(char::is_whitespace).unwrap_or(after.len());
+                        (&after[..close], &after[close..])
+                    }
+                };
+                rest = remaining;
+                Some(value)
+            }
+            None => None, // Attribute without a value
+        };
+
+        if key.eq_ignore_ascii_case(name) {
+            return Some(decode_entities(value.unwrap_or("")));
+        }
+        if key.is_empty() && value.is_none() {
+            break; // Nothing left to read
+        }
+    }
+    None
+}
+
+// Decode the character references browsers write into bookmark files, which are the same
+// as XML's, so feed parsing uses this too
+pub fn decode_entities(text: &str) -> String {
+    let mut decoded = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        decoded.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let entity = rest[1..].find(';').map(|end| &rest[1..end + 1]);
+        let character = entity.and_then(|entity| match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" | "#39" => Some('\''),
+            _ => {
+                let code = match entity.strip_prefix("#x").or(entity.strip_prefix("#X")) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok(),
+                    None => entity.strip_prefix('#').and_then(|dec| dec.parse().ok()),
+                };
+                code.and_then(char::from_u32)
+            }
+        });
+
+        match (character, entity) {
+            (Some(character), Some(entity)) => {
+                decoded.push(character);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                // Not a reference we know, keep the ampersand as written
+                decoded.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_folders_as_tags_and_collections() {
+        let rows = parse(&fixture("bookmarks.html"));
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://www.rust-lang.org/".to_string()),
+                    title: Some("The Rust & Cargo site".to_string()),
+                    type_: None,
+                    tags: vec!["Rust", "lang", "systems"]
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                    read: false,
+                    collection: Some("Rust".to_string()),
+                    created_at: DateTime::from_timestamp(1700000000, 0),
+                    ..ImportItem::default()
+                }),
+                Ok(ImportItem {
+                    url: Some("https://doc.rust-lang.org/book/".to_string()),
+                    title: Some("The Book".to_string()),
+                    tags: vec!["Rust".to_string()],
+                    collection: Some("Rust".to_string()),
+                    created_at: DateTime::from_timestamp(1700000100, 0),
+                    ..ImportItem::default()
+                }),
+                Ok(ImportItem {
+                    url: Some("https://example.com/top".to_string()),
+                    title: Some("Top level".to_string()),
+                    ..ImportItem::default()
+                }),
+                Err("Unsupported bookmark URL javascript:alert(1)".to_string()),
+                Ok(ImportItem {
+                    url: Some("https://example.com/untitled".to_string()),
+                    ..ImportItem::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_closing_tags_in_any_case() {
+        assert_eq!(
+            element_text("Title</a> rest", "</A"),
+            ("Title".to_string(), " rest")
+        );
+        assert_eq!(
+            element_text("Unclosed", "</A"),
+            ("Unclosed".to_string(), "")
+        );
+        assert_eq!(find_ignore_case("aé</H3>", "</h3"), Some(3));
+        assert_eq!(find_ignore_case("", "</A"), None);
+    }
+
+    #[test]
+    fn reads_attributes_and_entities() {
+        let attributes = r#"HREF="https://a.test/?x=1&amp;y=2" add_date=1 PRIVATE tags='a b'"#;
+        assert_eq!(
+            attribute(attributes, "href").as_deref(),
+            Some("https://a.test/?x=1&y=2")
+        );
+        assert_eq!(attribute(attributes, "ADD_DATE").as_deref(), Some("1"));
+        assert_eq!(attribute(attributes, "private").as_deref(), Some(""));
+        assert_eq!(attribute(attributes, "TAGS").as_deref(), Some("a b"));
+        assert_eq!(attribute(attributes, "icon"), None);
+        assert_eq!(
+            decode_entities("&lt;b&gt; &#65;&#x42; &unknown; & done"),
+            "<b> AB &unknown; & done"
+        );
+    }
+}
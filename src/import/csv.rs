@@ -0,0 +1,180 @@
+use std::str::FromStr;
+
+use brainly_types::content::ContentType;
+
+use super::{collection_for, parse_timestamp, split_tags, ImportItem, ParsedRow};
+
+// Read a CSV file with a header row. Columns are matched by name, case-insensitively:
+// `url` is required, `title`, `type`, `tags` (comma or semicolon separated), `folder`
+// (nested folders separated by '/', kept as tags and as the collection) and `created_at`
+// are optional, others are ignored.
+pub fn parse(text: &str) -> Result<Vec<ParsedRow>, String> {
+    let mut records = records(text)?.into_iter();
+    let header = records.next().ok_or("The CSV file is empty")?;
+    let column = |names: &[&str]| column(&header, names);
+
+    let url = column(&["url", "link", "href"]).ok_or("The CSV header has no url column")?;
+    let title = column(&["title", "name"]);
+    let type_ = column(&["type", "type_"]);
+    let tags = column(&["tags", "tag"]);
+    let folder = column(&["folder", "folders"]);
+    let created_at = column(&["created_at", "created", "time_added", "date_added"]);
+
+    Ok(records
+        .filter(|record| record.iter().any(|field| !field.trim().is_empty())) // Blank lines
+        .map(|record| {
+            let field = |index: Option<usize>| field(&record, index);
+
+            let mut item = ImportItem {
+                url: field(Some(url)).map(str::to_string),
+                title: field(title).map(str::to_string),
+                ..ImportItem::default()
+            };
+            if item.url.is_none() {
+                return Err("Missing URL".to_string());
+            }
+            if let Some(value) = field(type_) {
+                let type_ = ContentType::from_str(value)
+                    .or_else(|_| ContentType::from_str(&capitalize(value)))
+                    .map_err(|_| format!("Unknown content type {}", value))?;
+                item.type_ = Some(type_);
+            }
+            if let Some(value) = field(folder) {
+                let folders: Vec<&str> = value.split('/').collect();
+                item.tags.extend(
+                    folders
+                        .iter()
+                        .map(|name| name.trim())
+                        .filter(|name| !name.is_empty())
+                        .map(str::to_string),
+                );
+                item.collection = collection_for(&folders);
+            }
+            if let Some(value) = field(tags) {
+                item.tags.extend(split_tags(value));
+            }
+            if let Some(value) = field(created_at) {
+                item.created_at =
+                    Some(parse_timestamp(value).ok_or_else(|| format!("Invalid date {}", value))?);
+            }
+            Ok(item)
+        })
+        .collect())
+}
+
+// Index of the first header column named like one of `names`, ignoring case
+pub fn column(header: &[String], names: &[&str]) -> Option<usize> {
+    header
+        .iter()
+        .position(|name| names.contains(&name.trim().to_ascii_lowercase().as_str()))
+}
+
+// Value of a column in a record, None when the column is missing or the cell is blank
+pub fn field(record: &[String], index: Option<usize>) -> Option<&str> {
+    index
+        .and_then(|index| record.get(index))
+        .map(|value| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+// "video" -> "Video", so lower case types written by hand are accepted
+fn capitalize(value: &str) -> String {
+    let lower = value.to_ascii_lowercase();
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(first) => first.to_ascii_uppercase().to_string() + chars.as_str(),
+        None => String::new(),
+    }
+}
+
+// Split CSV text into records of fields (RFC 4180: quoted fields may contain commas,
+// line breaks and doubled quotes)
+pub fn records(text: &str) -> Result<Vec<Vec<String>>, String> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match (quoted, c) {
+            (true, '"') if chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            (true, '"') => quoted = false,
+            (true, c) => field.push(c),
+            (false, '"') if field.is_empty() => quoted = true,
+            (false, ',') => record.push(std::mem::take(&mut field)),
+            (false, '\r') if chars.peek() == Some(&'\n') => {}
+            (false, '\n') => {
+                record.push(std::mem::take(&mut field));
+                records.push(std::mem::take(&mut record));
+            }
+            (false, c) => field.push(c),
+        }
+    }
+    if quoted {
+        return Err("Unterminated quoted field in the CSV file".to_string());
+    }
+    if !field.is_empty() || !record.is_empty() {
+        record.push(field);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_columns_by_name() {
+        let rows = parse(&fixture("generic.csv")).unwrap();
+        assert_eq!(rows.len(), 5); // The blank line is skipped
+        assert_eq!(
+            rows[0],
+            Ok(ImportItem {
+                url: Some("https://example.com/a".to_string()),
+                title: Some("Quoted, with comma".to_string()),
+                type_: Some(ContentType::Video),
+                tags: vec!["Work", "Reading", "x", "y"]
+                    .into_iter()
+                    .map(str::to_string)
+                    .collect(),
+                read: false,
+                collection: Some("Work/Reading".to_string()),
+                created_at: DateTime::from_timestamp(1704164645, 0),
+                ..ImportItem::default()
+            })
+        );
+        assert_eq!(
+            rows[1],
+            Ok(ImportItem {
+                url: Some("https://example.com/b".to_string()),
+                title: Some("Line\nbreak \"quoted\"".to_string()),
+                created_at: DateTime::from_timestamp(1700000000, 0),
+                ..ImportItem::default()
+            })
+        );
+        assert_eq!(rows[2], Err("Missing URL".to_string()));
+        assert_eq!(rows[3], Err("Unknown content type Podcast".to_string()));
+        assert_eq!(rows[4], Err("Invalid date yesterday".to_string()));
+    }
+
+    #[test]
+    fn rejects_unreadable_files() {
+        assert_eq!(parse("").unwrap_err(), "The CSV file is empty");
+        assert_eq!(
+            parse("title\nx\n").unwrap_err(),
+            "The CSV header has no url column"
+        );
+        assert_eq!(
+            parse("url\n\"https://example.com\n").unwrap_err(),
+            "Unterminated quoted field in the CSV file"
+        );
+    }
+}
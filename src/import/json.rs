@@ -0,0 +1,87 @@
+use brainly_types::export::{Export, EXPORT_VERSION};
+
+use super::{ImportItem, ParsedRow};
+
+// Read a document produced by GET /api/v1/user/export. IDs and links belong to the
+// exporting server, so only the content itself is kept.
+pub fn parse(text: &str) -> Result<Vec<ParsedRow>, String> {
+    let export: Export =
+        serde_json::from_str(text).map_err(|e| format!("Not a brainly export: {}", e))?;
+    if export.version > EXPORT_VERSION {
+        return Err(format!(
+            "Export version {} is newer than this server supports ({})",
+            export.version, EXPORT_VERSION
+        ));
+    }
+
+    Ok(export
+        .contents
+        .into_iter()
+        .map(|content| {
+            Ok(ImportItem {
+                url: content.url,
+                title: Some(content.title),
+                type_: Some(content.type_),
+                tags: content.tags,
+                read: content.read,
+                collection: content.collection,
+                visibility: content.visibility,
+                created_at: Some(content.created_at),
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use brainly_types::content::{ContentType, Visibility};
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_a_brainly_export() {
+        let rows = parse(&fixture("export.json")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://youtube.com/watch?v=abc".to_string()),
+                    title: Some("A talk".to_string()),
+                    type_: Some(ContentType::Video),
+                    tags: vec!["talks".to_string(), "rust".to_string()],
+                    read: true,
+                    collection: Some("Talks".to_string()),
+                    visibility: Visibility::Private,
+                    created_at: DateTime::from_timestamp(1706933106, 0),
+                }),
+                Ok(ImportItem {
+                    url: None,
+                    title: Some("A note".to_string()),
+                    type_: Some(ContentType::Article),
+                    tags: Vec::new(),
+                    read: false,
+                    collection: None,
+                    visibility: Visibility::Shared,
+                    created_at: DateTime::from_timestamp(1709528767, 0),
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_newer_versions_and_other_documents() {
+        let newer = fixture("export.json").replacen("\"version\": 1", "\"version\": 99", 1);
+        assert_eq!(
+            parse(&newer).unwrap_err(),
+            format!(
+                "Export version 99 is newer than this server supports ({})",
+                EXPORT_VERSION
+            )
+        );
+        assert!(parse("{\"items\": []}")
+            .unwrap_err()
+            .starts_with("Not a brainly export"));
+    }
+}
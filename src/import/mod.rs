@@ -0,0 +1,288 @@
+// Parsers turning uploaded files into items to save. Each format module only reads its file;
+// deduplication, validation and saving happen in the import job (routes/import.rs).
+use brainly_types::{
+    content::{ContentType, Visibility},
+    import::ImportFormat,
+};
+use chrono::{DateTime, Utc};
+
+pub mod bookmarks; // Netscape bookmark files exported by browsers
+pub mod csv; // Generic CSV with a header row
+pub mod json; // brainly's own JSON export
+pub mod pinboard; // Pinboard JSON export
+pub mod pocket; // Pocket CSV or HTML export
+pub mod raindrop; // Raindrop.io CSV export
+
+// Longest collection name, see validate_collection
+const COLLECTION_MAX_CHARS: usize = 64;
+
+// One entry read from an import file
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportItem {
+    pub url: Option<String>,
+    pub title: Option<String>,      // Defaults to the URL when missing
+    pub type_: Option<ContentType>, // Guessed from the URL when missing
+    pub tags: Vec<String>,          // Tags and folder names
+    pub read: bool,                 // Read state kept by read-later services
+    pub collection: Option<String>, // Folder or collection the item was filed under
+    pub visibility: Visibility,     // Shared unless the file records otherwise
+    pub created_at: Option<DateTime<Utc>>, // Original save time, when the file records it
+}
+
+// A parsed row, or why the row could not be read
+pub type ParsedRow = Result<ImportItem, String>;
+
+// Parse a whole file. Errors that make the file unreadable fail the import, problems
+// with a single row are reported for that row only.
+pub fn parse(format: ImportFormat, data: &[u8]) -> Result<Vec<ParsedRow>, String> {
+    let text = std::str::from_utf8(data).map_err(|_| "File is not valid UTF-8".to_string())?;
+    let text = text.trim_start_matches('\u{feff}'); // Spreadsheet tools like to add a BOM
+    match format {
+        ImportFormat::Json => json::parse(text),
+        ImportFormat::Csv => csv::parse(text),
+        ImportFormat::Bookmarks => Ok(bookmarks::parse(text)),
+        ImportFormat::Pocket => pocket::parse(text),
+        ImportFormat::Raindrop => raindrop::parse(text),
+        ImportFormat::Pinboard => pinboard::parse(text),
+    }
+}
+
+// Guess the format from the start of the file, which tells the services' exports apart,
+// falling back to the file name and then to the generic formats
+pub fn detect_format(filename: Option<&str>, data: &[u8]) -> Option<ImportFormat> {
+    let start = String::from_utf8_lossy(&data[..data.len().min(1024)]).to_ascii_lowercase();
+    let start = start.trim_start_matches('\u{feff}').trim_start();
+    let first_line = start.lines().next().unwrap_or_default();
+
+    // Formats with a recognisable signature
+    if start.starts_with('[') && start.contains("\"href\"") {
+        return Some(ImportFormat::Pinboard);
+    }
+    if start.starts_with('<') && start.contains("<title>pocket export") {
+        return Some(ImportFormat::Pocket);
+    }
+    if first_line.contains("time_added") && first_line.contains("status") {
+        return Some(ImportFormat::Pocket);
+    }
+    if first_line.contains("excerpt") && first_line.contains("folder") {
+        return Some(ImportFormat::Raindrop);
+    }
+
+    let extension = filename
+        .and_then(|name| name.rsplit_once('.'))
+        .map(|(_, extension)| extension.to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("json") => return Some(ImportFormat::Json),
+        Some("csv") => return Some(ImportFormat::Csv),
+        Some("html" | "htm") => return Some(ImportFormat::Bookmarks),
+        _ => {}
+    }
+
+    if start.starts_with('{') {
+        Some(ImportFormat::Json)
+    } else if start.starts_with("<!doctype netscape-bookmark-file") || start.contains("<dl") {
+        Some(ImportFormat::Bookmarks)
+    } else if first_line.contains(',') {
+        Some(ImportFormat::Csv)
+    } else {
+        None
+    }
+}
+
+// Content type for an item whose source did not record one, judged by where the URL points
+pub fn content_type_for_url(url: &str) -> ContentType {
+    let Ok(url) = url::Url::parse(url) else {
+        return ContentType::Article;
+    };
+    let host = url
+        .host_str()
+        .unwrap_or_default()
+        .trim_start_matches("www.");
+    let path = url.path().to_ascii_lowercase();
+    let extension = path.rsplit_once('.').map_or("", |(_, extension)| extension);
+
+    const VIDEO_HOSTS: [&str; 5] = [
+        "youtube.com",
+        "youtu.be",
+        "vimeo.com",
+        "twitch.tv",
+        "dailymotion.com",
+    ];
+    const AUDIO_HOSTS: [&str; 3] = ["soundcloud.com", "podcasts.apple.com", "open.spotify.com"];
+    if VIDEO_HOSTS
+        .iter()
+        .any(|video| host == *video || host.ends_with(&format!(".{}", video)))
+        || matches!(extension, "mp4" | "webm" | "mov" | "mkv")
+    {
+        ContentType::Video
+    } else if AUDIO_HOSTS.contains(&host)
+        || matches!(extension, "mp3" | "ogg" | "wav" | "m4a" | "flac")
+    {
+        ContentType::Audio
+    } else if matches!(extension, "png" | "jpg" | "jpeg" | "gif" | "webp" | "svg") {
+        ContentType::Image
+    } else {
+        ContentType::Article
+    }
+}
+
+// Split a list of tags on commas and semicolons, dropping empty entries
+pub fn split_tags(tags: &str) -> Vec<String> {
+    tags.split([',', ';'])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// Collection for an item filed under nested `folders`, outermost first: the folder path
+// joined with '/', or the innermost folder when the path is too long for a collection name
+pub fn collection_for(folders: &[&str]) -> Option<String> {
+    let folders: Vec<&str> = folders
+        .iter()
+        .map(|folder| folder.trim())
+        .filter(|folder| !folder.is_empty())
+        .collect();
+    let path = folders.join("/");
+    let name = if path.chars().count() <= COLLECTION_MAX_CHARS {
+        path
+    } else {
+        folders.last()?.chars().take(COLLECTION_MAX_CHARS).collect()
+    };
+    let name = name.trim();
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+// Read a timestamp given either as RFC 3339 or as seconds since the Unix epoch
+pub fn parse_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<i64>() {
+        return DateTime::from_timestamp(seconds, 0);
+    }
+    DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+// Contents of a file in tests/fixtures/import
+#[cfg(test)]
+pub(crate) fn fixture(name: &str) -> String {
+    let path = format!(
+        "{}/tests/fixtures/import/{}",
+        env!("CARGO_MANIFEST_DIR"),
+        name
+    );
+    std::fs::read_to_string(path).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_signatures_before_file_names() {
+        let pinboard = br#"[{"href":"https://example.com","description":"x"}]"#;
+        assert_eq!(
+            detect_format(Some("export.json"), pinboard),
+            Some(ImportFormat::Pinboard)
+        );
+        let pocket = b"<!DOCTYPE html>\n<html><head><title>Pocket Export</title></head>";
+        assert_eq!(
+            detect_format(Some("ril_export.html"), pocket),
+            Some(ImportFormat::Pocket)
+        );
+        let pocket_csv = b"title,url,time_added,tags,status\n";
+        assert_eq!(
+            detect_format(Some("part_000000.csv"), pocket_csv),
+            Some(ImportFormat::Pocket)
+        );
+        let raindrop = b"\xef\xbb\xbfid,title,note,excerpt,url,folder,tags,created\n";
+        assert_eq!(
+            detect_format(Some("export.csv"), raindrop),
+            Some(ImportFormat::Raindrop)
+        );
+    }
+
+    #[test]
+    fn detects_generic_formats_by_name_then_contents() {
+        let csv = fixture("generic.csv");
+        let bookmarks = fixture("bookmarks.html");
+        let export = fixture("export.json");
+        assert_eq!(
+            detect_format(Some("LINKS.CSV"), b"anything"),
+            Some(ImportFormat::Csv)
+        );
+        assert_eq!(
+            detect_format(Some("bookmarks.htm"), b"anything"),
+            Some(ImportFormat::Bookmarks)
+        );
+        assert_eq!(
+            detect_format(None, export.as_bytes()),
+            Some(ImportFormat::Json)
+        );
+        assert_eq!(
+            detect_format(None, bookmarks.as_bytes()),
+            Some(ImportFormat::Bookmarks)
+        );
+        assert_eq!(
+            detect_format(Some("upload"), csv.as_bytes()),
+            Some(ImportFormat::Csv)
+        );
+        assert_eq!(detect_format(Some("notes.txt"), b"just some text"), None);
+    }
+
+    #[test]
+    fn rejects_invalid_utf8_and_skips_the_bom() {
+        assert_eq!(
+            parse(ImportFormat::Csv, b"url\n\xff").unwrap_err(),
+            "File is not valid UTF-8"
+        );
+        let rows = parse(
+            ImportFormat::Csv,
+            "\u{feff}url\nhttps://example.com\n".as_bytes(),
+        );
+        assert_eq!(
+            rows.unwrap()[0].as_ref().unwrap().url.as_deref(),
+            Some("https://example.com")
+        );
+    }
+
+    #[test]
+    fn names_collections_after_folder_paths() {
+        assert_eq!(
+            collection_for(&["Work", " Reading "]).as_deref(),
+            Some("Work/Reading")
+        );
+        assert_eq!(collection_for(&["", "  "]), None);
+        let long = "x".repeat(70);
+        assert_eq!(
+            collection_for(&[&long, "Rust"]).as_deref(),
+            Some("Rust") // The path is longer than a collection name
+        );
+        assert_eq!(
+            collection_for(&[&"y".repeat(80)]).map(|name| name.chars().count()),
+            Some(COLLECTION_MAX_CHARS)
+        );
+    }
+
+    #[test]
+    fn guesses_content_types_from_urls() {
+        assert_eq!(
+            content_type_for_url("https://www.youtube.com/watch?v=abc"),
+            ContentType::Video
+        );
+        assert_eq!(
+            content_type_for_url("https://player.vimeo.com/video/1"),
+            ContentType::Video
+        );
+        assert_eq!(
+            content_type_for_url("https://open.spotify.com/episode/1"),
+            ContentType::Audio
+        );
+        assert_eq!(
+            content_type_for_url("https://example.com/cat.JPG"),
+            ContentType::Image
+        );
+        assert_eq!(content_type_for_url("not a url"), ContentType::Article);
+    }
+}
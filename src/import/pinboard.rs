@@ -0,0 +1,87 @@
+use serde::Deserialize;
+
+use super::{parse_timestamp, ImportItem, ParsedRow};
+
+// A bookmark in Pinboard's JSON export (https://pinboard.in/export/format:json/)
+#[derive(Deserialize)]
+struct Post {
+    #[serde(default)]
+    href: String,
+    #[serde(default)]
+    description: String, // The title, despite the name
+    #[serde(default)]
+    tags: String, // Space separated
+    #[serde(default)]
+    time: String, // RFC 3339
+    #[serde(default)]
+    toread: String, // "yes" for items on the reading list
+}
+
+// Read a Pinboard JSON export. Bookmarks marked "to read" import as unread, every other
+// bookmark as read.
+pub fn parse(text: &str) -> Result<Vec<ParsedRow>, String> {
+    let posts: Vec<Post> =
+        serde_json::from_str(text).map_err(|e| format!("Not a Pinboard export: {}", e))?;
+
+    Ok(posts
+        .into_iter()
+        .map(|post| {
+            if post.href.trim().is_empty() {
+                return Err("Missing URL".to_string());
+            }
+            Ok(ImportItem {
+                url: Some(post.href.trim().to_string()),
+                title: Some(post.description.trim().to_string()).filter(|title| !title.is_empty()),
+                type_: None,
+                tags: post.tags.split_whitespace().map(str::to_string).collect(),
+                read: post.toread != "yes",
+                created_at: parse_timestamp(&post.time),
+                ..ImportItem::default()
+            })
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_tags_and_the_reading_list() {
+        let rows = parse(&fixture("pinboard.json")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://example.com/later".to_string()),
+                    title: Some("Read later".to_string()),
+                    type_: None,
+                    tags: vec!["rust".to_string(), "async".to_string()],
+                    read: false, // toread=yes
+                    created_at: DateTime::from_timestamp(1704164645, 0),
+                    ..ImportItem::default()
+                }),
+                Ok(ImportItem {
+                    url: Some("https://example.com/done".to_string()),
+                    title: None,
+                    type_: None,
+                    tags: Vec::new(),
+                    read: true,
+                    created_at: DateTime::from_timestamp(1704240000, 0),
+                    ..ImportItem::default()
+                }),
+                Err("Missing URL".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_other_documents() {
+        assert!(parse("{\"posts\": []}")
+            .unwrap_err()
+            .starts_with("Not a Pinboard export"));
+    }
+}
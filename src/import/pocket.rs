@@ -0,0 +1,190 @@
+use super::{
+    bookmarks::{attribute, element_text},
+    collection_for,
+    csv::{column, field, records},
+    parse_timestamp, ImportItem, ParsedRow,
+};
+
+// Read a Pocket export, either the CSV Pocket produces today
+// (`title,url,time_added,tags,status`, tags separated by '|', status "unread" or "archive")
+// or the older HTML file, which lists unread items under <h1>Unread</h1> and archived
+// ones under <h1>Read Archive</h1>. Archived items are imported as read. Exports that
+// carry a `collection` column file the items under that collection.
+pub fn parse(text: &str) -> Result<Vec<ParsedRow>, String> {
+    if text.trim_start().starts_with('<') {
+        Ok(parse_html(text))
+    } else {
+        parse_csv(text)
+    }
+}
+
+fn parse_csv(text: &str) -> Result<Vec<ParsedRow>, String> {
+    let mut records = records(text)?.into_iter();
+    let header = records.next().ok_or("The Pocket export is empty")?;
+    let url = column(&header, &["url"]).ok_or("The Pocket export has no url column")?;
+    let title = column(&header, &["title"]);
+    let time_added = column(&header, &["time_added"]);
+    let tags = column(&header, &["tags"]);
+    let status = column(&header, &["status"]);
+    let collection = column(&header, &["collection", "collections"]);
+
+    Ok(records
+        .filter(|record| record.iter().any(|value| !value.trim().is_empty()))
+        .map(|record| {
+            Ok(ImportItem {
+                url: Some(field(&record, Some(url)).ok_or("Missing URL")?.to_string()),
+                // Pocket uses the URL as title when it could not fetch the page
+                title: field(&record, title).map(str::to_string),
+                type_: None,
+                tags: field(&record, tags).map(pocket_tags).unwrap_or_default(),
+                read: field(&record, status).is_some_and(|status| status == "archive"),
+                collection: field(&record, collection).and_then(|name| collection_for(&[name])),
+                created_at: field(&record, time_added).and_then(parse_timestamp),
+                ..ImportItem::default()
+            })
+        })
+        .collect())
+}
+
+fn parse_html(text: &str) -> Vec<ParsedRow> {
+    let mut rows = Vec::new();
+    let mut read = false; // Unread items come first
+    let mut rest = text;
+
+    while let Some(start) = rest.find('<') {
+        rest = &rest[start + 1..];
+        let end = match rest.find('>') {
+            Some(end) => end,
+            None => break,
+        };
+        let tag = &rest[..end];
+        rest = &rest[end + 1..];
+
+        let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+        match name.to_ascii_lowercase().as_str() {
+            "h1" => {
+                let (heading, after) = element_text(rest, "</H1");
+                rest = after;
+                read = heading.to_ascii_lowercase().contains("archive");
+            }
+            "a" => {
+                let (title, after) = element_text(rest, "</A");
+                rest = after;
+                rows.push(match attribute(attributes, "href") {
+                    Some(url) => Ok(ImportItem {
+                        url: Some(url),
+                        title: (!title.is_empty()).then_some(title),
+                        type_: None,
+                        tags: attribute(attributes, "tags")
+                            .map(|tags| pocket_tags(&tags))
+                            .unwrap_or_default(),
+                        read,
+                        created_at: attribute(attributes, "time_added")
+                            .and_then(|value| parse_timestamp(&value)),
+                        ..ImportItem::default()
+                    }),
+                    None => Err("Link without a URL".to_string()),
+                });
+            }
+            _ => {}
+        }
+    }
+    rows
+}
+
+// Pocket separates tags with '|' in CSV and ',' in HTML
+fn pocket_tags(tags: &str) -> Vec<String> {
+    tags.split(['|', ','])
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_the_csv_export() {
+        let rows = parse(&fixture("pocket.csv")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://blog.rust-lang.org/2024".to_string()),
+                    title: Some("Rust 2024".to_string()),
+                    type_: None,
+                    tags: vec!["rust".to_string(), "news".to_string()],
+                    read: true, // status=archive
+                    created_at: DateTime::from_timestamp(1700000000, 0),
+                    ..ImportItem::default()
+                }),
+                Ok(ImportItem {
+                    url: Some("https://example.com/raw".to_string()),
+                    title: Some("https://example.com/raw".to_string()),
+                    read: false,
+                    created_at: DateTime::from_timestamp(1700000100, 0),
+                    ..ImportItem::default()
+                }),
+                Err("Missing URL".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_the_collection_column() {
+        let rows = parse(
+            "title,url,time_added,tags,status,collection\n\
+             A,https://example.com/a,1700000000,,unread,Weekend reads\n\
+             B,https://example.com/b,1700000000,,unread,\n",
+        )
+        .unwrap();
+        assert_eq!(
+            rows[0].as_ref().unwrap().collection.as_deref(),
+            Some("Weekend reads")
+        );
+        assert_eq!(rows[1].as_ref().unwrap().collection, None);
+    }
+
+    #[test]
+    fn reads_the_html_export_with_its_archive() {
+        let rows = parse(&fixture("pocket.html")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://example.com/unread".to_string()),
+                    title: Some("An unread article".to_string()),
+                    type_: None,
+                    tags: vec!["reading".to_string(), "long".to_string()],
+                    read: false,
+                    created_at: DateTime::from_timestamp(1700000000, 0),
+                    ..ImportItem::default()
+                }),
+                Err("Link without a URL".to_string()),
+                Ok(ImportItem {
+                    url: Some("https://example.com/read".to_string()),
+                    title: Some("A read \"article\"".to_string()),
+                    type_: None,
+                    tags: Vec::new(),
+                    read: true, // Under <h1>Read Archive</h1>
+                    created_at: DateTime::from_timestamp(1700000100, 0),
+                    ..ImportItem::default()
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_csv_without_urls() {
+        assert_eq!(parse("").unwrap_err(), "The Pocket export is empty");
+        assert_eq!(
+            parse("title,status\nx,unread\n").unwrap_err(),
+            "The Pocket export has no url column"
+        );
+    }
+}
@@ -0,0 +1,109 @@
+use brainly_types::content::ContentType;
+
+use super::{
+    collection_for,
+    csv::{column, field, records},
+    parse_timestamp, split_tags, ImportItem, ParsedRow,
+};
+
+// Read a Raindrop.io CSV export
+// (`id,title,note,excerpt,url,folder,tags,created,cover,highlights,favorite`, some exports
+// also carry a `type` column). The collection an item sits in becomes its collection and
+// tags, except the catch-all "Unsorted" collection. Raindrop has no read state, so items
+// import as unread.
+pub fn parse(text: &str) -> Result<Vec<ParsedRow>, String> {
+    let mut records = records(text)?.into_iter();
+    let header = records.next().ok_or("The Raindrop export is empty")?;
+    let url = column(&header, &["url"]).ok_or("The Raindrop export has no url column")?;
+    let title = column(&header, &["title"]);
+    let folder = column(&header, &["folder"]);
+    let tags = column(&header, &["tags"]);
+    let created = column(&header, &["created"]);
+    let type_ = column(&header, &["type"]);
+
+    Ok(records
+        .filter(|record| record.iter().any(|value| !value.trim().is_empty()))
+        .map(|record| {
+            let folders: Vec<&str> = field(&record, folder)
+                .into_iter()
+                .flat_map(|folder| folder.split('/'))
+                .map(str::trim)
+                .filter(|name| !name.is_empty() && !name.eq_ignore_ascii_case("unsorted"))
+                .collect();
+            let mut item_tags: Vec<String> = folders.iter().map(|name| name.to_string()).collect();
+            item_tags.extend(field(&record, tags).map(split_tags).unwrap_or_default());
+
+            Ok(ImportItem {
+                url: Some(field(&record, Some(url)).ok_or("Missing URL")?.to_string()),
+                title: field(&record, title).map(str::to_string),
+                type_: field(&record, type_).and_then(content_type),
+                tags: item_tags,
+                read: false,
+                collection: collection_for(&folders),
+                created_at: field(&record, created).and_then(parse_timestamp),
+                ..ImportItem::default()
+            })
+        })
+        .collect())
+}
+
+// Map Raindrop's item types onto ours. "link" and unknown types are left to the URL.
+fn content_type(type_: &str) -> Option<ContentType> {
+    match type_.to_ascii_lowercase().as_str() {
+        "article" | "document" | "book" => Some(ContentType::Article),
+        "image" => Some(ContentType::Image),
+        "video" => Some(ContentType::Video),
+        "audio" => Some(ContentType::Audio),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::DateTime;
+
+    use super::*;
+    use crate::import::fixture;
+
+    #[test]
+    fn reads_collections_tags_and_types() {
+        let rows = parse(&fixture("raindrop.csv")).unwrap();
+        assert_eq!(
+            rows,
+            vec![
+                Ok(ImportItem {
+                    url: Some("https://vimeo.com/1".to_string()),
+                    title: Some("A talk".to_string()),
+                    type_: Some(ContentType::Video),
+                    tags: vec!["Talks", "Rust", "conf", "2024"]
+                        .into_iter()
+                        .map(str::to_string)
+                        .collect(),
+                    read: false,
+                    collection: Some("Talks/Rust".to_string()),
+                    created_at: DateTime::from_timestamp(1704164645, 0),
+                    ..ImportItem::default()
+                }),
+                Ok(ImportItem {
+                    url: Some("https://example.com/u".to_string()),
+                    title: Some("Unsorted link".to_string()),
+                    type_: None, // "link" is left to the URL
+                    tags: Vec::new(),
+                    read: false,
+                    created_at: DateTime::from_timestamp(1704240000, 0),
+                    ..ImportItem::default()
+                }),
+                Err("Missing URL".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_csv_without_urls() {
+        assert_eq!(parse("").unwrap_err(), "The Raindrop export is empty");
+        assert_eq!(
+            parse("id,title\n1,x\n").unwrap_err(),
+            "The Raindrop export has no url column"
+        );
+    }
+}
@@ -0,0 +1,15 @@
+// Shared by the API server (src/main.rs) and the command-line tools in src/bin/
+pub mod canonical_url; // URL normalization, so a page is only saved once
+pub mod config; // Environment-driven application configuration
+pub mod database; // Database connection and schema migrations
+pub mod feed; // Atom and RSS feeds of shared brains
+pub mod health; // Liveness and readiness probes
+pub mod import; // Parsers for files imported into a user's brain
+pub mod metrics; // Prometheus metrics registry
+pub mod middleware; // Custom middleware (CORS, CSRF, rate limiting, request IDs, request tracing, metrics)
+pub mod openapi; // OpenAPI document for the HTTP API
+pub mod routes; // Route handlers for users and content
+pub mod subscriptions; // Background polling of subscribed RSS and Atom feeds
+pub mod telemetry; // Structured logging setup
+pub mod tls; // Optional HTTPS with certificate hot reload
+pub mod vault; // Markdown vault export (Obsidian, Logseq)
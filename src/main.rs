@@ -1,42 +1,381 @@
 // Import necessary modules and functions
+use std::sync::Arc;
+
 use actix_web::{
-    web::{delete, get, post, Data}, // HTTP methods and shared state
-    App, HttpServer,
+    http::header::STRICT_TRANSPORT_SECURITY,
+    middleware::DefaultHeaders,
+    web::{delete, get, post, to, Data}, // HTTP methods and shared state
+    App,
+    HttpRequest,
+    HttpServer,
+};
+use brainly::config::Config; // Environment-driven application configuration
+use brainly::database::{database_connetion, MIGRATOR}; // Database connection and schema migrations
+use brainly::health::{self, Health}; // Liveness and readiness probes
+use brainly::metrics::{self, Metrics}; // Prometheus metrics registry
+use brainly::middleware::{
+    cors, CsrfProtection, InMemoryStore, RateLimiter, RequestIdMiddleware, RequestMetrics,
+    TracingLogger,
 };
-mod routes; // Module containing route handlers for users and content
-use routes::{Content, User}; // Import Content and User route handlers
-mod database; // Module for database connection
-use database::database_connetion; // Function to establish a database connection
+use brainly::openapi::ApiDoc; // OpenAPI document for the HTTP API
+use brainly::routes::jwt::AccountCache; // Account statuses shared by every worker
+use brainly::routes::{
+    api_key, brain, content, export, import, oidc, subscription, trash, user, Content, Import,
+    Oidc, Trash,
+}; // Route handlers
+use brainly::subscriptions::{self, HttpFetcher}; // Background feed polling
+use brainly::telemetry::init_tracing; // Structured logging setup
+use brainly::tls::{self, redirect_to_https}; // Optional HTTPS with certificate hot reload
+use utoipa::OpenApi;
+use utoipa_swagger_ui::SwaggerUi;
 
 #[tokio::main] // Macro to designate the main function as an asynchronous Tokio runtime
 async fn main() -> std::io::Result<()> {
-    const PORT: u16 = 8080; // Server port
+    let config = Config::from_env();
+    init_tracing(config.log_json);
+
+    if config.public_url.base_url.is_none() {
+        tracing::warn!(
+            "PUBLIC_URL is not set, feed and export links use the request's Host header"
+        );
+    }
 
     // Step 1: Establish a database connection
-    let database = database_connetion()
+    let database = database_connetion(&config.database)
         .await
         .expect("Failed to connect to database"); // Panic if the database connection fails
-    println!("Database connection established");
+    tracing::info!("Database connection established");
+
+    // Optionally bring the schema up to date before serving traffic
+    if config.run_migrations {
+        MIGRATOR
+            .run(&database)
+            .await
+            .expect("Failed to run database migrations");
+        tracing::info!("Database migrations applied");
+    }
+
+    // Import jobs run in-process, so none can still be running after a restart
+    match Import::fail_interrupted_jobs(&database).await {
+        Ok(0) => {}
+        Ok(jobs) => tracing::warn!(
+            jobs,
+            "Marked import jobs interrupted by the restart as failed"
+        ),
+        Err(e) => tracing::warn!(error = %e, "Failed to clean up interrupted import jobs"),
+    }
+
+    // Duplicate detection needs the canonical URL hash of items saved before it existed
+    match Content::backfill_url_hashes(&database).await {
+        Ok(0) => {}
+        Ok(items) => tracing::info!(items, "Hashed the URLs of existing content"),
+        Err(e) => tracing::warn!(error = %e, "Failed to hash the URLs of existing content"),
+    }
+
+    // Step 2: Set up rate limiting, one limiter per route group sharing a single bucket store
+    let rate_limit_store = Arc::new(InMemoryStore::new());
+    let auth_limit = RateLimiter::new("auth", config.rate_limit.auth, rate_limit_store.clone());
+    let write_limit = RateLimiter::new(
+        "content_write",
+        config.rate_limit.content_write,
+        rate_limit_store.clone(),
+    );
+    let read_limit = RateLimiter::new(
+        "content_read",
+        config.rate_limit.content_read,
+        rate_limit_store.clone(),
+    );
+
+    let password_config = config.password.clone();
+    let cookie_config = config.cookie.clone();
+    let trash_config = config.trash.clone();
+    let public_url_config = config.public_url.clone();
+    let cors_config = config.cors;
+    let metrics = Data::new(Metrics::new());
+    let health = Data::new(Health::new());
+    let account_cache = Data::new(AccountCache::new());
+    let server_health = health.clone();
+    let server_database = database.clone();
+    let api_doc = ApiDoc::openapi();
+
+    // Fetch subscribed feeds in the background, saving their new entries
+    subscriptions::spawn_poller(
+        database.clone(),
+        metrics.clone().into_inner(),
+        Arc::new(HttpFetcher::new(config.feeds.fetch_timeout)),
+        config.feeds.clone(),
+    );
+
+    // Permanently delete trashed content once its retention period has passed
+    Trash::spawn_purge_job(database.clone(), config.trash.clone());
+
+    // Single sign-on is only enabled when an identity provider is configured
+    let oidc = config.oidc.clone().map(|oidc| Data::new(Oidc::new(oidc)));
+
+    // Optionally serve HTTPS directly, reloading the certificate whenever it is renewed
+    let tls_config = config.tls.as_ref().map(|tls| {
+        let (server_config, resolver) =
+            tls::server_config(tls).expect("Failed to load TLS certificate");
+        tls::watch_certificates(resolver, tls.clone());
+        server_config
+    });
+    let hsts = config.tls.as_ref().and_then(tls::hsts_header);
 
-    // Step 2: Configure and run the HTTP server
+    // Step 3: Configure and run the HTTP server
     let server = HttpServer::new(move || {
+        let oidc = oidc.clone();
+        let oidc_limit = auth_limit.clone();
+
+        // Browsers only honour HSTS over HTTPS, so it is only sent when TLS is enabled
+        let mut security_headers = DefaultHeaders::new();
+        if let Some(hsts) = &hsts {
+            security_headers = security_headers.add((STRICT_TRANSPORT_SECURITY, hsts.as_str()));
+        }
+
         // Define routes and handlers
         App::new()
-            .app_data(Data::new(database.clone())) // Share the database connection across handlers
+            .wrap(security_headers) // Strict-Transport-Security when serving HTTPS
+            .wrap(CsrfProtection) // Require a CSRF token on state-changing requests authenticated by cookie
+            .wrap(cors(&cors_config)) // Answer preflights and add CORS headers for allowed origins
+            .wrap(RequestMetrics::new(metrics.clone())) // Count requests and observe latency
+            .wrap(TracingLogger) // Log every request inside a span
+            .wrap(RequestIdMiddleware) // Outermost, so the request ID is set before the span is created
+            .app_data(Data::new(server_database.clone())) // Share the database connection across handlers
+            .app_data(Data::new(password_config.clone())) // Password hashing parameters
+            .app_data(Data::new(cookie_config.clone())) // Session cookie attributes
+            .app_data(Data::new(trash_config.clone())) // Trash retention, for reporting when items are purged
+            .app_data(Data::new(public_url_config.clone())) // Base of the links in feeds and exports
+            .app_data(metrics.clone()) // Metrics registry, for handlers recording business metrics
+            .app_data(server_health.clone()) // Readiness state
+            .app_data(account_cache.clone()) // Recently checked account statuses
+            .route("/metrics", get().to(metrics::metrics_endpoint)) // Prometheus scrape endpoint
+            .route("/healthz", get().to(health::liveness)) // Liveness probe
+            .route("/readyz", get().to(health::readiness)) // Readiness probe
+            .service(
+                SwaggerUi::new("/api/v1/docs/{_:.*}").url("/api/v1/openapi.json", api_doc.clone()),
+            ) // API docs
             // User routes
-            .route("/api/v1/signup", post().to(User::create_user)) // User signup endpoint
-            .route("/api/v1/signin", post().to(User::signin_user)) // User signin endpoint
-
+            .route(
+                "/api/v1/signup",
+                post().to(user::create_user).wrap(auth_limit.clone()),
+            ) // User signup endpoint
+            .route(
+                "/api/v1/signin",
+                post().to(user::signin_user).wrap(auth_limit.clone()),
+            ) // User signin endpoint
+            .configure(move |cfg| {
+                if let Some(oidc) = oidc {
+                    cfg.app_data(oidc)
+                        .route(
+                            "/api/v1/auth/oidc/login",
+                            get().to(oidc::login).wrap(oidc_limit.clone()),
+                        ) // Start OIDC login
+                        .route(
+                            "/api/v1/auth/oidc/callback",
+                            get().to(oidc::callback).wrap(oidc_limit),
+                        ); // OIDC callback
+                }
+            })
+            .route(
+                "/api/v1/user/api-keys",
+                post().to(api_key::create_api_key).wrap(write_limit.clone()),
+            ) // Create API key
+            .route(
+                "/api/v1/user/api-keys",
+                get().to(api_key::get_api_keys).wrap(read_limit.clone()),
+            ) // List API keys
+            .route(
+                "/api/v1/user/api-keys/{id}",
+                delete()
+                    .to(api_key::delete_api_key)
+                    .wrap(write_limit.clone()),
+            ) // Revoke API key
+            .route(
+                "/api/v1/user/export",
+                get().to(export::export_json).wrap(read_limit.clone()),
+            ) // Download everything as JSON
+            .route(
+                "/api/v1/user/export/markdown",
+                get().to(export::export_markdown).wrap(read_limit.clone()),
+            ) // Download a zipped Markdown vault
+            .route(
+                "/api/v1/user/import",
+                post().to(import::import_file).wrap(write_limit.clone()),
+            ) // Start importing an uploaded file
+            .route(
+                "/api/v1/user/import/{id}",
+                get().to(import::get_import_job).wrap(read_limit.clone()),
+            ) // Import job status and report
             // Content routes
-            .route("/api/v1/content", post().to(Content::create_content)) // Create content
-            .route("/api/v1/user/content", get().to(Content::get_all_content)) // Get all user content
-            .route("/api/v1/content/{id}", get().to(Content::get_content_by_id)) // Get content by ID
-            .route("/api/v1/content/{id}", delete().to(Content::delete_content)) // Delete content by ID
-            .route("/api/v1/content/link/{link}", get().to(Content::get_content_by_link)) // Get content by link
+            .route(
+                "/api/v1/content",
+                post().to(content::create_content).wrap(write_limit.clone()),
+            ) // Create content
+            .route(
+                "/api/v1/content/bulk",
+                post().to(content::bulk_content).wrap(write_limit.clone()),
+            ) // Create, delete, tag or untag many items at once
+            .route(
+                "/api/v1/user/content",
+                get().to(content::get_all_content).wrap(read_limit.clone()),
+            ) // Get all user content
+            .route(
+                "/api/v1/content/{id}",
+                get()
+                    .to(content::get_content_by_id)
+                    .wrap(read_limit.clone()),
+            ) // Get content by ID
+            .route(
+                "/api/v1/content/{id}",
+                delete()
+                    .to(content::delete_content)
+                    .wrap(write_limit.clone()),
+            ) // Move content to the trash
+            .route(
+                "/api/v1/content/{id}/restore",
+                post().to(trash::restore_content).wrap(write_limit.clone()),
+            ) // Restore content from the trash
+            .route(
+                "/api/v1/content/link/{link}",
+                get()
+                    .to(content::get_content_by_link)
+                    .wrap(read_limit.clone()),
+            ) // Get content by link
+            .route(
+                "/api/v1/trash",
+                get().to(trash::get_trash).wrap(read_limit.clone()),
+            ) // List trashed content
+            .route(
+                "/api/v1/trash",
+                delete().to(trash::empty_trash).wrap(write_limit.clone()),
+            ) // Permanently delete everything in the trash
+            .route(
+                "/api/v1/trash/{id}",
+                delete().to(trash::purge_content).wrap(write_limit.clone()),
+            ) // Permanently delete trashed content
+            // Shared brain routes
+            .route(
+                "/api/v1/brain/share",
+                post().to(brain::share_brain).wrap(write_limit.clone()),
+            ) // Turn public sharing on or off
+            .route(
+                "/api/v1/brain/{share_hash}/feed.atom",
+                get().to(brain::feed_atom).wrap(read_limit.clone()),
+            ) // Public Atom feed
+            .route(
+                "/api/v1/brain/{share_hash}/feed.rss",
+                get().to(brain::feed_rss).wrap(read_limit.clone()),
+            ) // Public RSS feed
+            .route(
+                "/api/v1/brain/{share_hash}/content/{link}",
+                get().to(brain::shared_content).wrap(read_limit.clone()),
+            ) // Public view of one shared item
+            // Feed subscription routes
+            .route(
+                "/api/v1/feeds",
+                post()
+                    .to(subscription::create_subscription)
+                    .wrap(write_limit.clone()),
+            ) // Subscribe to a feed
+            .route(
+                "/api/v1/feeds",
+                get()
+                    .to(subscription::get_subscriptions)
+                    .wrap(read_limit.clone()),
+            ) // List subscriptions
+            .route(
+                "/api/v1/feeds/{id}",
+                delete()
+                    .to(subscription::delete_subscription)
+                    .wrap(write_limit.clone()),
+            ) // Unsubscribe
     })
-    .bind(("127.0.0.1", PORT))? // Bind the server to localhost and the specified port
+    .workers(config.server.workers) // Number of worker threads
+    .keep_alive(config.server.keep_alive) // Idle keep-alive connection lifetime
+    .shutdown_timeout(config.server.shutdown_timeout.as_secs()); // Drain deadline for in-flight requests
+
+    // Bind the configured interface and port, over TLS when a certificate is configured
+    let address = (config.server.host.clone(), config.server.port);
+    let server = match tls_config {
+        Some(tls_config) => server.bind_rustls_0_23(address, tls_config)?,
+        None => server.bind(address)?,
+    }
+    .disable_signals() // Signals are handled below so readiness can report the shutdown
     .run(); // Start the server
 
-    println!("Server is running on port {}", PORT); // Log the server's status
-    server.await // Await the server's completion
+    // With TLS, optionally answer plain HTTP on a second port with a redirect to HTTPS
+    let https_port = config.server.port;
+    let redirect = match config.tls.as_ref().and_then(|tls| tls.redirect_port) {
+        Some(redirect_port) => Some(
+            HttpServer::new(move || {
+                App::new().default_service(to(move |req: HttpRequest| async move {
+                    redirect_to_https(&req, https_port)
+                }))
+            })
+            .workers(1)
+            .bind((config.server.host.clone(), redirect_port))?
+            .disable_signals()
+            .run(),
+        ),
+        None => None,
+    };
+
+    // Step 4: On SIGINT/SIGTERM, fail readiness, give load balancers time to notice,
+    // then stop accepting connections and drain in-flight requests
+    let handle = server.handle();
+    let redirect_handle = redirect.as_ref().map(|redirect| redirect.handle());
+    let shutdown_grace = config.server.shutdown_grace;
+    tokio::spawn(async move {
+        shutdown_signal().await;
+        tracing::info!("Shutdown signal received");
+        health.begin_shutdown();
+        if !shutdown_grace.is_zero() {
+            tokio::time::sleep(shutdown_grace).await;
+        }
+        if let Some(redirect_handle) = redirect_handle {
+            redirect_handle.stop(true).await;
+        }
+        handle.stop(true).await;
+    });
+
+    tracing::info!(
+        host = %config.server.host,
+        port = config.server.port,
+        tls = config.tls.is_some(),
+        "Server is running"
+    ); // Log the server's status
+       // Await the server's completion, alongside the redirect listener when there is one
+    let result = match redirect {
+        Some(redirect) => tokio::try_join!(server, redirect).map(|_| ()),
+        None => server.await,
+    };
+
+    // Step 5: Close the pool once every worker has stopped
+    database.close().await;
+    tracing::info!("Server stopped");
+    result
+}
+
+// Resolve once the process receives SIGINT (Ctrl+C) or, on Unix, SIGTERM
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("Failed to listen for Ctrl+C");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("Failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
 }
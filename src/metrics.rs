@@ -0,0 +1,110 @@
+use actix_web::{http::header::ContentType, web::Data, HttpResponse, Responder};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use sqlx::MySqlPool;
+
+// Application metrics, exposed in Prometheus text format on /metrics
+pub struct Metrics {
+    registry: Registry,
+    pub http_requests: IntCounterVec, // Requests by method, route and status
+    pub http_duration: HistogramVec,  // Request latency by method, route and status
+    pub signin_attempts: IntCounterVec, // Password sign ins by outcome
+    pub content_created: IntCounterVec, // Created content by type
+    pub feed_fetches: IntCounterVec,  // Subscribed feed fetches by outcome
+    db_connections: IntGaugeVec,      // Pool connections by state, sampled on scrape
+}
+
+impl Default for Metrics {
+    fn default() -> Metrics {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("brainly".to_string()), None)
+            .expect("Invalid metrics namespace");
+
+        let http_requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("Invalid metric");
+        let http_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "HTTP request latency"),
+            &["method", "route", "status"],
+        )
+        .expect("Invalid metric");
+        let signin_attempts = IntCounterVec::new(
+            Opts::new("signin_attempts_total", "Password sign in attempts"),
+            &["outcome"],
+        )
+        .expect("Invalid metric");
+        let content_created = IntCounterVec::new(
+            Opts::new("content_created_total", "Content items created"),
+            &["type"],
+        )
+        .expect("Invalid metric");
+        let feed_fetches = IntCounterVec::new(
+            Opts::new("feed_fetches_total", "Subscribed feed fetches"),
+            &["outcome"],
+        )
+        .expect("Invalid metric");
+        let db_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections"),
+            &["state"],
+        )
+        .expect("Invalid metric");
+
+        for collector in [
+            Box::new(http_requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(http_duration.clone()),
+            Box::new(signin_attempts.clone()),
+            Box::new(content_created.clone()),
+            Box::new(feed_fetches.clone()),
+            Box::new(db_connections.clone()),
+        ] {
+            registry.register(collector).expect("Duplicate metric");
+        }
+
+        Metrics {
+            registry,
+            http_requests,
+            http_duration,
+            signin_attempts,
+            content_created,
+            feed_fetches,
+            db_connections,
+        }
+    }
+}
+
+// Serve all metrics in Prometheus text format
+#[utoipa::path(get, path = "/metrics", operation_id = "metrics", tag = "operations",
+    responses((status = 200, description = "Prometheus text exposition format", body = String, content_type = "text/plain")))]
+pub async fn metrics_endpoint(metrics: Data<Metrics>, db: Data<MySqlPool>) -> impl Responder {
+    // Pool usage is sampled at scrape time rather than tracked on every acquire
+    let size = db.size() as i64;
+    let idle = db.num_idle() as i64;
+    let max = db.options().get_max_connections() as i64;
+    metrics
+        .db_connections
+        .with_label_values(&["idle"])
+        .set(idle);
+    metrics
+        .db_connections
+        .with_label_values(&["in_use"])
+        .set(size - idle);
+    metrics.db_connections.with_label_values(&["max"]).set(max);
+
+    let mut buffer = Vec::new();
+    match TextEncoder::new().encode(&metrics.registry.gather(), &mut buffer) {
+        Ok(()) => HttpResponse::Ok()
+            .content_type(ContentType(
+                TextEncoder::new().format_type().parse().unwrap(),
+            ))
+            .body(buffer),
+        Err(e) => HttpResponse::InternalServerError().body(e.to_string()),
+    }
+}
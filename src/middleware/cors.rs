@@ -0,0 +1,48 @@
+use actix_cors::Cors;
+use actix_web::http::header::{HeaderName, AUTHORIZATION, CONTENT_TYPE};
+
+use crate::config::CorsConfig;
+
+use super::csrf::CSRF_HEADER;
+
+// Response headers browsers may read from cross-origin responses
+const EXPOSED_HEADERS: [&str; 6] = [
+    "x-request-id",
+    CSRF_HEADER,
+    "x-ratelimit-limit",
+    "x-ratelimit-remaining",
+    "x-ratelimit-reset",
+    "retry-after",
+];
+
+// Build the CORS middleware. With no allowed origins configured, cross-origin
+// requests are rejected and only same-origin clients can call the API.
+pub fn cors(config: &CorsConfig) -> Cors {
+    let mut cors = Cors::default()
+        .allowed_methods(config.allowed_methods.iter().map(String::as_str))
+        .expose_headers(EXPOSED_HEADERS.map(HeaderName::from_static))
+        .max_age(config.max_age_secs);
+
+    cors = if config.allowed_headers.is_empty() {
+        cors.allowed_headers([AUTHORIZATION, CONTENT_TYPE])
+            .allowed_header("x-api-key")
+            .allowed_header("x-request-id")
+            .allowed_header(CSRF_HEADER)
+    } else {
+        config
+            .allowed_headers
+            .iter()
+            .fold(cors, |cors, header| cors.allowed_header(header.as_str()))
+    };
+
+    cors = config
+        .allowed_origins
+        .iter()
+        .fold(cors, |cors, origin| cors.allowed_origin(origin));
+
+    if config.allow_credentials {
+        cors = cors.supports_credentials();
+    }
+
+    cors
+}
@@ -0,0 +1,201 @@
+use std::{
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    cookie::{time::Duration, Cookie},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::Method,
+    Error, HttpResponse,
+};
+
+use crate::config::CookieConfig;
+use crate::routes::{jwt::uses_session_cookie, utils::generate_random_string, SuccessResponse};
+
+// Cookie holding the CSRF token. Not HttpOnly, so the browser app can read it
+// and echo it back in the header below.
+pub const CSRF_COOKIE: &str = "csrf_token";
+// Requests echo the token in this header. Sign in responses also carry the new token in
+// it, for apps on another origin that cannot read the cookie.
+pub const CSRF_HEADER: &str = "x-csrf-token";
+
+// Build a fresh CSRF cookie, issued alongside every session cookie
+pub fn csrf_cookie(config: &CookieConfig) -> Cookie<'static> {
+    let mut cookie = Cookie::build(CSRF_COOKIE, generate_random_string(32))
+        .path("/")
+        .http_only(false)
+        .max_age(Duration::hours(2)) // Same lifetime as the session token
+        .same_site(config.same_site)
+        .secure(config.secure)
+        .finish();
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+    cookie
+}
+
+// Double-submit CSRF protection. State-changing requests authenticated by the session
+// cookie must repeat the CSRF cookie's value in the X-CSRF-Token header, which a cross-site
+// page cannot do because it can neither read the cookie nor set custom headers.
+// Requests authenticated with a Bearer token or API key are exempt, since browsers never
+// attach those automatically.
+pub struct CsrfProtection;
+
+impl<S, B> Transform<S, ServiceRequest> for CsrfProtection
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = CsrfMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(CsrfMiddleware {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct CsrfMiddleware<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for CsrfMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+
+        Box::pin(async move {
+            if requires_token(&req) && !token_matches(&req) {
+                tracing::warn!("Rejected request with a missing or invalid CSRF token");
+                let response = HttpResponse::Forbidden().json(SuccessResponse::<()> {
+                    success: false,
+                    message: "Missing or invalid CSRF token".to_string(),
+                    data: None,
+                });
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let response = service.call(req).await?;
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+// Only state-changing methods authenticated by the session cookie need a token
+fn requires_token(req: &ServiceRequest) -> bool {
+    let state_changing = matches!(
+        *req.method(),
+        Method::POST | Method::PUT | Method::PATCH | Method::DELETE
+    );
+    state_changing && uses_session_cookie(req.request())
+}
+
+// Check the header against the cookie, comparing in constant time
+fn token_matches(req: &ServiceRequest) -> bool {
+    let cookie = match req.cookie(CSRF_COOKIE) {
+        Some(cookie) => cookie,
+        None => return false,
+    };
+    let header = match req.headers().get(CSRF_HEADER).and_then(|v| v.to_str().ok()) {
+        Some(header) => header,
+        None => return false,
+    };
+
+    let (expected, given) = (cookie.value().as_bytes(), header.as_bytes());
+    !expected.is_empty()
+        && expected.len() == given.len()
+        && expected
+            .iter()
+            .zip(given)
+            .fold(0, |acc, (a, b)| acc | (a ^ b))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{
+        cookie::Cookie,
+        http::{header::AUTHORIZATION, StatusCode},
+        test::{call_service, init_service, TestRequest},
+        web, App, HttpResponse,
+    };
+
+    use super::*;
+
+    async fn status(req: TestRequest) -> StatusCode {
+        let app = init_service(
+            App::new()
+                .wrap(CsrfProtection)
+                .route("/", web::get().to(HttpResponse::Ok))
+                .route("/", web::post().to(HttpResponse::Ok)),
+        )
+        .await;
+        call_service(&app, req.to_request()).await.status()
+    }
+
+    // A POST authenticated by the session cookie, with the CSRF cookie set
+    fn cookie_post() -> TestRequest {
+        TestRequest::post()
+            .uri("/")
+            .cookie(Cookie::new("auth_token", "session"))
+            .cookie(Cookie::new(CSRF_COOKIE, "token-1"))
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_missing_header() {
+        assert_eq!(status(cookie_post()).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn rejects_a_mismatched_header() {
+        let req = cookie_post().insert_header((CSRF_HEADER, "token-2"));
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+        let req = cookie_post().insert_header((CSRF_HEADER, "token-10"));
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+
+        // An empty cookie never matches
+        let req = TestRequest::post()
+            .uri("/")
+            .cookie(Cookie::new("auth_token", "session"))
+            .cookie(Cookie::new(CSRF_COOKIE, ""))
+            .insert_header((CSRF_HEADER, ""));
+        assert_eq!(status(req).await, StatusCode::FORBIDDEN);
+    }
+
+    #[actix_web::test]
+    async fn accepts_a_matching_header() {
+        let req = cookie_post().insert_header((CSRF_HEADER, "token-1"));
+        assert_eq!(status(req).await, StatusCode::OK);
+    }
+
+    #[actix_web::test]
+    async fn exempts_header_credentials_and_safe_methods() {
+        let req = cookie_post().insert_header((AUTHORIZATION, "Bearer token"));
+        assert_eq!(status(req).await, StatusCode::OK);
+        let req = cookie_post().insert_header(("x-api-key", "brk_key"));
+        assert_eq!(status(req).await, StatusCode::OK);
+
+        let req = TestRequest::get()
+            .uri("/")
+            .cookie(Cookie::new("auth_token", "session"));
+        assert_eq!(status(req).await, StatusCode::OK);
+        // Without a session cookie there is nothing a cross-site page could ride on
+        assert_eq!(status(TestRequest::post().uri("/")).await, StatusCode::OK);
+    }
+}
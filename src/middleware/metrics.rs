@@ -0,0 +1,89 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    time::Instant,
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    web::Data,
+    Error,
+};
+use futures_util::future::LocalBoxFuture;
+
+use crate::metrics::Metrics;
+
+// Middleware counting requests and observing their latency, labelled by route pattern
+// (not the raw path) so the number of series stays bounded
+pub struct RequestMetrics {
+    metrics: Data<Metrics>,
+}
+
+impl RequestMetrics {
+    pub fn new(metrics: Data<Metrics>) -> RequestMetrics {
+        RequestMetrics { metrics }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RequestMetrics
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = RequestMetricsService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RequestMetricsService {
+            service: Rc::new(service),
+            metrics: self.metrics.clone(),
+        }))
+    }
+}
+
+pub struct RequestMetricsService<S> {
+    service: Rc<S>,
+    metrics: Data<Metrics>,
+}
+
+impl<S, B> Service<ServiceRequest> for RequestMetricsService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let method = req.method().to_string();
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let metrics = self.metrics.clone();
+        let started = Instant::now();
+        let fut = self.service.call(req);
+
+        Box::pin(async move {
+            let result = fut.await;
+            let status = match &result {
+                Ok(response) => response.status(),
+                Err(e) => e.as_response_error().status_code(),
+            };
+
+            let labels = [method.as_str(), route.as_str(), status.as_str()];
+            metrics.http_requests.with_label_values(&labels).inc();
+            metrics
+                .http_duration
+                .with_label_values(&labels)
+                .observe(started.elapsed().as_secs_f64());
+
+            result
+        })
+    }
+}
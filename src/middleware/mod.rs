@@ -0,0 +1,12 @@
+pub mod cors;
+pub use cors::cors;
+pub mod csrf;
+pub use csrf::CsrfProtection;
+pub mod rate_limit;
+pub use rate_limit::{InMemoryStore, Quota, RateLimiter};
+pub mod request_id;
+pub use request_id::RequestIdMiddleware;
+pub mod request_tracing;
+pub use request_tracing::TracingLogger;
+pub mod metrics;
+pub use metrics::RequestMetrics;
@@ -0,0 +1,333 @@
+use std::{
+    collections::HashMap,
+    future::{ready, Future, Ready},
+    pin::Pin,
+    rc::Rc,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+use actix_web::{
+    body::{EitherBody, MessageBody},
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER},
+    web::Data,
+    Error, HttpResponse,
+};
+use async_trait::async_trait;
+use sqlx::MySqlPool;
+
+use crate::routes::{
+    api_key::api_key_owner,
+    jwt::{api_key_from_request, user_id_from_request},
+    SuccessResponse,
+};
+
+// Once the in-memory store holds more buckets than this, idle full buckets are pruned
+const PRUNE_THRESHOLD: usize = 10_000;
+
+// A quota of `requests` per `period`, enforced as a token bucket that holds at most
+// `requests` tokens and refills continuously over `period`
+#[derive(Clone, Copy, Debug)]
+pub struct Quota {
+    pub requests: u32,
+    pub period: Duration,
+}
+
+impl Quota {
+    pub fn new(requests: u32, period: Duration) -> Quota {
+        Quota { requests, period }
+    }
+
+    // Parse a quota written as "<requests>/<seconds>", e.g. "10/60"
+    pub fn parse(value: &str) -> Option<Quota> {
+        let (requests, seconds) = value.split_once('/')?;
+        let requests: u32 = requests.trim().parse().ok()?;
+        let seconds: u64 = seconds.trim().parse().ok()?;
+        if requests == 0 || seconds == 0 {
+            return None;
+        }
+        Some(Quota::new(requests, Duration::from_secs(seconds)))
+    }
+
+    // Tokens added back to the bucket per second
+    fn refill_rate(&self) -> f64 {
+        self.requests as f64 / self.period.as_secs_f64()
+    }
+}
+
+// Outcome of taking a token from a bucket
+#[derive(Debug)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    pub reset_after: Duration, // Time until the bucket is full again
+    pub retry_after: Duration, // Time until the next token is available (zero when allowed)
+}
+
+// Storage backend for rate limit buckets. The in-memory store below is per-process;
+// a shared backend (e.g. Redis) can implement this trait to limit across instances.
+#[async_trait]
+pub trait RateLimitStore: Send + Sync {
+    async fn acquire(&self, key: &str, quota: Quota) -> Decision;
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+// Process-local token bucket store
+#[derive(Default)]
+pub struct InMemoryStore {
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl InMemoryStore {
+    pub fn new() -> InMemoryStore {
+        InMemoryStore::default()
+    }
+}
+
+#[async_trait]
+impl RateLimitStore for InMemoryStore {
+    async fn acquire(&self, key: &str, quota: Quota) -> Decision {
+        let now = Instant::now();
+        let capacity = quota.requests as f64;
+        let rate = quota.refill_rate();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|e| e.into_inner());
+
+        // Drop buckets that have been idle long enough to be full again
+        if buckets.len() > PRUNE_THRESHOLD {
+            buckets.retain(|_, b| {
+                b.tokens + now.duration_since(b.updated_at).as_secs_f64() * rate < capacity
+            });
+        }
+
+        buckets
+            .entry(key.to_string())
+            .or_insert(Bucket {
+                tokens: capacity,
+                updated_at: now,
+            })
+            .take(quota, now)
+    }
+}
+
+impl Bucket {
+    // Refill the bucket for the time elapsed since it was last touched, then take a token
+    // if one is available
+    fn take(&mut self, quota: Quota, now: Instant) -> Decision {
+        let capacity = quota.requests as f64;
+        let rate = quota.refill_rate();
+
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate).min(capacity);
+        self.updated_at = now;
+
+        let allowed = self.tokens >= 1.0;
+        if allowed {
+            self.tokens -= 1.0;
+        }
+
+        let retry_after = if allowed {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64((1.0 - self.tokens) / rate)
+        };
+
+        Decision {
+            allowed,
+            limit: quota.requests,
+            remaining: self.tokens.floor() as u32,
+            reset_after: Duration::from_secs_f64((capacity - self.tokens) / rate),
+            retry_after,
+        }
+    }
+}
+
+// Middleware factory limiting a group of routes to a quota per client.
+// Clients are identified by their authenticated user ID, from a session token or an API key,
+// falling back to their IP address.
+#[derive(Clone)]
+pub struct RateLimiter {
+    group: &'static str,
+    quota: Quota,
+    store: Arc<dyn RateLimitStore>,
+}
+
+impl RateLimiter {
+    pub fn new(group: &'static str, quota: Quota, store: Arc<dyn RateLimitStore>) -> RateLimiter {
+        RateLimiter {
+            group,
+            quota,
+            store,
+        }
+    }
+}
+
+impl<S, B> Transform<S, ServiceRequest> for RateLimiter
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Transform = RateLimitMiddleware<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(RateLimitMiddleware {
+            service: Rc::new(service),
+            limiter: self.clone(),
+        }))
+    }
+}
+
+pub struct RateLimitMiddleware<S> {
+    service: Rc<S>,
+    limiter: RateLimiter,
+}
+
+impl<S, B> Service<ServiceRequest> for RateLimitMiddleware<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: MessageBody + 'static,
+{
+    type Response = ServiceResponse<EitherBody<B>>;
+    type Error = Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>>>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let service = Rc::clone(&self.service);
+        let limiter = self.limiter.clone();
+
+        Box::pin(async move {
+            let key = client_key(limiter.group, &req).await;
+            let decision = limiter.store.acquire(&key, limiter.quota).await;
+
+            if !decision.allowed {
+                let mut response = HttpResponse::TooManyRequests().json(SuccessResponse::<()> {
+                    success: false,
+                    message: "Too many requests, please slow down".to_string(),
+                    data: None,
+                });
+                insert_headers(response.headers_mut(), &decision);
+                return Ok(req.into_response(response).map_into_right_body());
+            }
+
+            let mut response = service.call(req).await?;
+            insert_headers(response.headers_mut(), &decision);
+            Ok(response.map_into_left_body())
+        })
+    }
+}
+
+// Build the bucket key for a request: "<group>:user:<id>" or "<group>:ip:<addr>".
+// An API key counts for its owner only once it is known, so sending made-up keys does not
+// earn a fresh bucket per request.
+async fn client_key(group: &str, req: &ServiceRequest) -> String {
+    let user_id = match api_key_from_request(req.request()) {
+        Some(key) => match req.app_data::<Data<MySqlPool>>() {
+            Some(db) => api_key_owner(db, &key).await,
+            None => None,
+        },
+        None => user_id_from_request(req.request()),
+    };
+    match user_id {
+        Some(user_id) => format!("{}:user:{}", group, user_id),
+        // Use the socket address rather than X-Forwarded-For, which clients can spoof
+        None => match req.peer_addr() {
+            Some(addr) => format!("{}:ip:{}", group, addr.ip()),
+            None => format!("{}:ip:unknown", group),
+        },
+    }
+}
+
+// Attach X-RateLimit-* headers, plus Retry-After when the request was rejected
+fn insert_headers(headers: &mut HeaderMap, decision: &Decision) {
+    let mut insert = |name: &'static str, value: u64| {
+        headers.insert(HeaderName::from_static(name), HeaderValue::from(value));
+    };
+    insert("x-ratelimit-limit", decision.limit as u64);
+    insert("x-ratelimit-remaining", decision.remaining as u64);
+    insert(
+        "x-ratelimit-reset",
+        decision.reset_after.as_secs_f64().ceil() as u64,
+    );
+
+    if !decision.allowed {
+        let retry_after = decision.retry_after.as_secs_f64().ceil().max(1.0) as u64;
+        headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn full(quota: Quota, now: Instant) -> Bucket {
+        Bucket {
+            tokens: quota.requests as f64,
+            updated_at: now,
+        }
+    }
+
+    #[test]
+    fn empties_then_refills_over_the_period() {
+        let quota = Quota::new(2, Duration::from_secs(10));
+        let start = Instant::now();
+        let mut bucket = full(quota, start);
+
+        assert!(bucket.take(quota, start).allowed);
+        let last = bucket.take(quota, start);
+        assert!(last.allowed);
+        assert_eq!(last.remaining, 0);
+        assert_eq!(last.reset_after, Duration::from_secs(10));
+
+        let rejected = bucket.take(quota, start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.retry_after, Duration::from_secs(5));
+
+        // One token comes back every 5 seconds
+        assert!(!bucket.take(quota, start + Duration::from_secs(4)).allowed);
+        assert!(bucket.take(quota, start + Duration::from_secs(5)).allowed);
+        // Never more than the quota, however long the bucket sat idle
+        let idle = bucket.take(quota, start + Duration::from_secs(3600));
+        assert_eq!(idle.remaining, 1);
+    }
+
+    #[test]
+    fn rejection_sets_retry_after_in_whole_seconds() {
+        let quota = Quota::new(1, Duration::from_secs(60));
+        let now = Instant::now();
+        let mut bucket = full(quota, now);
+        bucket.take(quota, now);
+        let rejected = bucket.take(quota, now + Duration::from_millis(59_500));
+
+        let mut headers = HeaderMap::new();
+        insert_headers(&mut headers, &rejected);
+        assert_eq!(headers.get(RETRY_AFTER).unwrap(), "1");
+        assert_eq!(headers.get("x-ratelimit-remaining").unwrap(), "0");
+
+        let mut headers = HeaderMap::new();
+        insert_headers(
+            &mut headers,
+            &bucket.take(quota, now + Duration::from_secs(60)),
+        );
+        assert!(headers.get(RETRY_AFTER).is_none());
+    }
+
+    #[test]
+    fn parses_quotas() {
+        let quota = Quota::parse(" 10 / 60 ").unwrap();
+        assert_eq!(quota.requests, 10);
+        assert_eq!(quota.period, Duration::from_secs(60));
+        assert!(Quota::parse("0/60").is_none());
+        assert!(Quota::parse("10").is_none());
+    }
+}
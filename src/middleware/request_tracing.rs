@@ -0,0 +1,102 @@
+use std::{
+    future::{ready, Ready},
+    rc::Rc,
+    time::Instant,
+};
+
+use actix_web::{
+    dev::{forward_ready, Service, ServiceRequest, ServiceResponse, Transform},
+    Error, HttpMessage,
+};
+use futures_util::future::LocalBoxFuture;
+use tracing::{field::Empty, Instrument};
+
+use super::request_id::RequestId;
+
+// Middleware wrapping each request in a span with its method, route, request ID, the
+// authenticated user (recorded by the auth layer), status and latency, and logging its completion
+pub struct TracingLogger;
+
+impl<S, B> Transform<S, ServiceRequest> for TracingLogger
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Transform = TracingLoggerService<S>;
+    type InitError = ();
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ready(Ok(TracingLoggerService {
+            service: Rc::new(service),
+        }))
+    }
+}
+
+pub struct TracingLoggerService<S> {
+    service: Rc<S>,
+}
+
+impl<S, B> Service<ServiceRequest> for TracingLoggerService<S>
+where
+    S: Service<ServiceRequest, Response = ServiceResponse<B>, Error = Error> + 'static,
+    B: 'static,
+{
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    forward_ready!(service);
+
+    fn call(&self, req: ServiceRequest) -> Self::Future {
+        let request_id = req
+            .extensions()
+            .get::<RequestId>()
+            .map(|id| id.0.clone())
+            .unwrap_or_default();
+        // Use the route pattern rather than the raw path to keep IDs and links out of the span name
+        let route = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+
+        let span = tracing::info_span!(
+            "http_request",
+            method = %req.method(),
+            route = %route,
+            request_id = %request_id,
+            user_id = Empty,
+            status = Empty,
+            latency_ms = Empty,
+        );
+
+        let started = Instant::now();
+        let fut = span.in_scope(|| self.service.call(req));
+
+        Box::pin(
+            async move {
+                let result = fut.await;
+                let status = match &result {
+                    Ok(response) => response.status(),
+                    Err(e) => e.as_response_error().status_code(),
+                };
+
+                let span = tracing::Span::current();
+                span.record("status", status.as_u16());
+                span.record("latency_ms", started.elapsed().as_millis() as u64);
+
+                if status.is_server_error() {
+                    tracing::error!("request failed");
+                } else if status.is_client_error() {
+                    tracing::warn!("request rejected");
+                } else {
+                    tracing::info!("request completed");
+                }
+
+                result
+            }
+            .instrument(span),
+        )
+    }
+}
@@ -0,0 +1,145 @@
+use utoipa::{
+    openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+    Modify, OpenApi, ToSchema,
+};
+
+use brainly_types::{
+    api_key::{ApiKey as ApiKeyMetadata, CreateApiKey, CreatedApiKey},
+    brain::{BrainShare, ShareBrain, SharedContent},
+    content::{
+        BulkAction, BulkContent, BulkItemResult, BulkResult, Content, ContentResponse, OnDuplicate,
+        TrashedContent, UserContents, Visibility,
+    },
+    export::{Export, ExportedContent, ExportedUser},
+    health::CheckResult,
+    import::{ImportFormat, ImportJob, ImportOutcome, ImportRowReport, ImportStatus},
+    subscription::{CreateSubscription, Subscription},
+    user::CreateUser,
+};
+
+use crate::{
+    health, metrics,
+    routes::{api_key, brain, content, export, import, oidc, subscription, trash, user},
+};
+
+// Single content lookups return the row as a JSON array: [id, title, type_, link, url]
+#[allow(dead_code)] // Only used to describe the response shape
+#[derive(ToSchema)]
+pub(crate) struct ContentRow(i32, String, String, String, Option<String>);
+
+// Form fields of an import upload
+#[allow(dead_code)] // Only used to describe the request shape
+#[derive(ToSchema)]
+pub(crate) struct ImportUpload {
+    #[schema(format = Binary, content_media_type = "application/octet-stream")]
+    file: String, // brainly JSON export, CSV, bookmarks HTML, or a Pocket, Raindrop or Pinboard export
+    format: Option<ImportFormat>, // Detected from the file name and contents when omitted
+}
+
+// Stands in for `()` in `SuccessResponse<()>`, whose `data` is always null
+#[allow(dead_code)] // Only used to describe the response shape
+#[derive(ToSchema)]
+pub(crate) struct NoData;
+
+// OpenAPI 3 description of the HTTP API, served at /api/v1/openapi.json.
+// Each operation is described by the `#[utoipa::path]` attribute on its handler.
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "Brainly API", description = "Save, organise and share links to content"),
+    paths(
+        user::create_user,
+        user::signin_user,
+        oidc::login,
+        oidc::callback,
+        api_key::create_api_key,
+        api_key::get_api_keys,
+        api_key::delete_api_key,
+        content::create_content,
+        content::bulk_content,
+        content::get_all_content,
+        content::get_content_by_id,
+        content::delete_content,
+        content::get_content_by_link,
+        trash::restore_content,
+        trash::get_trash,
+        trash::empty_trash,
+        trash::purge_content,
+        export::export_json,
+        export::export_markdown,
+        import::import_file,
+        import::get_import_job,
+        brain::share_brain,
+        brain::feed_atom,
+        brain::feed_rss,
+        brain::shared_content,
+        subscription::create_subscription,
+        subscription::get_subscriptions,
+        subscription::delete_subscription,
+        metrics::metrics_endpoint,
+        health::liveness,
+        health::readiness,
+    ),
+    components(schemas(
+        Content,
+        ContentResponse,
+        OnDuplicate,
+        Visibility,
+        UserContents,
+        TrashedContent,
+        BulkAction,
+        BulkContent,
+        BulkItemResult,
+        BulkResult,
+        CreateUser,
+        CreateApiKey,
+        CreatedApiKey,
+        ApiKeyMetadata,
+        ContentRow,
+        Export,
+        ExportedUser,
+        ExportedContent,
+        ImportFormat,
+        ImportStatus,
+        ImportOutcome,
+        ImportRowReport,
+        ImportJob,
+        ShareBrain,
+        BrainShare,
+        SharedContent,
+        CreateSubscription,
+        Subscription,
+        CheckResult,
+    )),
+    modifiers(&SecuritySchemes),
+    tags(
+        (name = "user", description = "Accounts and authentication"),
+        (name = "content", description = "Saved content"),
+        (name = "brain", description = "Sharing a brain publicly"),
+        (name = "feeds", description = "RSS and Atom feeds saved into the brain automatically"),
+        (name = "operations", description = "Probes and metrics"),
+    )
+)]
+pub struct ApiDoc;
+
+// Register the ways a request can authenticate
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut utoipa::openapi::OpenApi) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+        // Cookie authenticated POST and DELETE requests must also echo the csrf_token
+        // cookie in the X-CSRF-Token header
+        components.add_security_scheme(
+            "cookie_auth",
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::new("auth_token"))),
+        );
+        components.add_security_scheme(
+            "bearer_auth",
+            SecurityScheme::Http(HttpBuilder::new().scheme(HttpAuthScheme::Bearer).build()),
+        );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-Api-Key"))),
+        );
+    }
+}
@@ -0,0 +1,329 @@
+// Handlers for managing API keys. The request and response types live in brainly-types.
+
+use std::{collections::HashMap, str::FromStr};
+
+use actix_web::{
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse, Responder,
+};
+use brainly_types::api_key::{ApiKey as ApiKeyMetadata, CreateApiKey, CreatedApiKey};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use validator::Validate;
+
+use crate::openapi::NoData;
+use crate::routes::utils::generate_random_string;
+
+use super::{jwt::validate_token, validation::validation_error, SuccessResponse};
+
+pub use brainly_types::api_key::Scope;
+
+// Every API key starts with this prefix so it can be told apart from a JWT in a Bearer header
+pub const API_KEY_PREFIX: &str = "brk_";
+
+// API key metadata row: (id, name, prefix, scopes, expires_at, last_used_at, created_at)
+type ApiKeyRow = (
+    i32,
+    String,
+    String,
+    String,
+    Option<DateTime<Utc>>,
+    Option<DateTime<Utc>>,
+    DateTime<Utc>,
+);
+
+// Hash an API key for storage and lookup. Keys are long random strings, so a fast
+// unsalted hash is enough (unlike passwords, they cannot be brute forced from a dictionary)
+fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+// Parse the comma separated scope column back into scopes, ignoring unknown entries
+fn parse_scopes(scopes: &str) -> Vec<Scope> {
+    scopes
+        .split(',')
+        .filter_map(|scope| Scope::from_str(scope.trim()).ok())
+        .collect()
+}
+
+// Whether a key with the stored `scopes` column may act with `scope`
+fn grants(scopes: &str, scope: Scope) -> bool {
+    parse_scopes(scopes).contains(&scope)
+}
+
+// The user an unexpired API key belongs to, whatever its scopes. Used to rate limit a key's
+// requests per user before the handler checks the scope.
+pub async fn api_key_owner(db: &MySqlPool, key: &str) -> Option<i32> {
+    sqlx::query_scalar(
+        "SELECT user_id FROM api_keys
+         WHERE key_hash = ? AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)",
+    )
+    .bind(hash_api_key(key))
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten()
+}
+
+// Look up an API key, returning the owning user ID when the key exists, has not expired
+// and grants `scope`. Records the time of use on success.
+pub async fn verify_api_key(db: &MySqlPool, key: &str, scope: Scope) -> Result<i32, HttpResponse> {
+    let row: Result<Option<(i32, i32, String)>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, user_id, scopes FROM api_keys
+         WHERE key_hash = ? AND (expires_at IS NULL OR expires_at > CURRENT_TIMESTAMP)",
+    )
+    .bind(hash_api_key(key))
+    .fetch_optional(db)
+    .await;
+
+    match row {
+        Ok(Some((id, user_id, scopes))) => {
+            if !grants(&scopes, scope) {
+                return Err(HttpResponse::Forbidden().json(SuccessResponse::<()> {
+                    success: false,
+                    message: format!("API key is missing the {} scope", scope),
+                    data: None,
+                }));
+            }
+
+            // Failing to record usage should not fail the request
+            let _ =
+                sqlx::query("UPDATE api_keys SET last_used_at = CURRENT_TIMESTAMP WHERE id = ?")
+                    .bind(id)
+                    .execute(db)
+                    .await;
+
+            Ok(user_id)
+        }
+        Ok(None) => Err(HttpResponse::Unauthorized().json(SuccessResponse::<()> {
+            success: false,
+            message: "Invalid or expired API key".to_string(),
+            data: None,
+        })),
+        Err(e) => Err(
+            HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            }),
+        ),
+    }
+}
+
+// Create a new API key for the signed in user
+#[utoipa::path(post, path = "/api/v1/user/api-keys", tag = "user",
+    request_body = CreateApiKey,
+    security(("cookie_auth" = []), ("bearer_auth" = [])),
+    responses(
+        (status = 201, description = "Key created, the plain key is only returned here", body = SuccessResponse<CreatedApiKey>),
+        (status = 400, description = "No scopes requested", body = SuccessResponse<NoData>),
+        (status = 401, description = "Not signed in", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 422, description = "Invalid fields", body = SuccessResponse<HashMap<String, Vec<String>>>),
+    ))]
+pub async fn create_api_key(
+    db: Data<MySqlPool>,      // Database connection pool
+    req: HttpRequest,         // Incoming HTTP request
+    body: Json<CreateApiKey>, // JSON payload describing the key
+) -> impl Responder {
+    // API keys can only be managed from a signed in session, not with another API key
+    let user_id = match validate_token(req).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    if let Err(errors) = body.validate() {
+        return validation_error(errors);
+    }
+    if body.scopes.is_empty() {
+        return HttpResponse::BadRequest().json(SuccessResponse::<()> {
+            success: false,
+            message: "At least one scope is required".to_string(),
+            data: None,
+        });
+    }
+
+    let key = format!("{}{}", API_KEY_PREFIX, generate_random_string(40));
+    let prefix = key[..API_KEY_PREFIX.len() + 6].to_string();
+    let scopes = body
+        .scopes
+        .iter()
+        .map(|scope| scope.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+
+    let result = sqlx::query(
+        "INSERT INTO api_keys (user_id, name, prefix, key_hash, scopes, expires_at) VALUES (?, ?, ?, ?, ?, ?)",
+    )
+    .bind(user_id)
+    .bind(&body.name)
+    .bind(&prefix)
+    .bind(hash_api_key(&key))
+    .bind(&scopes)
+    .bind(body.expires_at)
+    .execute(&**db)
+    .await;
+
+    match result {
+        Ok(data) => HttpResponse::Created().json(SuccessResponse {
+            success: true,
+            message: "API key created, store it now as it will not be shown again".to_string(),
+            data: Some(CreatedApiKey {
+                key,
+                metadata: ApiKeyMetadata {
+                    id: data.last_insert_id() as i32,
+                    name: body.name.clone(),
+                    prefix,
+                    scopes,
+                    expires_at: body.expires_at,
+                    last_used_at: None,
+                    created_at: Utc::now(),
+                },
+            }),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+            success: false,
+            message: e.to_string(),
+            data: None,
+        }),
+    }
+}
+
+// List the signed in user's API keys
+#[utoipa::path(get, path = "/api/v1/user/api-keys", tag = "user",
+    security(("cookie_auth" = []), ("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "The user's API keys", body = SuccessResponse<Vec<ApiKeyMetadata>>),
+        (status = 401, description = "Not signed in", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_api_keys(db: Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let user_id = match validate_token(req).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let keys = sqlx::query_as::<_, ApiKeyRow>(
+        "SELECT id, name, prefix, scopes, expires_at, last_used_at, created_at
+         FROM api_keys WHERE user_id = ? ORDER BY created_at DESC",
+    )
+    .bind(user_id)
+    .fetch_all(&**db)
+    .await;
+
+    match keys {
+        Ok(rows) => HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: "API keys fetched successfully".to_string(),
+            data: Some(
+                rows.into_iter()
+                    .map(
+                        |(id, name, prefix, scopes, expires_at, last_used_at, created_at)| {
+                            ApiKeyMetadata {
+                                id,
+                                name,
+                                prefix,
+                                scopes,
+                                expires_at,
+                                last_used_at,
+                                created_at,
+                            }
+                        },
+                    )
+                    .collect::<Vec<_>>(),
+            ),
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+            success: false,
+            message: e.to_string(),
+            data: None,
+        }),
+    }
+}
+
+// Revoke one of the signed in user's API keys
+#[utoipa::path(delete, path = "/api/v1/user/api-keys/{id}", tag = "user",
+    params(("id" = i32, Path, description = "API key ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = [])),
+    responses(
+        (status = 200, description = "Key revoked", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 404, description = "Key not found", body = SuccessResponse<NoData>),
+    ))]
+pub async fn delete_api_key(
+    db: Data<MySqlPool>, // Database connection pool
+    params: Path<i32>,   // Extracted API key ID from the URL path
+    req: HttpRequest,    // Incoming HTTP request
+) -> impl Responder {
+    let user_id = match validate_token(req).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let result = sqlx::query("DELETE FROM api_keys WHERE id = ? AND user_id = ?")
+        .bind(params.into_inner())
+        .bind(user_id)
+        .execute(&**db)
+        .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            HttpResponse::Ok().json(SuccessResponse::<()> {
+                success: true,
+                message: "API key revoked".to_string(),
+                data: None,
+            })
+        }
+        Ok(_) => HttpResponse::NotFound().json(SuccessResponse::<()> {
+            success: false,
+            message: "API key not found".to_string(),
+            data: None,
+        }),
+        Err(e) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+            success: false,
+            message: e.to_string(),
+            data: None,
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hashes_keys_to_stable_hex_digests() {
+        let hash = hash_api_key("brk_abc");
+        assert_eq!(hash, hash_api_key("brk_abc"));
+        assert_eq!(hash.len(), 64);
+        assert!(hash.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(hash, hash_api_key("brk_abd"));
+        assert_ne!(hash, "brk_abc");
+    }
+
+    #[test]
+    fn limits_key_names_to_the_column_size() {
+        let key = |name: &str| CreateApiKey {
+            name: name.to_string(),
+            scopes: vec![Scope::ContentRead],
+            expires_at: None,
+        };
+        assert!(key("laptop script").validate().is_ok());
+        assert!(key(&"é".repeat(100)).validate().is_ok());
+        assert!(key("").validate().is_err());
+        assert!(key(&"x".repeat(101)).validate().is_err());
+    }
+
+    #[test]
+    fn grants_only_stored_scopes() {
+        assert!(grants("content:read", Scope::ContentRead));
+        assert!(!grants("content:read", Scope::ContentWrite));
+        assert!(grants("content:read, content:write", Scope::ContentWrite));
+        // Unknown entries, e.g. from a newer server, are ignored rather than failing the key
+        assert_eq!(
+            parse_scopes("admin,content:write"),
+            vec![Scope::ContentWrite]
+        );
+        assert!(!grants("", Scope::ContentRead));
+    }
+}
@@ -0,0 +1,345 @@
+// Handlers for sharing a brain publicly
+
+use std::time::SystemTime;
+
+use actix_web::{
+    http::header::{
+        CacheControl, CacheDirective, ETag, EntityTag, HttpDate, IfModifiedSince, IfNoneMatch,
+        LastModified,
+    },
+    web::{Data, Json, Path},
+    HttpMessage, HttpRequest, HttpResponse, Responder,
+};
+use brainly_types::brain::{BrainShare, ShareBrain, SharedContent};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use tracing::{info_span, Instrument};
+
+use crate::config::PublicUrlConfig;
+use crate::feed::{self, FeedInfo};
+use crate::openapi::NoData;
+use crate::routes::utils::{generate_random_string, public_base_url};
+
+use super::{api_key::Scope, export::recent_shared_contents, jwt::authenticate, SuccessResponse};
+
+// Items in a feed. Readers poll, so older items have already been seen.
+const FEED_MAX_ITEMS: u32 = 50;
+
+// How long shared caches and readers may reuse a feed without asking again, in seconds
+const FEED_MAX_AGE: u32 = 300;
+
+// Shared content row: (id, title, type_, url, created_at)
+type SharedRow = (i32, String, String, Option<String>, DateTime<Utc>);
+
+// Feed formats served for a shared brain
+enum FeedFormat {
+    Atom,
+    Rss,
+}
+
+// Turn public sharing on or off. Sharing again keeps the existing hash, so feed URLs
+// already handed out keep working; turning sharing off invalidates them for good.
+#[utoipa::path(post, path = "/api/v1/brain/share", tag = "brain",
+    request_body = ShareBrain,
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Sharing updated, with the share hash while shared", body = SuccessResponse<BrainShare>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token, or API key lacks the scope", body = SuccessResponse<NoData>),
+    ))]
+pub async fn share_brain(
+    db: Data<MySqlPool>,
+    req: HttpRequest,
+    body: Json<ShareBrain>,
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let result = if body.share {
+        sqlx::query(
+            "UPDATE users SET share_hash = COALESCE(share_hash, ?),
+             shared_at = COALESCE(shared_at, CURRENT_TIMESTAMP) WHERE id = ?",
+        )
+        .bind(generate_random_string(32))
+        .bind(user_id)
+        .execute(&**db)
+        .instrument(info_span!("db_query", query = "update_user_share"))
+        .await
+    } else {
+        sqlx::query("UPDATE users SET share_hash = NULL, shared_at = NULL WHERE id = ?")
+            .bind(user_id)
+            .execute(&**db)
+            .instrument(info_span!("db_query", query = "update_user_share"))
+            .await
+    };
+    let share_hash = match result {
+        Ok(_) => {
+            sqlx::query_scalar::<_, Option<String>>("SELECT share_hash FROM users WHERE id = ?")
+                .bind(user_id)
+                .fetch_one(&**db)
+                .instrument(info_span!("db_query", query = "select_user_share"))
+                .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match share_hash {
+        Ok(share_hash) => HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: if share_hash.is_some() {
+                "Brain Shared"
+            } else {
+                "Brain Private"
+            }
+            .to_string(),
+            data: Some(BrainShare { share_hash }),
+        }),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to update brain sharing");
+            HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            })
+        }
+    }
+}
+
+// Atom feed of a shared brain
+#[utoipa::path(get, path = "/api/v1/brain/{share_hash}/feed.atom", tag = "brain",
+    params(
+        ("share_hash" = String, Path, description = "Share hash of the brain"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the copy the reader has"),
+        ("If-Modified-Since" = Option<String>, Header, description = "Last-Modified of the copy the reader has"),
+    ),
+    responses(
+        (status = 200, description = "Atom feed of the newest 50 items, newest first", body = String, content_type = "application/atom+xml"),
+        (status = 304, description = "The reader's copy is current"),
+        (status = 404, description = "No brain is shared under this hash", body = SuccessResponse<NoData>),
+    ))]
+pub async fn feed_atom(
+    db: Data<MySqlPool>,
+    public_url: Data<PublicUrlConfig>, // Base of the links in the feed
+    req: HttpRequest,
+    params: Path<String>, // Share hash
+) -> impl Responder {
+    feed(
+        &db,
+        &public_url,
+        &req,
+        &params.into_inner(),
+        FeedFormat::Atom,
+    )
+    .await
+}
+
+// RSS feed of a shared brain
+#[utoipa::path(get, path = "/api/v1/brain/{share_hash}/feed.rss", tag = "brain",
+    params(
+        ("share_hash" = String, Path, description = "Share hash of the brain"),
+        ("If-None-Match" = Option<String>, Header, description = "ETag of the copy the reader has"),
+        ("If-Modified-Since" = Option<String>, Header, description = "Last-Modified of the copy the reader has"),
+    ),
+    responses(
+        (status = 200, description = "RSS 2.0 feed of the newest 50 items, newest first", body = String, content_type = "application/rss+xml"),
+        (status = 304, description = "The reader's copy is current"),
+        (status = 404, description = "No brain is shared under this hash", body = SuccessResponse<NoData>),
+    ))]
+pub async fn feed_rss(
+    db: Data<MySqlPool>,
+    public_url: Data<PublicUrlConfig>, // Base of the links in the feed
+    req: HttpRequest,
+    params: Path<String>, // Share hash
+) -> impl Responder {
+    feed(
+        &db,
+        &public_url,
+        &req,
+        &params.into_inner(),
+        FeedFormat::Rss,
+    )
+    .await
+}
+
+// One item of a shared brain, for anyone with its link. Private items are not found.
+#[utoipa::path(get, path = "/api/v1/brain/{share_hash}/content/{link}", tag = "brain",
+    params(
+        ("share_hash" = String, Path, description = "Share hash of the brain"),
+        ("link" = String, Path, description = "Generated content link"),
+    ),
+    responses(
+        (status = 200, description = "The item", body = SuccessResponse<SharedContent>),
+        (status = 404, description = "No brain is shared under this hash, or it has no such shared item", body = SuccessResponse<NoData>),
+    ))]
+pub async fn shared_content(
+    db: Data<MySqlPool>,
+    params: Path<(String, String)>, // Share hash and content link
+) -> impl Responder {
+    let (share_hash, link) = params.into_inner();
+    let (user_id, _, _) = match shared_brain_owner(&db, &share_hash).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return not_found("Brain Not Found"),
+        Err(e) => return feed_error(e),
+    };
+
+    let row: Result<Option<SharedRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, title, type_, url, created_at FROM contents
+         WHERE user_id = ? AND link = ? AND deleted_at IS NULL AND visibility = 'shared'",
+    )
+    .bind(user_id)
+    .bind(&link)
+    .fetch_optional(&**db)
+    .instrument(info_span!("db_query", query = "select_shared_content"))
+    .await;
+    let (id, title, type_, url, created_at) = match row {
+        Ok(Some(row)) => row,
+        Ok(None) => return not_found("Content Not Found"),
+        Err(e) => return feed_error(e),
+    };
+    let Ok(type_) = type_.parse() else {
+        return not_found("Content Not Found"); // Invalid content type, hidden like everywhere else
+    };
+
+    let tags: Result<Vec<String>, sqlx::Error> =
+        sqlx::query_scalar("SELECT tag FROM content_tags WHERE content_id = ? ORDER BY tag")
+            .bind(id)
+            .fetch_all(&**db)
+            .instrument(info_span!("db_query", query = "select_content_tags"))
+            .await;
+    match tags {
+        Ok(tags) => HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: "Content fetched successfully".to_string(),
+            data: Some(SharedContent {
+                type_,
+                title,
+                url,
+                tags,
+                created_at,
+            }),
+        }),
+        Err(e) => feed_error(e),
+    }
+}
+
+// Owner of the brain shared under `share_hash`: (id, username, shared_at).
+// Disabled accounts stop publishing, like they stop signing in.
+async fn shared_brain_owner(
+    db: &MySqlPool,
+    share_hash: &str,
+) -> Result<Option<(i32, String, DateTime<Utc>)>, sqlx::Error> {
+    sqlx::query_as(
+        "SELECT id, username, shared_at FROM users WHERE share_hash = ? AND disabled_at IS NULL",
+    )
+    .bind(share_hash)
+    .fetch_optional(db)
+    .instrument(info_span!("db_query", query = "select_user_by_share_hash"))
+    .await
+}
+
+// Render the newest shared items of a shared brain, answering 304 Not Modified when the reader
+// already has the current version
+async fn feed(
+    db: &MySqlPool,
+    public_url: &PublicUrlConfig,
+    req: &HttpRequest,
+    share_hash: &str,
+    format: FeedFormat,
+) -> HttpResponse {
+    let (user_id, username, shared_at) = match shared_brain_owner(db, share_hash).await {
+        Ok(Some(owner)) => owner,
+        Ok(None) => return not_found("Brain Not Found"),
+        Err(e) => return feed_error(e),
+    };
+
+    let items = match recent_shared_contents(db, user_id, FEED_MAX_ITEMS)
+        .instrument(info_span!("db_query", query = "select_recent_contents"))
+        .await
+    {
+        Ok(items) => items,
+        Err(e) => return feed_error(e),
+    };
+
+    // The feed changes when an item is saved, or when sharing starts
+    let updated = items
+        .iter()
+        .map(|item| item.created_at)
+        .fold(shared_at, DateTime::max);
+    let base_url = public_base_url(public_url, req);
+    let info = FeedInfo {
+        title: format!("{}'s brain", username),
+        author: username,
+        self_url: format!("{}{}", base_url, req.path()),
+        share_url: format!("{}/api/v1/brain/{}", base_url, share_hash),
+        base_url,
+        updated,
+    };
+    let (body, content_type) = match format {
+        FeedFormat::Atom => (
+            feed::atom(&info, &items),
+            "application/atom+xml; charset=utf-8",
+        ),
+        FeedFormat::Rss => (
+            feed::rss(&info, &items),
+            "application/rss+xml; charset=utf-8",
+        ),
+    };
+
+    // Hashing the document also catches deleted items, which leave the date unchanged
+    let etag = EntityTag::new_strong(hex::encode(&Sha256::digest(body.as_bytes())[..16]));
+    let last_modified = HttpDate::from(SystemTime::from(updated));
+    if is_not_modified(req, &etag, last_modified) {
+        return HttpResponse::NotModified()
+            .insert_header(ETag(etag))
+            .insert_header(LastModified(last_modified))
+            .finish();
+    }
+
+    HttpResponse::Ok()
+        .content_type(content_type)
+        .insert_header(ETag(etag))
+        .insert_header(LastModified(last_modified))
+        .insert_header(CacheControl(vec![
+            // Links built from the request's Host header are only fit for the reader who sent it
+            if public_url.base_url.is_some() {
+                CacheDirective::Public
+            } else {
+                CacheDirective::Private
+            },
+            CacheDirective::MaxAge(FEED_MAX_AGE),
+        ]))
+        .body(body)
+}
+
+// Conditional GET. If-None-Match takes precedence over If-Modified-Since when a reader
+// sends both (RFC 9110, section 13.2.2).
+fn is_not_modified(req: &HttpRequest, etag: &EntityTag, last_modified: HttpDate) -> bool {
+    if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+        return match if_none_match {
+            IfNoneMatch::Any => true,
+            IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(etag)),
+        };
+    }
+    req.get_header::<IfModifiedSince>()
+        .is_some_and(|IfModifiedSince(since)| last_modified <= since)
+}
+
+fn not_found(message: &str) -> HttpResponse {
+    HttpResponse::NotFound().json(SuccessResponse::<()> {
+        success: false,
+        message: message.to_string(),
+        data: None,
+    })
+}
+
+fn feed_error(e: sqlx::Error) -> HttpResponse {
+    tracing::error!(error = %e, "Failed to read a shared brain");
+    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+        success: false,
+        message: e.to_string(),
+        data: None,
+    })
+}
@@ -1,290 +1,1038 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr};
 
 use actix_web::{
-    web::{Data, Json, Path},
+    web::{Data, Json, Path, Query},
     HttpRequest, HttpResponse, Responder,
 };
-use serde::{Deserialize, Serialize};
-use sqlx::{prelude::FromRow, MySqlPool};
-use strum_macros::{Display, EnumString};
+use brainly_types::content::{
+    BulkAction, BulkContent, BulkItemResult, BulkResult, Content as NewContent, ContentResponse,
+    ContentRow, ContentType, CreateOptions, ListOptions, OnDuplicate, UserContents, Visibility,
+};
+use chrono::{DateTime, Utc};
+use sqlx::{MySqlConnection, MySqlPool, QueryBuilder};
+use tracing::{info_span, Instrument};
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
+use validator::Validate;
 
+use crate::canonical_url::url_hash;
+use crate::metrics::Metrics;
+use crate::openapi::{ContentRow as ContentRowSchema, NoData};
 use crate::routes::utils::generate_random_string;
 
-use super::{jwt::validate_token, SuccessResponse};
+use super::validation::{validation_error, validation_summary};
+use super::{api_key::Scope, jwt::authenticate, SuccessResponse};
+
+// Saving content, shared by the handlers below, imports and the feed poller. The request
+// and response types live in brainly-types so the client SDK can share them.
+pub struct Content;
+
+// Tags an item can carry, as enforced when it is created
+const MAX_TAGS: usize = 20;
+
+// Error of saving a page the user saved already
+const DUPLICATE_CONTENT: &str = "Content with this URL is already saved";
+
+// Per-item error of bulk actions on an item the user does not have, or has trashed
+const ITEM_NOT_FOUND: &str = "Content not found";
+
+// Listed content row: (id, title, type_, link, url, is_read, collection, visibility)
+type ListRow = (
+    i32,
+    String,
+    String,
+    String,
+    Option<String>,
+    bool,
+    Option<String>,
+    String,
+);
 
-// Define the possible content types using an enum
-#[derive(Serialize, Deserialize, Debug, Display, EnumString)]
-#[strum(serialize_all = "PascalCase")] // PascalCase serialization for compatibility with external systems
-enum ContentType {
-    Image,
-    Video,
-    Article,
-    Audio,
+// Key two tags share when the tag column's accent- and case-insensitive collation treats them as equal
+fn tag_key(tag: &str) -> String {
+    tag.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect::<String>()
+        .to_lowercase()
 }
 
-// Struct representing the content creation request payload
-#[derive(Serialize, Deserialize)]
-pub struct Content {
-    type_: ContentType, // Type of content (e.g., Image, Video)
-    title: String,      // Title of the content
+// Drop duplicate tags ignoring case and accents, keeping the first spelling
+pub(crate) fn unique_tags(tags: &[String]) -> Vec<String> {
+    let mut unique: Vec<String> = Vec::new();
+    for tag in tags {
+        if !unique.iter().any(|seen| tag_key(seen) == tag_key(tag)) {
+            unique.push(tag.clone());
+        }
+    }
+    unique
 }
 
-// Struct representing the response for a single content item
-#[derive(Serialize)]
-pub struct ContentResponse {
-    type_: ContentType, // Type of content
-    title: String,      // Title of the content
-    link: String,       // Generated unique link for the content
+// LIKE pattern matching `text` anywhere, with the wildcards in it matched literally
+fn like_pattern(text: &str) -> String {
+    let escaped = text
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_");
+    format!("%{}%", escaped)
 }
 
-// Struct representing content data stored in the database
-#[derive(Serialize, FromRow)]
-pub struct UserContents {
-    id: i32,            // Unique ID of the content
-    title: String,      // Title of the content
-    type_: ContentType, // Type of content
-    link: String,       // Link to access the content
+// Tags of every item a user saved, keyed by content ID
+async fn tags_by_content(
+    db: &MySqlPool,
+    user_id: i32,
+) -> Result<HashMap<i32, Vec<String>>, sqlx::Error> {
+    let rows: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT ct.content_id, ct.tag FROM content_tags ct
+         JOIN contents c ON c.id = ct.content_id
+         WHERE c.user_id = ? AND c.deleted_at IS NULL ORDER BY ct.tag",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .instrument(info_span!("db_query", query = "select_user_content_tags"))
+    .await?;
+
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for (content_id, tag) in rows {
+        tags.entry(content_id).or_default().push(tag);
+    }
+    Ok(tags)
 }
 
-// Implement helper functions for ContentType
-impl ContentType {
-    // Convert enum variant to its string representation
-    pub fn enum_to_string(&self) -> String {
-        match self {
-            ContentType::Image => "Image".to_owned(),
-            ContentType::Video => "Video".to_owned(),
-            ContentType::Article => "Article".to_owned(),
-            ContentType::Audio => "Audio".to_owned(),
+impl Content {
+    // Answer a create request for a page the user saved already: 409 with the saved item, or
+    // with `OnDuplicate::Merge` the saved item with the new tags added. None when the page is new.
+    async fn save_duplicate(
+        db: &MySqlPool,
+        user_id: i32,
+        url: &str,
+        tags: &[String],
+        on_duplicate: OnDuplicate,
+    ) -> Result<Option<HttpResponse>, sqlx::Error> {
+        let mut tx = db.begin().await?;
+        let Some(mut existing) = find_duplicate(&mut tx, user_id, url).await? else {
+            return Ok(None);
+        };
+
+        if on_duplicate == OnDuplicate::Reject {
+            return Ok(Some(HttpResponse::Conflict().json(SuccessResponse {
+                success: false,
+                message: DUPLICATE_CONTENT.to_string(),
+                data: Some(existing),
+            })));
+        }
+
+        if let Some(error) = tag_item(&mut tx, user_id, existing.id, tags).await? {
+            return Ok(Some(HttpResponse::UnprocessableEntity().json(
+                SuccessResponse::<()> {
+                    success: false,
+                    message: error,
+                    data: None,
+                },
+            )));
+        }
+        tx.commit().await?;
+
+        existing.tags = unique_tags(&[existing.tags, tags.to_vec()].concat());
+        Ok(Some(HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: "Merged into existing content".to_string(),
+            data: Some(existing),
+        })))
+    }
+
+    // Hash the URLs of items saved before duplicate detection, in batches, returning how many
+    // were hashed. Items repeating a page saved earlier are left without a hash.
+    pub async fn backfill_url_hashes(db: &MySqlPool) -> Result<u64, sqlx::Error> {
+        let mut hashed = 0;
+        let mut last_id = 0;
+        loop {
+            let rows: Vec<(i32, String)> = sqlx::query_as(
+                "SELECT id, url FROM contents WHERE id > ? AND url IS NOT NULL AND url_hash IS NULL
+                 ORDER BY id LIMIT 500",
+            )
+            .bind(last_id)
+            .fetch_all(db)
+            .instrument(info_span!("db_query", query = "select_unhashed_urls"))
+            .await?;
+            let Some(&(last, _)) = rows.last() else {
+                return Ok(hashed);
+            };
+            last_id = last;
+
+            for (id, url) in rows {
+                let result = sqlx::query("UPDATE contents SET url_hash = ? WHERE id = ?")
+                    .bind(url_hash(&url))
+                    .bind(id)
+                    .execute(db)
+                    .instrument(info_span!("db_query", query = "update_url_hash"))
+                    .await;
+                match result {
+                    Ok(_) => hashed += 1,
+                    Err(e)
+                        if e.as_database_error()
+                            .is_some_and(|e| e.is_unique_violation()) => {}
+                    Err(e) => return Err(e),
+                }
+            }
         }
     }
 
-    // Convert a string to an enum variant, if valid
-    pub fn enum_from_string(value: &str) -> Option<ContentType> {
-        match value {
-            "Image" => Some(ContentType::Image),
-            "Video" => Some(ContentType::Video),
-            "Article" => Some(ContentType::Article),
-            "Audio" => Some(ContentType::Audio),
-            _ => None,
+    // Insert a content row and its tags in one transaction, so a failed tag insert
+    // does not leave an untagged item behind. `created_at` defaults to now, imports pass
+    // the original save time. Returns the new content ID.
+    pub(crate) async fn insert_content(
+        db: &MySqlPool,
+        user_id: i32,
+        link: &str,
+        content: &NewContent,
+        tags: &[String],
+        created_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, sqlx::Error> {
+        let mut tx = db.begin().await?;
+        let content_id =
+            Self::insert_content_in(&mut tx, user_id, link, content, tags, created_at).await?;
+        tx.commit().await?;
+        Ok(content_id)
+    }
+
+    // Insert a content row and its tags as part of a caller's transaction
+    async fn insert_content_in(
+        conn: &mut MySqlConnection,
+        user_id: i32,
+        link: &str,
+        content: &NewContent,
+        tags: &[String],
+        created_at: Option<DateTime<Utc>>,
+    ) -> Result<i32, sqlx::Error> {
+        let content_id = sqlx::query(
+            "INSERT INTO contents (link, type_, title, url, url_hash, is_read, collection, visibility,
+                 user_id, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, COALESCE(?, CURRENT_TIMESTAMP))",
+        )
+        .bind(link)
+        .bind(content.type_.to_string())
+        .bind(&content.title)
+        .bind(&content.url)
+        .bind(content.url.as_deref().map(url_hash)) // Rejects a page the user saved already
+        .bind(content.read)
+        .bind(&content.collection)
+        .bind(content.visibility.to_string())
+        .bind(user_id)
+        .bind(created_at)
+        .execute(&mut *conn)
+        .instrument(info_span!("db_query", query = "insert_content"))
+        .await?
+        .last_insert_id() as i32;
+
+        if !tags.is_empty() {
+            QueryBuilder::new("INSERT INTO content_tags (content_id, tag) ")
+                .push_values(tags, |mut row, tag| {
+                    row.push_bind(content_id).push_bind(tag);
+                })
+                .build()
+                .execute(&mut *conn)
+                .instrument(info_span!("db_query", query = "insert_content_tags"))
+                .await?;
         }
+
+        Ok(content_id)
     }
 }
 
-// Implement CRUD operations for the Content struct
-impl Content {
-    // Create new content and save it to the database
-    pub async fn create_content(
-        db: Data<MySqlPool>,    // Database connection pool
-        req: HttpRequest,       // Incoming HTTP request
-        content: Json<Content>, // JSON payload for the content
-    ) -> impl Responder {
-        // Validate the JWT token and extract user ID
-        let token_data = validate_token(req).await;
-        match token_data {
-            Ok(user_id) => {
-                let random_link = generate_random_string(16); // Generate a unique random link
-
-                // Insert the new content into the database
-                let result = sqlx::query(
-                    "INSERT INTO contents (link, type_, title, user_id) VALUES (?, ?, ?, ?)",
-                )
-                .bind(random_link.clone())
-                .bind(content.type_.to_string())
-                .bind(content.title.clone())
-                .bind(user_id)
-                .execute(&**db)
-                .await;
+// Create new content and save it to the database
+#[utoipa::path(post, path = "/api/v1/content", tag = "content",
+    request_body = NewContent,
+    params(("on_duplicate" = Option<OnDuplicate>, Query, description = "What to do when the URL is already saved, after normalization (case, tracking parameters, trailing slashes, site aliases). Defaults to reject")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "The URL was already saved, its item with the new tags added (on_duplicate=merge)", body = SuccessResponse<ContentResponse>),
+        (status = 201, description = "Content created", body = SuccessResponse<ContentResponse>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token, or API key lacks the scope", body = SuccessResponse<NoData>),
+        (status = 409, description = "The URL is already saved, data holds the saved item (on_duplicate=reject)", body = SuccessResponse<ContentResponse>),
+        (status = 422, description = "Invalid fields", body = SuccessResponse<HashMap<String, Vec<String>>>),
+    ))]
+pub async fn create_content(
+    db: Data<MySqlPool>,           // Database connection pool
+    metrics: Data<Metrics>,        // Metrics registry
+    req: HttpRequest,              // Incoming HTTP request
+    options: Query<CreateOptions>, // What to do when the page is saved already
+    content: Json<NewContent>,     // JSON payload for the content
+) -> impl Responder {
+    // Validate the JWT token and extract user ID
+    let token_data = authenticate(req, Scope::ContentWrite).await;
+    match token_data {
+        Ok(user_id) => {
+            // Reject malformed input before touching the database
+            if let Err(errors) = content.validate() {
+                return validation_error(errors);
+            }
 
-                // Handle database insertion result
-                match result {
-                    Ok(_) => {
-                        let type_to_string = ContentType::enum_to_string(&content.type_);
-                        HttpResponse::Created().json(SuccessResponse {
-                            success: true,
-                            message: "Content created successfully".to_string(),
-                            data: Some(ContentResponse {
-                                link: random_link.clone(),
-                                type_: ContentType::enum_from_string(&type_to_string)
-                                    .expect("Type Not Found"),
-                                title: content.title.clone(),
-                            }),
-                        })
-                    }
+            let random_link = generate_random_string(16); // Generate a unique random link
+            let tags = unique_tags(&content.tags);
+
+            // The same page saved again is rejected, or merged into the saved copy
+            if let Some(url) = &content.url {
+                match Content::save_duplicate(&db, user_id, url, &tags, options.on_duplicate).await
+                {
+                    Ok(Some(response)) => return response,
+                    Ok(None) => {}
                     Err(e) => {
-                        HttpResponse::InternalServerError().body(format!("Database error: {}", e))
+                        tracing::error!(error = %e, "Failed to look up duplicate content");
+                        return HttpResponse::InternalServerError()
+                            .body(format!("Database error: {}", e));
                     }
                 }
             }
-            Err(err) => err, // Return token validation error response
-        }
-    }
 
-    // Fetch content by its ID
-    pub async fn get_content_by_id(
-        db: Data<MySqlPool>, // Database connection pool
-        req: HttpRequest,    // Incoming HTTP request
-        params: Path<i32>,   // Extracted content ID from the URL path
-    ) -> impl Responder {
-        let verify_token = validate_token(req).await; // Validate token
-        match verify_token {
-            Ok(user_id) => {
-                // Query the database for content with the given ID and user ID
-                let content: Result<(i32, String, String, String), sqlx::Error> = sqlx::query_as(
-                    "SELECT id, title, type_, link FROM contents WHERE id = ? AND user_id = ?",
-                )
-                .bind(params.into_inner())
-                .bind(user_id)
-                .fetch_one(&**db)
-                .await;
+            // Insert the new content and its tags into the database
+            let result =
+                Content::insert_content(&db, user_id, &random_link, &content, &tags, None).await;
 
-                // Handle query result
-                match content {
-                    Ok(content) => HttpResponse::Ok().json(SuccessResponse {
+            // Handle database insertion result
+            match result {
+                Ok(id) => {
+                    let type_to_string = ContentType::enum_to_string(&content.type_);
+                    metrics
+                        .content_created
+                        .with_label_values(&[&type_to_string])
+                        .inc();
+                    HttpResponse::Created().json(SuccessResponse {
                         success: true,
-                        message: "Content fetched successfully".to_string(),
-                        data: Some(content),
-                    }),
-                    Err(_) => HttpResponse::NotFound().json(SuccessResponse::<()> {
-                        success: false,
-                        message: "Not Found".to_string(),
-                        data: None,
-                    }),
+                        message: "Content created successfully".to_string(),
+                        data: Some(ContentResponse {
+                            id,
+                            link: random_link.clone(),
+                            type_: ContentType::enum_from_string(&type_to_string)
+                                .expect("Type Not Found"),
+                            title: content.title.clone(),
+                            url: content.url.clone(),
+                            tags,
+                            read: content.read,
+                        }),
+                    })
+                }
+                // The same page was saved by a concurrent request, answer as if it had been
+                // saved before this one
+                Err(e)
+                    if e.as_database_error()
+                        .is_some_and(|e| e.is_unique_violation()) =>
+                {
+                    let duplicate = match &content.url {
+                        Some(url) => {
+                            Content::save_duplicate(&db, user_id, url, &tags, options.on_duplicate)
+                                .await
+                        }
+                        None => Ok(None),
+                    };
+                    match duplicate {
+                        Ok(Some(response)) => response,
+                        Ok(None) => HttpResponse::Conflict().json(SuccessResponse::<()> {
+                            success: false,
+                            message: DUPLICATE_CONTENT.to_string(),
+                            data: None,
+                        }),
+                        Err(e) => {
+                            tracing::error!(error = %e, "Failed to look up duplicate content");
+                            HttpResponse::InternalServerError()
+                                .body(format!("Database error: {}", e))
+                        }
+                    }
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to create content");
+                    HttpResponse::InternalServerError().body(format!("Database error: {}", e))
                 }
             }
-            Err(e) => e, // Return token validation error response
         }
+        Err(err) => err, // Return token validation error response
     }
+}
+
+// Fetch content by its ID
+#[utoipa::path(get, path = "/api/v1/content/{id}", tag = "content",
+    params(("id" = i32, Path, description = "Content ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "The content item", body = SuccessResponse<ContentRowSchema>),
+        (status = 404, description = "Not found", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_content_by_id(
+    db: Data<MySqlPool>, // Database connection pool
+    req: HttpRequest,    // Incoming HTTP request
+    params: Path<i32>,   // Extracted content ID from the URL path
+) -> impl Responder {
+    let verify_token = authenticate(req, Scope::ContentRead).await; // Validate token
+    match verify_token {
+        Ok(user_id) => {
+            // Query the database for content with the given ID and user ID
+            let content: Result<ContentRow, sqlx::Error> = sqlx::query_as(
+                "SELECT id, title, type_, link, url FROM contents WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+            )
+            .bind(params.into_inner())
+            .bind(user_id)
+            .fetch_one(&**db)
+            .instrument(info_span!("db_query", query = "select_content_by_id"))
+            .await;
 
-    // Fetch all content for a user
-    pub async fn get_all_content(db: Data<MySqlPool>, req: HttpRequest) -> impl Responder {
-        let token_data = validate_token(req).await; // Validate token
-
-        match token_data {
-            Ok(user_id) => {
-                // Query the database for all content belonging to the user
-                let content: Result<Vec<(i32, String, String, String)>, _> =
-                    sqlx::query_as("SELECT id, title, type_, link FROM contents WHERE user_id = ?")
-                        .bind(user_id)
-                        .fetch_all(&**db)
-                        .await;
-
-                // Handle query result
-                match content {
-                    Ok(rows) => {
-                        let contents: Vec<UserContents> = rows
-                            .into_iter()
-                            .filter_map(|(id, title, type_, link)| {
+            // Handle query result
+            match content {
+                Ok(content) => HttpResponse::Ok().json(SuccessResponse {
+                    success: true,
+                    message: "Content fetched successfully".to_string(),
+                    data: Some(content),
+                }),
+                Err(_) => HttpResponse::NotFound().json(SuccessResponse::<()> {
+                    success: false,
+                    message: "Not Found".to_string(),
+                    data: None,
+                }),
+            }
+        }
+        Err(e) => e, // Return token validation error response
+    }
+}
+
+// Fetch all content for a user, optionally only the items matching a search
+#[utoipa::path(get, path = "/api/v1/user/content", tag = "content",
+    params(("q" = Option<String>, Query, description = "Only items whose title, URL or a tag contains this text, ignoring case")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "The user's content", body = SuccessResponse<Vec<UserContents>>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_all_content(
+    db: Data<MySqlPool>,
+    req: HttpRequest,
+    options: Query<ListOptions>,
+) -> impl Responder {
+    let token_data = authenticate(req, Scope::ContentRead).await; // Validate token
+
+    match token_data {
+        Ok(user_id) => {
+            // Query the database for all content belonging to the user
+            let mut query = QueryBuilder::new(
+                "SELECT id, title, type_, link, url, is_read, collection, visibility
+                 FROM contents c WHERE user_id = ",
+            );
+            query.push_bind(user_id).push(" AND deleted_at IS NULL");
+            if let Some(search) = options.q.as_deref().filter(|q| !q.trim().is_empty()) {
+                let pattern = like_pattern(search.trim());
+                query
+                    .push(" AND (title LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR url LIKE ")
+                    .push_bind(pattern.clone())
+                    .push(" OR EXISTS (SELECT 1 FROM content_tags ct WHERE ct.content_id = c.id AND ct.tag LIKE ")
+                    .push_bind(pattern)
+                    .push("))");
+            }
+            let content: Result<Vec<ListRow>, _> = query
+                .build_query_as()
+                .fetch_all(&**db)
+                .instrument(info_span!("db_query", query = "select_user_contents"))
+                .await;
+
+            // Look up the tags of every item in one more query
+            let content = match content {
+                Ok(rows) => tags_by_content(&db, user_id).await.map(|tags| (rows, tags)),
+                Err(err) => Err(err),
+            };
+
+            // Handle query result
+            match content {
+                Ok((rows, mut tags)) => {
+                    let contents: Vec<UserContents> = rows
+                        .into_iter()
+                        .filter_map(
+                            |(id, title, type_, link, url, read, collection, visibility)| {
                                 if let Ok(type_enum) = ContentType::from_str(&type_) {
                                     Some(UserContents {
                                         id,
                                         title,
                                         type_: type_enum,
                                         link,
+                                        url,
+                                        tags: tags.remove(&id).unwrap_or_default(),
+                                        read,
+                                        collection,
+                                        visibility: visibility.parse().unwrap_or_default(),
                                     })
                                 } else {
                                     None // Skip invalid content type
                                 }
-                            })
-                            .collect();
+                            },
+                        )
+                        .collect();
 
-                        HttpResponse::Ok().json(SuccessResponse {
-                            success: true,
-                            message: "Content fetched successfully".to_string(),
-                            data: Some(contents),
-                        })
-                    }
-                    Err(err) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                    HttpResponse::Ok().json(SuccessResponse {
+                        success: true,
+                        message: "Content fetched successfully".to_string(),
+                        data: Some(contents),
+                    })
+                }
+                Err(err) => {
+                    tracing::error!(error = %err, "Failed to fetch user content");
+                    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
                         success: false,
                         message: err.to_string(),
                         data: None,
-                    }),
+                    })
                 }
             }
-            Err(err) => err, // Return token validation error response
         }
+        Err(err) => err, // Return token validation error response
     }
+}
 
-    // Delete content by its ID
-    pub async fn delete_content(
-        db: Data<MySqlPool>, // Database connection pool
-        params: Path<i32>,   // Extracted content ID from the URL path
-        req: HttpRequest,    // Incoming HTTP request
-    ) -> impl Responder {
-        let token_data = validate_token(req).await; // Validate token
-
-        match token_data {
-            Ok(user_id) => {
-                // Run DELETE query to remove content by ID and user ID
-                let response = sqlx::query("DELETE FROM contents WHERE id = ? AND user_id = ?")
-                    .bind(params.into_inner()) // Content ID
-                    .bind(user_id) // User ID
-                    .execute(&**db)
-                    .await;
+// Delete content by its ID, moving it to the trash
+#[utoipa::path(delete, path = "/api/v1/content/{id}", tag = "content",
+    params(("id" = i32, Path, description = "Content ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Content moved to the trash, from which it can be restored until purged", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 404, description = "Not found or not owned by the user", body = SuccessResponse<NoData>),
+    ))]
+pub async fn delete_content(
+    db: Data<MySqlPool>, // Database connection pool
+    params: Path<i32>,   // Extracted content ID from the URL path
+    req: HttpRequest,    // Incoming HTTP request
+) -> impl Responder {
+    let token_data = authenticate(req, Scope::ContentWrite).await; // Validate token
 
-                match response {
-                    Ok(result) => {
-                        if result.rows_affected() > 0 {
-                            HttpResponse::Ok().json(SuccessResponse::<()> {
-                                success: true,
-                                message: "Content deleted".to_string(),
-                                data: None,
-                            })
-                        } else {
-                            HttpResponse::NotFound().json(SuccessResponse::<()> {
-                                success: false,
-                                message: "Content not found or not owned by user".to_string(),
-                                data: None,
-                            })
-                        }
+    match token_data {
+        Ok(user_id) => {
+            // Move the content to the trash, see `Trash` for restoring and purging it
+            let response = sqlx::query(
+                "UPDATE contents SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+            )
+            .bind(params.into_inner()) // Content ID
+            .bind(user_id) // User ID
+            .execute(&**db)
+            .instrument(info_span!("db_query", query = "trash_content"))
+            .await;
+
+            match response {
+                Ok(result) => {
+                    if result.rows_affected() > 0 {
+                        HttpResponse::Ok().json(SuccessResponse::<()> {
+                            success: true,
+                            message: "Content moved to trash".to_string(),
+                            data: None,
+                        })
+                    } else {
+                        HttpResponse::NotFound().json(SuccessResponse::<()> {
+                            success: false,
+                            message: "Content not found or not owned by user".to_string(),
+                            data: None,
+                        })
                     }
-                    Err(e) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                }
+                Err(e) => {
+                    tracing::error!(error = %e, "Failed to delete content");
+                    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
                         success: false,
                         message: e.to_string(),
                         data: None,
-                    }),
+                    })
                 }
             }
-            Err(e) => e, // Return token validation error response
         }
+        Err(e) => e, // Return token validation error response
     }
+}
 
-    pub async fn get_content_by_link(
-        db: Data<MySqlPool>,  // Shared database connection pool
-        params: Path<String>, // Path parameter, representing the unique content link
-        req: HttpRequest,     // HTTP request object, used for token validation
-    ) -> impl Responder {
-        // Returns a response that implements the Responder trait
-        // Step 1: Validate the JWT token from the request
-        match validate_token(req).await {
-            Ok(_) => {
-                // Step 2: Query the database to fetch content using the provided link
-                let content: Result<(i32, String, String, String), sqlx::Error> =
-                    sqlx::query_as("SELECT id, title, type_, link FROM contents WHERE link = ?")
-                        .bind(params.into_inner()) // Bind the link from the path parameter
-                        .fetch_one(&**db) // Execute the query on the database
-                        .await;
-
-                // Step 3: Handle the query result
-                match content {
-                    Ok(content) => {
-                        // If the content is found, return a successful response with content data
-                        HttpResponse::Ok().json(SuccessResponse {
-                            success: true,
-                            message: "Content Fetch Success".to_string(),
-                            data: Some(content),
-                        })
-                    }
-                    Err(_) => {
-                        // If the content is not found, return a 404 Not Found response
-                        HttpResponse::NotFound().json(SuccessResponse::<()> {
-                            success: false,
-                            message: "Content Not Found".to_string(),
-                            data: None,
-                        })
-                    }
+#[utoipa::path(get, path = "/api/v1/content/link/{link}", tag = "content",
+    params(("link" = String, Path, description = "Generated content link")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "The content item", body = SuccessResponse<ContentRowSchema>),
+        (status = 404, description = "Not found", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_content_by_link(
+    db: Data<MySqlPool>,  // Shared database connection pool
+    params: Path<String>, // Path parameter, representing the unique content link
+    req: HttpRequest,     // HTTP request object, used for token validation
+) -> impl Responder {
+    // Returns a response that implements the Responder trait
+    // Step 1: Validate the JWT token from the request
+    match authenticate(req, Scope::ContentRead).await {
+        Ok(_) => {
+            // Step 2: Query the database to fetch content using the provided link
+            let content: Result<ContentRow, sqlx::Error> =
+                sqlx::query_as("SELECT id, title, type_, link, url FROM contents WHERE link = ? AND deleted_at IS NULL")
+                    .bind(params.into_inner()) // Bind the link from the path parameter
+                    .fetch_one(&**db) // Execute the query on the database
+                    .instrument(info_span!("db_query", query = "select_content_by_link"))
+                    .await;
+
+            // Step 3: Handle the query result
+            match content {
+                Ok(content) => {
+                    // If the content is found, return a successful response with content data
+                    HttpResponse::Ok().json(SuccessResponse {
+                        success: true,
+                        message: "Content Fetch Success".to_string(),
+                        data: Some(content),
+                    })
+                }
+                Err(_) => {
+                    // If the content is not found, return a 404 Not Found response
+                    HttpResponse::NotFound().json(SuccessResponse::<()> {
+                        success: false,
+                        message: "Content Not Found".to_string(),
+                        data: None,
+                    })
                 }
             }
-            Err(e) => e, // If token validation fails, return the validation error response
         }
+        Err(e) => e, // If token validation fails, return the validation error response
+    }
+}
+
+// Apply one action to many items in a single transaction, reporting the outcome per item.
+// Items that fail are skipped, unless `all_or_nothing` asks for everything to be rolled back.
+#[utoipa::path(post, path = "/api/v1/content/bulk", tag = "content",
+    request_body = BulkContent,
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Action applied in one transaction, skipping the items that failed", body = SuccessResponse<BulkResult>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token, or API key lacks the scope", body = SuccessResponse<NoData>),
+        (status = 422, description = "Invalid fields, nothing to apply, tags or URLs colliding with saved content, or with all_or_nothing an item failed and every change was rolled back", body = SuccessResponse<BulkResult>),
+    ))]
+pub async fn bulk_content(
+    db: Data<MySqlPool>,     // Database connection pool
+    metrics: Data<Metrics>,  // Metrics registry
+    req: HttpRequest,        // Incoming HTTP request
+    body: Json<BulkContent>, // Action and the items it applies to
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+    if let Err(errors) = body.validate() {
+        return validation_error(errors);
+    }
+    if let Some(message) = bulk_request_error(&body) {
+        return HttpResponse::UnprocessableEntity().json(SuccessResponse::<()> {
+            success: false,
+            message: message.to_string(),
+            data: None,
+        });
+    }
+
+    // Database errors abort the whole request, only per-item failures are reported
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(e) => return bulk_error(e),
+    };
+    let results = match body.action {
+        BulkAction::Create => create_items(&mut tx, user_id, &body.items).await,
+        _ => update_items(&mut tx, user_id, &body).await,
+    };
+    let results = match results {
+        Ok(results) => results,
+        Err(e) => return bulk_error(e),
+    };
+
+    let failed = results.iter().filter(|result| !result.success).count() as u32;
+    let succeeded = results.len() as u32 - failed;
+    let applied = failed == 0 || !body.all_or_nothing;
+    let finished = if applied {
+        tx.commit().await
+    } else {
+        tx.rollback().await
+    };
+    if let Err(e) = finished {
+        return bulk_error(e);
+    }
+
+    if applied && body.action == BulkAction::Create {
+        for (item, result) in body.items.iter().zip(&results) {
+            if result.success {
+                metrics
+                    .content_created
+                    .with_label_values(&[&item.type_.enum_to_string()])
+                    .inc();
+            }
+        }
+    }
+
+    let data = Some(BulkResult {
+        applied,
+        succeeded,
+        failed,
+        results,
+    });
+    if applied {
+        HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: format!("{} succeeded, {} failed", succeeded, failed),
+            data,
+        })
+    } else {
+        HttpResponse::UnprocessableEntity().json(SuccessResponse {
+            success: false,
+            message: format!("{} failed, no changes were made", failed),
+            data,
+        })
+    }
+}
+
+// Why a bulk request has nothing to apply, beyond its field rules
+fn bulk_request_error(body: &BulkContent) -> Option<&'static str> {
+    match body.action {
+        BulkAction::Create if body.items.is_empty() => Some("No items to create"),
+        BulkAction::Create => None,
+        _ if body.ids.is_empty() => Some("No content IDs given"),
+        BulkAction::Tag | BulkAction::Untag if body.tags.is_empty() => Some("No tags given"),
+        BulkAction::SetVisibility if body.visibility.is_none() => Some("No visibility given"),
+        _ => None,
+    }
+}
+
+// Save each valid item, reporting invalid ones
+async fn create_items(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    items: &[NewContent],
+) -> Result<Vec<BulkItemResult>, sqlx::Error> {
+    let mut results = Vec::with_capacity(items.len());
+    for (index, item) in items.iter().enumerate() {
+        if let Err(errors) = item.validate() {
+            results.push(BulkItemResult {
+                index,
+                id: None,
+                success: false,
+                link: None,
+                error: Some(validation_summary(&errors)),
+            });
+            continue;
+        }
+        // Pages saved earlier, or earlier in this request, are not saved again
+        if let Some(url) = &item.url {
+            if let Some(existing) = find_duplicate(conn, user_id, url).await? {
+                results.push(BulkItemResult {
+                    index,
+                    id: Some(existing.id),
+                    success: false,
+                    link: Some(existing.link),
+                    error: Some(DUPLICATE_CONTENT.to_string()),
+                });
+                continue;
+            }
+        }
+
+        let link = generate_random_string(16);
+        let id =
+            Content::insert_content_in(conn, user_id, &link, item, &unique_tags(&item.tags), None)
+                .await?;
+        results.push(BulkItemResult {
+            index,
+            id: Some(id),
+            success: true,
+            link: Some(link),
+            error: None,
+        });
+    }
+    Ok(results)
+}
+
+// Trash, tag, untag, move or change the visibility of each item of `ids`, reporting the
+// ones that are missing or cannot take the tags
+async fn update_items(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    body: &BulkContent,
+) -> Result<Vec<BulkItemResult>, sqlx::Error> {
+    let tags = unique_tags(&body.tags);
+    let mut results = Vec::with_capacity(body.ids.len());
+    for (index, &id) in body.ids.iter().enumerate() {
+        let error = match body.action {
+            BulkAction::Delete => trash_item(conn, user_id, id).await?,
+            BulkAction::Tag => tag_item(conn, user_id, id, &tags).await?,
+            BulkAction::Untag => untag_item(conn, user_id, id, &tags).await?,
+            BulkAction::Move => move_item(conn, user_id, id, body.collection.as_deref()).await?,
+            BulkAction::SetVisibility => {
+                let visibility = body.visibility.unwrap_or_default();
+                set_visibility(conn, user_id, id, visibility).await?
+            }
+            BulkAction::Create => unreachable!("Items are created by create_items"),
+        };
+        results.push(BulkItemResult {
+            index,
+            id: Some(id),
+            success: error.is_none(),
+            link: None,
+            error,
+        });
+    }
+    Ok(results)
+}
+
+// The live item the user saved `url` as, matched by canonical URL, locking it for the transaction
+async fn find_duplicate(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    url: &str,
+) -> Result<Option<ContentResponse>, sqlx::Error> {
+    let row: Option<ListRow> = sqlx::query_as(
+        "SELECT id, title, type_, link, url, is_read, collection, visibility FROM contents
+         WHERE user_id = ? AND live_url_hash = ? FOR UPDATE",
+    )
+    .bind(user_id)
+    .bind(url_hash(url))
+    .fetch_optional(&mut *conn)
+    .instrument(info_span!("db_query", query = "select_duplicate_content"))
+    .await?;
+    let Some((id, title, type_, link, url, read, ..)) = row else {
+        return Ok(None);
+    };
+
+    let tags: Vec<String> =
+        sqlx::query_scalar("SELECT tag FROM content_tags WHERE content_id = ? ORDER BY tag")
+            .bind(id)
+            .fetch_all(&mut *conn)
+            .instrument(info_span!("db_query", query = "select_content_tags"))
+            .await?;
+    Ok(ContentType::from_str(&type_)
+        .ok()
+        .map(|type_| ContentResponse {
+            id,
+            type_,
+            title,
+            link,
+            url,
+            tags,
+            read,
+        }))
+}
+
+// Whether the user has a live (not trashed) item with this ID, locking it for the transaction
+async fn owns_item(conn: &mut MySqlConnection, user_id: i32, id: i32) -> Result<bool, sqlx::Error> {
+    let found: Option<i32> = sqlx::query_scalar(
+        "SELECT id FROM contents WHERE id = ? AND user_id = ? AND deleted_at IS NULL FOR UPDATE",
+    )
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(&mut *conn)
+    .instrument(info_span!("db_query", query = "lock_user_content"))
+    .await?;
+    Ok(found.is_some())
+}
+
+// Move an item to the trash. Returns why it could not be, if so.
+async fn trash_item(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    id: i32,
+) -> Result<Option<String>, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE contents SET deleted_at = CURRENT_TIMESTAMP WHERE id = ? AND user_id = ? AND deleted_at IS NULL",
+    )
+    .bind(id)
+    .bind(user_id)
+    .execute(&mut *conn)
+    .instrument(info_span!("db_query", query = "trash_content"))
+    .await?;
+    Ok((result.rows_affected() == 0).then(|| ITEM_NOT_FOUND.to_string()))
+}
+
+// Add the tags an item does not have yet, matched case-insensitively. Returns why they
+// could not be added, if so.
+async fn tag_item(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    id: i32,
+    tags: &[String],
+) -> Result<Option<String>, sqlx::Error> {
+    if !owns_item(conn, user_id, id).await? {
+        return Ok(Some(ITEM_NOT_FOUND.to_string()));
+    }
+
+    let current: Vec<String> =
+        sqlx::query_scalar("SELECT tag FROM content_tags WHERE content_id = ?")
+            .bind(id)
+            .fetch_all(&mut *conn)
+            .instrument(info_span!("db_query", query = "select_content_tags"))
+            .await?;
+    let added: Vec<&String> = tags
+        .iter()
+        .filter(|tag| !current.iter().any(|seen| tag_key(seen) == tag_key(tag)))
+        .collect();
+    if current.len() + added.len() > MAX_TAGS {
+        return Ok(Some(format!("At most {} tags are allowed", MAX_TAGS)));
+    }
+
+    if !added.is_empty() {
+        QueryBuilder::new("INSERT INTO content_tags (content_id, tag) ")
+            .push_values(added, |mut row, tag| {
+                row.push_bind(id).push_bind(tag);
+            })
+            .build()
+            .execute(&mut *conn)
+            .instrument(info_span!("db_query", query = "insert_content_tags"))
+            .await?;
+    }
+    Ok(None)
+}
+
+// Remove tags from an item. Tags it does not have are ignored. Returns why they could not
+// be removed, if so.
+async fn untag_item(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    id: i32,
+    tags: &[String],
+) -> Result<Option<String>, sqlx::Error> {
+    if !owns_item(conn, user_id, id).await? {
+        return Ok(Some(ITEM_NOT_FOUND.to_string()));
+    }
+
+    // The column's collation compares tags case-insensitively
+    let mut query = QueryBuilder::new("DELETE FROM content_tags WHERE content_id = ");
+    query.push_bind(id).push(" AND tag IN (");
+    let mut separated = query.separated(", ");
+    for tag in tags {
+        separated.push_bind(tag);
+    }
+    separated.push_unseparated(")");
+    query
+        .build()
+        .execute(&mut *conn)
+        .instrument(info_span!("db_query", query = "delete_content_tags"))
+        .await?;
+    Ok(None)
+}
+
+// File an item under a collection, or under none. Returns why it could not be, if so.
+async fn move_item(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    id: i32,
+    collection: Option<&str>,
+) -> Result<Option<String>, sqlx::Error> {
+    if !owns_item(conn, user_id, id).await? {
+        return Ok(Some(ITEM_NOT_FOUND.to_string()));
+    }
+    sqlx::query("UPDATE contents SET collection = ? WHERE id = ?")
+        .bind(collection)
+        .bind(id)
+        .execute(&mut *conn)
+        .instrument(info_span!("db_query", query = "update_content_collection"))
+        .await?;
+    Ok(None)
+}
+
+// Show an item in the shared brain or hide it. Returns why it could not be, if so.
+async fn set_visibility(
+    conn: &mut MySqlConnection,
+    user_id: i32,
+    id: i32,
+    visibility: Visibility,
+) -> Result<Option<String>, sqlx::Error> {
+    if !owns_item(conn, user_id, id).await? {
+        return Ok(Some(ITEM_NOT_FOUND.to_string()));
+    }
+    sqlx::query("UPDATE contents SET visibility = ? WHERE id = ?")
+        .bind(visibility.to_string())
+        .bind(id)
+        .execute(&mut *conn)
+        .instrument(info_span!("db_query", query = "update_content_visibility"))
+        .await?;
+    Ok(None)
+}
+
+fn bulk_error(e: sqlx::Error) -> HttpResponse {
+    // The transaction is rolled back, so a collision leaves every item untouched
+    if e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+    {
+        return HttpResponse::UnprocessableEntity().json(SuccessResponse::<()> {
+            success: false,
+            message: "Tags or URLs collide with saved content, no changes were made".to_string(),
+            data: None,
+        });
+    }
+    tracing::error!(error = %e, "Bulk content request failed");
+    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+        success: false,
+        message: e.to_string(),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn like_patterns_match_wildcards_literally() {
+        assert_eq!(like_pattern("rust"), "%rust%");
+        assert_eq!(like_pattern("100%_done"), "%100\\%\\_done%");
+        assert_eq!(like_pattern("a\\b"), "%a\\\\b%");
+    }
+
+    #[test]
+    fn tags_are_unique_ignoring_case_and_accents() {
+        let tags: Vec<String> = ["cafe", "Café", "CAFÉ", "tea", "naïve", "naive"]
+            .iter()
+            .map(|tag| tag.to_string())
+            .collect();
+        assert_eq!(unique_tags(&tags), ["cafe", "tea", "naïve"]);
+    }
+
+    fn bulk(action: BulkAction) -> BulkContent {
+        BulkContent {
+            action,
+            ids: vec![1],
+            items: Vec::new(),
+            tags: Vec::new(),
+            collection: None,
+            visibility: None,
+            all_or_nothing: false,
+        }
+    }
+
+    #[test]
+    fn bulk_requests_need_something_to_apply() {
+        assert_eq!(
+            bulk_request_error(&bulk(BulkAction::Create)),
+            Some("No items to create")
+        );
+        let mut no_ids = bulk(BulkAction::Delete);
+        no_ids.ids.clear();
+        assert_eq!(bulk_request_error(&no_ids), Some("No content IDs given"));
+        assert_eq!(
+            bulk_request_error(&bulk(BulkAction::Tag)),
+            Some("No tags given")
+        );
+        assert_eq!(
+            bulk_request_error(&bulk(BulkAction::SetVisibility)),
+            Some("No visibility given")
+        );
+        // A move without a collection takes the items out of their collections
+        assert_eq!(bulk_request_error(&bulk(BulkAction::Move)), None);
+
+        let mut private = bulk(BulkAction::SetVisibility);
+        private.visibility = Some(Visibility::Private);
+        assert_eq!(bulk_request_error(&private), None);
+    }
+
+    #[test]
+    fn bulk_actions_read_from_json() {
+        let body: BulkContent = serde_json::from_str(
+            r#"{"action": "set_visibility", "ids": [1, 2], "visibility": "private"}"#,
+        )
+        .unwrap();
+        assert_eq!(body.action, BulkAction::SetVisibility);
+        assert_eq!(body.visibility, Some(Visibility::Private));
+
+        let body: BulkContent =
+            serde_json::from_str(r#"{"action": "move", "ids": [1], "collection": " padded"}"#)
+                .unwrap();
+        assert_eq!(body.action, BulkAction::Move);
+        assert!(body.validate().is_err());
     }
 }
@@ -0,0 +1,402 @@
+// Handlers for getting a user's data out of brainly
+
+use actix_web::{
+    http::header::{ContentDisposition, DispositionParam, DispositionType},
+    web::{self, Bytes, Data},
+    HttpRequest, HttpResponse, Responder,
+};
+use brainly_types::{
+    content::ContentType,
+    export::{Export, ExportedContent, ExportedUser, EXPORT_VERSION},
+};
+use chrono::{DateTime, Utc};
+use futures_util::stream;
+use serde_json::json;
+use sqlx::MySqlPool;
+use tokio::sync::mpsc;
+use tracing::{info_span, Instrument};
+
+use crate::config::PublicUrlConfig;
+use crate::openapi::NoData;
+use crate::routes::utils::public_base_url;
+use crate::vault::markdown_vault;
+
+use super::{api_key::Scope, jwt::authenticate, SuccessResponse};
+
+// Serialized items buffered ahead of a slow client before the database read pauses
+const EXPORT_BUFFER: usize = 64;
+
+// Items read per query. The connection goes back to the pool between batches, so a slow
+// download never holds one while it waits for the client.
+const EXPORT_BATCH: u32 = 500;
+
+// A piece of the response body, or the database error that cut the export short
+type Chunk = Result<Bytes, sqlx::Error>;
+
+// Content row joined with one of its tags:
+// (id, title, type_, link, url, is_read, collection, visibility, created_at, tag)
+type ExportRow = (
+    i32,
+    String,
+    String,
+    String,
+    Option<String>,
+    bool,
+    Option<String>,
+    String,
+    DateTime<Utc>,
+    Option<String>,
+);
+
+// Stream every item the user saved as one versioned JSON document (`brainly_types::export::Export`).
+// Items are read from the database in batches and written out one at a time, so memory use
+// does not grow with the size of the brain.
+#[utoipa::path(get, path = "/api/v1/user/export", tag = "content",
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "Every saved item with its tags, streamed as an attachment", body = Export),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+    ))]
+pub async fn export_json(db: Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentRead).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let user: Result<(String, DateTime<Utc>), sqlx::Error> =
+        sqlx::query_as("SELECT username, created_at FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(&**db)
+            .instrument(info_span!("db_query", query = "select_export_user"))
+            .await;
+    let collections: Result<Vec<String>, sqlx::Error> = sqlx::query_scalar(
+        "SELECT DISTINCT collection FROM contents
+         WHERE user_id = ? AND deleted_at IS NULL AND collection IS NOT NULL ORDER BY collection",
+    )
+    .bind(user_id)
+    .fetch_all(&**db)
+    .instrument(info_span!("db_query", query = "select_export_collections"))
+    .await;
+    let ((username, created_at), collections) = match (user, collections) {
+        (Ok(user), Ok(collections)) => (user, collections),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!(error = %e, "Failed to load user and collections for export");
+            return HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            });
+        }
+    };
+
+    // Everything before the first item, the array is closed once the rows run out
+    let exported_at = Utc::now();
+    let header = format!(
+        "{{\"version\":{},\"exported_at\":{},\"user\":{},\"collections\":{},\"contents\":[",
+        EXPORT_VERSION,
+        json!(exported_at),
+        json!(ExportedUser {
+            username,
+            created_at
+        }),
+        json!(collections),
+    );
+
+    // A bounded channel keeps the database read at the pace of the client
+    let (sender, receiver) = mpsc::channel::<Chunk>(EXPORT_BUFFER);
+    let db = db.into_inner();
+    tokio::spawn(
+        async move {
+            let _ = sender.send(Ok(Bytes::from(header))).await;
+            match stream_contents(&db, user_id, &sender).await {
+                Ok(()) => {
+                    let _ = sender.send(Ok(Bytes::from_static(b"]}"))).await;
+                }
+                Err(e) => {
+                    // The status is already sent, so end the body early and leave the JSON
+                    // unterminated, which clients detect as a failed download
+                    tracing::error!(error = %e, user_id, "Export failed mid-stream");
+                    let _ = sender.send(Err(e)).await;
+                }
+            }
+        }
+        .instrument(tracing::Span::current()),
+    );
+
+    let filename = format!("brainly-export-{}.json", exported_at.format("%Y-%m-%d"));
+    HttpResponse::Ok()
+        .content_type("application/json")
+        .insert_header(ContentDisposition {
+            disposition: DispositionType::Attachment,
+            parameters: vec![DispositionParam::Filename(filename)],
+        })
+        .streaming(stream::unfold(receiver, |mut receiver| async move {
+            receiver.recv().await.map(|chunk| (chunk, receiver))
+        }))
+}
+
+// Download every item as a zip of Markdown notes with YAML front matter, plus an index
+// per tag and per collection, ready to drop into an Obsidian or Logseq vault (see
+// `crate::vault`)
+#[utoipa::path(get, path = "/api/v1/user/export/markdown", tag = "content",
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "Zip of Markdown notes with YAML front matter and an index per tag and per collection, for Obsidian or Logseq", body = Vec<u8>, content_type = "application/zip"),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+    ))]
+pub async fn export_markdown(
+    db: Data<MySqlPool>,
+    public_url: Data<PublicUrlConfig>, // Base of the share links
+    req: HttpRequest,
+) -> impl Responder {
+    let base_url = public_base_url(&public_url, &req);
+    let user_id = match authenticate(req, Scope::ContentRead).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let (share_hash, items) = match load_vault(&db, user_id).await {
+        Ok(loaded) => loaded,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to load content for export");
+            return HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            });
+        }
+    };
+
+    // Share links go through the shared brain, so there are none while it is private
+    let brain_url =
+        share_hash.map(|share_hash| format!("{}/api/v1/brain/{}", base_url, share_hash));
+
+    // Compressing is CPU bound, so keep it off the async workers
+    let exported_at = Utc::now();
+    let archive =
+        web::block(move || markdown_vault(&items, brain_url.as_deref(), exported_at)).await;
+    match archive {
+        Ok(Ok(archive)) => {
+            let filename = format!("brainly-vault-{}.zip", exported_at.format("%Y-%m-%d"));
+            HttpResponse::Ok()
+                .content_type("application/zip")
+                .insert_header(ContentDisposition {
+                    disposition: DispositionType::Attachment,
+                    parameters: vec![DispositionParam::Filename(filename)],
+                })
+                .body(archive)
+        }
+        Ok(Err(e)) => {
+            tracing::error!(error = %e, "Failed to build the Markdown export");
+            HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                success: false,
+                message: e.to_string(),
+                data: None,
+            })
+        }
+        Err(e) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+            success: false,
+            message: e.to_string(),
+            data: None,
+        }),
+    }
+}
+
+// A batch of the items of a user after a given ID joined with their tags, grouped by item
+const CONTENTS_BATCH_QUERY: &str =
+    "SELECT c.id, c.title, c.type_, c.link, c.url, c.is_read, c.collection, c.visibility,
+            c.created_at, ct.tag
+     FROM (SELECT id, title, type_, link, url, is_read, collection, visibility, created_at
+           FROM contents
+           WHERE user_id = ? AND deleted_at IS NULL AND id > ? ORDER BY id LIMIT ?) c
+     LEFT JOIN content_tags ct ON ct.content_id = c.id
+     ORDER BY c.id, ct.tag";
+
+// Every item of a user joined with its tags, one row per tag, grouped by item
+const CONTENTS_QUERY: &str =
+    "SELECT c.id, c.title, c.type_, c.link, c.url, c.is_read, c.collection, c.visibility,
+            c.created_at, ct.tag
+     FROM contents c LEFT JOIN content_tags ct ON ct.content_id = c.id
+     WHERE c.user_id = ? AND c.deleted_at IS NULL ORDER BY c.id, ct.tag";
+
+// The newest shared items of a user joined with their tags, newest first
+const RECENT_CONTENTS_QUERY: &str =
+    "SELECT c.id, c.title, c.type_, c.link, c.url, c.is_read, c.collection, c.visibility,
+            c.created_at, ct.tag
+     FROM (SELECT id, title, type_, link, url, is_read, collection, visibility, created_at
+           FROM contents
+           WHERE user_id = ? AND deleted_at IS NULL AND visibility = 'shared'
+           ORDER BY created_at DESC, id DESC LIMIT ?) c
+     LEFT JOIN content_tags ct ON ct.content_id = c.id
+     ORDER BY c.created_at DESC, c.id DESC, ct.tag";
+
+// Add a row to the item being assembled. Rows arrive grouped by item, so a row with a new
+// ID completes the previous item, which is returned.
+fn add_row(current: &mut Option<ExportedContent>, row: ExportRow) -> Option<ExportedContent> {
+    let (id, title, type_, link, url, read, collection, visibility, created_at, tag) = row;
+    let completed = match current {
+        Some(item) if item.id != id => current.take(),
+        _ => None,
+    };
+
+    let item = current.get_or_insert_with(|| ExportedContent {
+        id,
+        type_: type_.parse().unwrap_or(ContentType::Article), // Unknown types export as articles
+        title,
+        url,
+        link,
+        tags: Vec::new(),
+        read,
+        collection,
+        visibility: visibility.parse().unwrap_or_default(),
+        created_at,
+    });
+    item.tags.extend(tag);
+    completed
+}
+
+// Read the user's content with its tags, oldest first, one batch at a time, sending each
+// item to the client. Stops early when the client goes away.
+async fn stream_contents(
+    db: &MySqlPool,
+    user_id: i32,
+    sender: &mpsc::Sender<Chunk>,
+) -> Result<(), sqlx::Error> {
+    let mut after = 0;
+    let mut first = true;
+    loop {
+        let rows = sqlx::query_as::<_, ExportRow>(CONTENTS_BATCH_QUERY)
+            .bind(user_id)
+            .bind(after)
+            .bind(EXPORT_BATCH)
+            .fetch_all(db)
+            .instrument(info_span!("db_query", query = "select_export_contents"))
+            .await?;
+        let items = group_rows(rows);
+        let Some(last) = items.last() else {
+            return Ok(());
+        };
+        after = last.id;
+        let done = items.len() < EXPORT_BATCH as usize;
+
+        for item in items {
+            if !send_item(sender, item, &mut first).await {
+                return Ok(());
+            }
+        }
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+// Every item of a user with its tags, oldest first
+async fn load_contents(db: &MySqlPool, user_id: i32) -> Result<Vec<ExportedContent>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ExportRow>(CONTENTS_QUERY)
+        .bind(user_id)
+        .fetch_all(db)
+        .await?;
+    Ok(group_rows(rows))
+}
+
+// The share hash of a user's brain, None while it is private, and every item of the user
+async fn load_vault(
+    db: &MySqlPool,
+    user_id: i32,
+) -> Result<(Option<String>, Vec<ExportedContent>), sqlx::Error> {
+    let share_hash =
+        sqlx::query_scalar::<_, Option<String>>("SELECT share_hash FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_one(db)
+            .instrument(info_span!("db_query", query = "select_share_hash"))
+            .await?;
+    let items = load_contents(db, user_id)
+        .instrument(info_span!("db_query", query = "select_export_contents"))
+        .await?;
+    Ok((share_hash, items))
+}
+
+// The `limit` newest shared items of a user with their tags, newest first. Private items
+// are left out, as this is what the shared brain publishes.
+pub(crate) async fn recent_shared_contents(
+    db: &MySqlPool,
+    user_id: i32,
+    limit: u32,
+) -> Result<Vec<ExportedContent>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, ExportRow>(RECENT_CONTENTS_QUERY)
+        .bind(user_id)
+        .bind(limit)
+        .fetch_all(db)
+        .await?;
+    Ok(group_rows(rows))
+}
+
+// Assemble items from rows grouped by item
+fn group_rows(rows: Vec<ExportRow>) -> Vec<ExportedContent> {
+    let mut items = Vec::new();
+    let mut current: Option<ExportedContent> = None;
+    for row in rows {
+        items.extend(add_row(&mut current, row));
+    }
+    items.extend(current);
+    items
+}
+
+// Serialize one item as an array element. Returns false once the client has disconnected.
+async fn send_item(sender: &mpsc::Sender<Chunk>, item: ExportedContent, first: &mut bool) -> bool {
+    let mut chunk = if *first {
+        String::new()
+    } else {
+        ",".to_string()
+    };
+    *first = false;
+    chunk.push_str(&serde_json::to_string(&item).expect("Export items always serialize"));
+    sender.send(Ok(Bytes::from(chunk))).await.is_ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use brainly_types::content::Visibility;
+
+    use super::*;
+
+    fn row(id: i32, tag: Option<&str>) -> ExportRow {
+        (
+            id,
+            format!("Item {}", id),
+            "Video".to_string(),
+            format!("link{}", id),
+            None,
+            false,
+            None,
+            "private".to_string(),
+            DateTime::<Utc>::UNIX_EPOCH,
+            tag.map(str::to_string),
+        )
+    }
+
+    #[test]
+    fn groups_tag_rows_by_item() {
+        let items = group_rows(vec![
+            row(1, Some("a")),
+            row(1, Some("b")),
+            row(2, None),
+            row(3, Some("c")),
+        ]);
+        let summary: Vec<(i32, Vec<String>)> = items
+            .iter()
+            .map(|item| (item.id, item.tags.clone()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                (1, vec!["a".to_string(), "b".to_string()]),
+                (2, vec![]),
+                (3, vec!["c".to_string()]),
+            ]
+        );
+        assert_eq!(items[0].type_, ContentType::Video);
+        assert_eq!(items[0].visibility, Visibility::Private);
+        assert!(group_rows(Vec::new()).is_empty());
+    }
+}
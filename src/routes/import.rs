@@ -0,0 +1,571 @@
+use std::{collections::HashMap, str::FromStr};
+
+use actix_multipart::{Multipart, MultipartError};
+use actix_web::{
+    http::{header::LOCATION, StatusCode},
+    web::{self, Bytes, Data, Path},
+    HttpRequest, HttpResponse, Responder, ResponseError,
+};
+use brainly_types::{
+    content::{Content as NewContent, ContentType},
+    import::{ImportFormat, ImportJob, ImportOutcome, ImportRowReport, ImportStatus},
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use sqlx::MySqlPool;
+use tracing::{info_span, Instrument};
+use validator::Validate;
+
+use crate::canonical_url::url_hash;
+use crate::import::{self, ParsedRow};
+use crate::metrics::Metrics;
+use crate::openapi::{ImportUpload, NoData};
+use crate::routes::utils::generate_random_string;
+
+use super::content::{unique_tags, Content};
+use super::validation::validation_summary;
+use super::{api_key::Scope, jwt::authenticate, SuccessResponse};
+
+// Largest accepted upload. Browser bookmark exports of tens of thousands of links stay well below.
+const IMPORT_MAX_BYTES: usize = 10 * 1024 * 1024;
+
+// Items processed per import, further rows are reported as failed
+const IMPORT_MAX_ROWS: usize = 20_000;
+
+// Background jobs bringing content saved elsewhere into brainly
+pub struct Import;
+
+// Import job row: (id, format, status, created, skipped, failed, error, report, created_at, finished_at)
+type ImportJobRow = (
+    i32,
+    String,
+    String,
+    i32,
+    i32,
+    i32,
+    Option<String>,
+    Option<String>,
+    DateTime<Utc>,
+    Option<DateTime<Utc>>,
+);
+
+// The fields of an import upload
+struct Upload {
+    filename: Option<String>, // Name of the uploaded file, if the client sent one
+    data: Bytes,              // Contents of the `file` field
+    format: Option<String>,   // The `format` field, lower-cased
+}
+
+fn failure(status: StatusCode, message: impl Into<String>) -> HttpResponse {
+    HttpResponse::build(status).json(SuccessResponse::<()> {
+        success: false,
+        message: message.into(),
+        data: None,
+    })
+}
+
+impl Import {
+    // Jobs run inside the server process, so any still pending or running at startup were
+    // cut short by a restart. Mark them failed instead of leaving them running forever.
+    pub async fn fail_interrupted_jobs(db: &MySqlPool) -> Result<u64, sqlx::Error> {
+        sqlx::query(
+            "UPDATE import_jobs SET status = 'failed', error = 'Interrupted by a server restart',
+             finished_at = CURRENT_TIMESTAMP WHERE status IN ('pending', 'running')",
+        )
+        .execute(db)
+        .await
+        .map(|result| result.rows_affected())
+    }
+}
+
+// Accept a file upload (multipart/form-data with a `file` field and an optional `format`
+// field), parse it and save its items in a background job. Responds 202 with the job,
+// whose report can be polled at /api/v1/user/import/{id}.
+#[utoipa::path(post, path = "/api/v1/user/import", tag = "content",
+    request_body(content = ImportUpload, content_type = "multipart/form-data"),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 202, description = "Import queued, poll the job in the Location header", body = SuccessResponse<ImportJob>),
+        (status = 400, description = "Malformed upload or unknown format", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 413, description = "File larger than 10 MiB", body = SuccessResponse<NoData>),
+        (status = 415, description = "Not a multipart/form-data upload", body = SuccessResponse<NoData>),
+        (status = 422, description = "The file could not be read in the given format", body = SuccessResponse<NoData>),
+    ))]
+pub async fn import_file(
+    db: Data<MySqlPool>,    // Database connection pool
+    metrics: Data<Metrics>, // Metrics registry
+    req: HttpRequest,       // Incoming HTTP request
+    payload: Multipart,     // Multipart body, read with a size limit
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let upload = match read_upload(payload, IMPORT_MAX_BYTES).await {
+        Ok(upload) => upload,
+        Err((status, message)) => return failure(status, message),
+    };
+
+    // An explicit format wins over the file name and contents
+    let format = match upload.format {
+        Some(value) => match ImportFormat::from_str(&value) {
+            Ok(format) => Some(format),
+            Err(_) => {
+                return failure(
+                    StatusCode::BAD_REQUEST,
+                    format!("Unknown import format {}", value),
+                )
+            }
+        },
+        None => import::detect_format(upload.filename.as_deref(), &upload.data),
+    };
+    let format = match format {
+        Some(format) => format,
+        None => {
+            return failure(
+                StatusCode::BAD_REQUEST,
+                "Could not tell the file format, set the format field",
+            )
+        }
+    };
+
+    // Parsing is CPU bound, so keep it off the async workers
+    let data = upload.data;
+    let rows = match web::block(move || import::parse(format, &data)).await {
+        Ok(Ok(rows)) => rows,
+        Ok(Err(e)) => return failure(StatusCode::UNPROCESSABLE_ENTITY, e),
+        Err(e) => return failure(StatusCode::INTERNAL_SERVER_ERROR, e.to_string()),
+    };
+
+    let job_id = sqlx::query("INSERT INTO import_jobs (user_id, format) VALUES (?, ?)")
+        .bind(user_id)
+        .bind(format.to_string())
+        .execute(&**db)
+        .instrument(info_span!("db_query", query = "insert_import_job"))
+        .await;
+    let job_id = match job_id {
+        Ok(result) => result.last_insert_id() as i32,
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to create import job");
+            return failure(StatusCode::INTERNAL_SERVER_ERROR, e.to_string());
+        }
+    };
+
+    tracing::info!(job_id, %format, rows = rows.len(), "Import job queued");
+    let job_db = db.into_inner();
+    let job_metrics = metrics.into_inner();
+    tokio::spawn(
+        run_job(job_db, job_metrics, job_id, user_id, rows).instrument(info_span!(
+            "import_job",
+            job_id,
+            user_id
+        )),
+    );
+
+    HttpResponse::Accepted()
+        .insert_header((LOCATION, format!("/api/v1/user/import/{}", job_id)))
+        .json(SuccessResponse {
+            success: true,
+            message: "Import started".to_string(),
+            data: Some(ImportJob {
+                id: job_id,
+                format,
+                status: ImportStatus::Pending,
+                created: 0,
+                skipped: 0,
+                failed: 0,
+                error: None,
+                rows: Vec::new(),
+                created_at: Utc::now(),
+                finished_at: None,
+            }),
+        })
+}
+
+// Fetch an import job of the signed in user, with its report once it has finished
+#[utoipa::path(get, path = "/api/v1/user/import/{id}", tag = "content",
+    params(("id" = i32, Path, description = "Import job ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "The job, with a per-row report once it has finished", body = SuccessResponse<ImportJob>),
+        (status = 404, description = "Not found or not owned by the user", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_import_job(
+    db: Data<MySqlPool>, // Database connection pool
+    params: Path<i32>,   // Extracted job ID from the URL path
+    req: HttpRequest,    // Incoming HTTP request
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentRead).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let row: Result<Option<ImportJobRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, format, status, created, skipped, failed, error, report, created_at, finished_at
+         FROM import_jobs WHERE id = ? AND user_id = ?",
+    )
+    .bind(params.into_inner())
+    .bind(user_id)
+    .fetch_optional(&**db)
+    .instrument(info_span!("db_query", query = "select_import_job"))
+    .await;
+
+    match row {
+        Ok(Some((
+            id,
+            format,
+            status,
+            created,
+            skipped,
+            failed,
+            error,
+            report,
+            created_at,
+            finished_at,
+        ))) => HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: "Import job fetched successfully".to_string(),
+            data: Some(ImportJob {
+                id,
+                format: ImportFormat::from_str(&format).unwrap_or(ImportFormat::Json),
+                status: ImportStatus::from_str(&status).unwrap_or(ImportStatus::Failed),
+                created: created as u32,
+                skipped: skipped as u32,
+                failed: failed as u32,
+                error,
+                rows: report
+                    .and_then(|report| serde_json::from_str(&report).ok())
+                    .unwrap_or_default(),
+                created_at,
+                finished_at,
+            }),
+        }),
+        Ok(None) => failure(StatusCode::NOT_FOUND, "Import job not found"),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to fetch import job");
+            failure(StatusCode::INTERNAL_SERVER_ERROR, e.to_string())
+        }
+    }
+}
+
+// Save the parsed rows, skipping URLs the user already saved, then store the report
+async fn run_job(
+    db: std::sync::Arc<MySqlPool>,
+    metrics: std::sync::Arc<Metrics>,
+    job_id: i32,
+    user_id: i32,
+    rows: Vec<ParsedRow>,
+) {
+    let result = async {
+        sqlx::query("UPDATE import_jobs SET status = 'running' WHERE id = ?")
+            .bind(job_id)
+            .execute(&*db)
+            .await?;
+
+        let report = import_rows(&db, &metrics, user_id, rows).await?;
+        let count = |outcome| report.iter().filter(|row| row.outcome == outcome).count() as u32;
+        sqlx::query(
+            "UPDATE import_jobs SET status = 'completed', created = ?, skipped = ?, failed = ?,
+             report = ?, finished_at = CURRENT_TIMESTAMP WHERE id = ?",
+        )
+        .bind(count(ImportOutcome::Created))
+        .bind(count(ImportOutcome::Skipped))
+        .bind(count(ImportOutcome::Failed))
+        .bind(serde_json::to_string(&report).expect("Import reports always serialize"))
+        .bind(job_id)
+        .execute(&*db)
+        .await?;
+        Ok::<_, sqlx::Error>(())
+    }
+    .await;
+
+    match result {
+        Ok(()) => tracing::info!("Import job completed"),
+        Err(e) => {
+            tracing::error!(error = %e, "Import job failed");
+            let _ = sqlx::query(
+                "UPDATE import_jobs SET status = 'failed', error = ?, finished_at = CURRENT_TIMESTAMP
+                 WHERE id = ?",
+            )
+            .bind(e.to_string())
+            .bind(job_id)
+            .execute(&*db)
+            .await;
+        }
+    }
+}
+
+// Save each row and record what happened to it. Only failing to read the user's existing
+// URLs aborts the job, a row that cannot be saved is reported and the import goes on.
+async fn import_rows(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    user_id: i32,
+    rows: Vec<ParsedRow>,
+) -> Result<Vec<ImportRowReport>, sqlx::Error> {
+    // Canonical URL hash -> row that saved it in this import, None for items saved before
+    let existing: Vec<String> = sqlx::query_scalar(
+        "SELECT live_url_hash FROM contents WHERE user_id = ? AND live_url_hash IS NOT NULL",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .instrument(info_span!("db_query", query = "select_user_url_hashes"))
+    .await?;
+    let mut seen: HashMap<String, Option<usize>> =
+        existing.into_iter().map(|url| (url, None)).collect();
+
+    let mut report = Vec::with_capacity(rows.len());
+    for (index, row) in rows.into_iter().enumerate() {
+        let row_number = index + 1;
+        let mut entry = ImportRowReport {
+            row: row_number,
+            url: None,
+            outcome: ImportOutcome::Failed,
+            content_id: None,
+            reason: None,
+        };
+
+        if index >= IMPORT_MAX_ROWS {
+            entry.reason = Some(format!("Imports are limited to {} rows", IMPORT_MAX_ROWS));
+            report.push(entry);
+            continue;
+        }
+        let item = match row {
+            Ok(item) => item,
+            Err(reason) => {
+                entry.reason = Some(reason);
+                report.push(entry);
+                continue;
+            }
+        };
+        entry.url = item.url.clone();
+
+        if let Some(url) = &item.url {
+            if let Some(first) = seen.get(&url_hash(url)) {
+                entry.outcome = ImportOutcome::Skipped;
+                entry.reason = Some(match first {
+                    Some(first) => format!("Duplicate of row {}", first),
+                    None => "Already saved".to_string(),
+                });
+                report.push(entry);
+                continue;
+            }
+        }
+
+        let created_at = item.created_at;
+        let content = new_content(item);
+        if let Err(errors) = content.validate() {
+            entry.reason = Some(validation_summary(&errors));
+            report.push(entry);
+            continue;
+        }
+
+        let tags = unique_tags(&content.tags);
+        let link = generate_random_string(16);
+        match Content::insert_content(db, user_id, &link, &content, &tags, created_at).await {
+            Ok(id) => {
+                metrics
+                    .content_created
+                    .with_label_values(&[&content.type_.to_string()])
+                    .inc();
+                if let Some(url) = content.url {
+                    seen.insert(url_hash(&url), Some(row_number));
+                }
+                entry.outcome = ImportOutcome::Created;
+                entry.content_id = Some(id);
+            }
+            Err(e) => {
+                tracing::warn!(error = %e, row = row_number, "Failed to import row");
+                entry.reason = Some(e.to_string());
+            }
+        }
+        report.push(entry);
+    }
+    Ok(report)
+}
+
+// Content to save for an imported entry, keeping its collection and visibility
+fn new_content(item: import::ImportItem) -> NewContent {
+    NewContent {
+        type_: item.type_.unwrap_or_else(|| match &item.url {
+            Some(url) => import::content_type_for_url(url),
+            None => ContentType::Article,
+        }),
+        title: item.title.or_else(|| item.url.clone()).unwrap_or_default(),
+        url: item.url,
+        tags: item.tags,
+        read: item.read,
+        collection: item.collection,
+        visibility: item.visibility,
+    }
+}
+
+// Read the `file` and `format` fields of a multipart/form-data upload. Uploads whose fields
+// add up to more than `limit` bytes are refused before they are buffered in full.
+async fn read_upload(mut payload: Multipart, limit: usize) -> Result<Upload, (StatusCode, String)> {
+    let mut file = None;
+    let mut format = None;
+    let mut remaining = limit;
+    while let Some(field) = payload.next().await {
+        let mut field = field.map_err(upload_error)?;
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field
+            .content_disposition()
+            .and_then(|disposition| disposition.get_filename())
+            .map(str::to_string);
+        let data = match field.bytes(remaining).await {
+            Ok(data) => data.map_err(upload_error)?,
+            Err(_) => {
+                return Err((
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    format!("Uploads are limited to {} MiB", limit / 1024 / 1024),
+                ))
+            }
+        };
+        remaining -= data.len();
+
+        match name.as_str() {
+            "file" => file = Some((filename, data)),
+            "format" => format = Some(String::from_utf8_lossy(&data).trim().to_ascii_lowercase()),
+            _ => {} // Unknown fields are ignored, but count towards the limit
+        }
+    }
+
+    let (filename, data) = file.ok_or((
+        StatusCode::BAD_REQUEST,
+        "Missing the file field".to_string(),
+    ))?;
+    Ok(Upload {
+        filename,
+        data,
+        format,
+    })
+}
+
+fn upload_error(e: MultipartError) -> (StatusCode, String) {
+    match e {
+        MultipartError::ContentTypeMissing
+        | MultipartError::ContentTypeParse
+        | MultipartError::ContentTypeIncompatible => (
+            StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            "Upload the file as multipart/form-data".to_string(),
+        ),
+        e => (e.status_code(), e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::http::header::{HeaderMap, HeaderValue, CONTENT_TYPE};
+    use futures_util::stream;
+
+    use brainly_types::{
+        content::Visibility,
+        export::{Export, ExportedContent, ExportedUser, EXPORT_VERSION},
+    };
+
+    use super::*;
+    use crate::import::fixture;
+
+    // A multipart body with one part per (name, filename, data)
+    fn multipart(content_type: &str, parts: &[(&str, Option<&str>, &str)]) -> Multipart {
+        let mut body = String::new();
+        for (name, filename, data) in parts {
+            body.push_str("--BOUNDARY\r\nContent-Disposition: form-data; name=\"");
+            body.push_str(name);
+            if let Some(filename) = filename {
+                body.push_str("\"; filename=\"");
+                body.push_str(filename);
+            }
+            body.push_str("\"\r\n\r\n");
+            body.push_str(data);
+            body.push_str("\r\n");
+        }
+        body.push_str("--BOUNDARY--\r\n");
+
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_str(content_type).unwrap());
+        Multipart::new(&headers, stream::once(async { Ok(Bytes::from(body)) }))
+    }
+
+    const FORM: &str = "multipart/form-data; boundary=BOUNDARY";
+
+    #[actix_web::test]
+    async fn reads_the_file_and_format_fields() {
+        let bookmarks = fixture("bookmarks.html");
+        let payload = multipart(
+            FORM,
+            &[
+                ("note", None, "ignored"),
+                ("format", None, " Bookmarks "),
+                ("file", Some("bookmarks.html"), &bookmarks),
+            ],
+        );
+        let upload = read_upload(payload, IMPORT_MAX_BYTES).await.unwrap();
+        assert_eq!(upload.filename.as_deref(), Some("bookmarks.html"));
+        assert_eq!(upload.format.as_deref(), Some("bookmarks"));
+        assert_eq!(upload.data, bookmarks.as_bytes());
+    }
+
+    #[actix_web::test]
+    async fn refuses_oversized_and_incomplete_uploads() {
+        let payload = multipart(FORM, &[("file", Some("a.csv"), "url\nhttps://a.test\n")]);
+        let (status, _) = read_upload(payload, 8).await.err().unwrap();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        // Earlier fields count towards the limit too
+        let payload = multipart(FORM, &[("note", None, "0123456789"), ("file", None, "x")]);
+        let (status, _) = read_upload(payload, 10).await.err().unwrap();
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+
+        let payload = multipart(FORM, &[("format", None, "csv")]);
+        let (status, message) = read_upload(payload, IMPORT_MAX_BYTES).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(message, "Missing the file field");
+    }
+
+    #[actix_web::test]
+    async fn requires_multipart_form_data() {
+        let payload = multipart("application/json", &[("file", None, "{}")]);
+        let (status, _) = read_upload(payload, IMPORT_MAX_BYTES).await.err().unwrap();
+        assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+        let payload = multipart("multipart/form-data", &[("file", None, "{}")]);
+        let (status, _) = read_upload(payload, IMPORT_MAX_BYTES).await.err().unwrap();
+        assert_eq!(status, StatusCode::BAD_REQUEST); // No boundary
+    }
+
+    #[test]
+    fn keeps_collection_and_visibility_through_export_and_import() {
+        let export = Export {
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+            user: ExportedUser {
+                username: "alice".to_string(),
+                created_at: Utc::now(),
+            },
+            collections: vec!["Work".to_string()],
+            contents: vec![ExportedContent {
+                id: 3,
+                type_: ContentType::Article,
+                title: "Salaries".to_string(),
+                url: Some("https://example.com/salaries".to_string()),
+                link: "abcdefghijklmnop".to_string(),
+                tags: vec!["hr".to_string()],
+                read: false,
+                collection: Some("Work".to_string()),
+                visibility: Visibility::Private,
+                created_at: Utc::now(),
+            }],
+        };
+        let data = serde_json::to_vec(&export).unwrap();
+
+        let rows = import::parse(ImportFormat::Json, &data).unwrap();
+        let content = new_content(rows.into_iter().next().unwrap().unwrap());
+        assert!(content.validate().is_ok());
+        assert_eq!(content.collection.as_deref(), Some("Work"));
+        assert_eq!(content.visibility, Visibility::Private);
+    }
+}
@@ -1,21 +1,39 @@
+use std::{collections::HashMap, sync::Mutex, time::Instant};
+
+use super::api_key::{verify_api_key, Scope, API_KEY_PREFIX};
 use super::SuccessResponse;
 use actix_web::{
-    cookie::time::{Duration, OffsetDateTime}, // Used for handling token expiration time
-    HttpRequest, HttpResponse
+    cookie::{
+        time::{Duration, OffsetDateTime}, // Used for handling token expiration time
+        Cookie,
+    },
+    http::{header::AUTHORIZATION, StatusCode},
+    web::Data,
+    HttpRequest, HttpResponse,
 };
+use chrono::{DateTime, Utc};
 use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, TokenData, Validation};
 use serde::{Deserialize, Serialize}; // PublicUser struct is imported from the user module
+use sqlx::MySqlPool;
+
+use crate::config::CookieConfig;
+
+// How long a looked-up account status is trusted before the database is asked again.
+// Disabling an account therefore ends its sessions within this time.
+const ACCOUNT_STATUS_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+// Once the cache holds more accounts than this, expired entries are pruned
+const ACCOUNT_CACHE_PRUNE_THRESHOLD: usize = 10_000;
 
 // Structure representing JWT Claims (Payload)
 #[derive(Serialize, Debug, Deserialize)]
 pub struct Claims {
-    sub: i32, // Subject: Holds user information (ID and username)
-    exp: usize,      // Expiry time: When the token expires (in seconds since epoch)
+    sub: i32,   // Subject: Holds user information (ID and username)
+    exp: usize, // Expiry time: When the token expires (in seconds since epoch)
 }
 
 // Secret key for signing and verifying JWT tokens
 // NOTE: `b""` converts the string into a byte array. It's used because JWT libraries work with binary data.
-const SECRET_KEY: &[u8] = b"my_super_secret_key"; // Use environment variables in production to avoid hardcoding secrets!
+pub(super) const SECRET_KEY: &[u8] = b"my_super_secret_key"; // Use environment variables in production to avoid hardcoding secrets!
 
 // Implementation of the Claims struct
 impl Claims {
@@ -27,14 +45,13 @@ impl Claims {
 
 // Function to generate a JWT token
 pub fn generate_token(id: i32) -> String {
-
     // Calculate expiration time (current time + 2 hours)
     let expiration = OffsetDateTime::now_utc() + Duration::hours(2);
 
     // Create a Claims instance
     let claims = Claims::new(
-        id,          // Add user info to the payload
-        expiration.unix_timestamp() as usize // Set the token expiration time
+        id,                                   // Add user info to the payload
+        expiration.unix_timestamp() as usize, // Set the token expiration time
     );
 
     // Encode the Claims into a JWT token
@@ -46,6 +63,21 @@ pub fn generate_token(id: i32) -> String {
     .expect("Error generating token") // Panic if token generation fails
 }
 
+// Build the cookie carrying a session token, shared by every sign in flow
+pub fn auth_cookie(token: &str, config: &CookieConfig) -> Cookie<'static> {
+    let mut cookie = Cookie::build("auth_token", token.to_string())
+        .path("/")
+        .http_only(true)
+        .max_age(Duration::hours(2))
+        .same_site(config.same_site)
+        .secure(config.secure)
+        .finish();
+    if let Some(domain) = &config.domain {
+        cookie.set_domain(domain.clone());
+    }
+    cookie
+}
+
 // Function to validate (verify) a JWT token
 fn verify_token(token: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::Error> {
     decode(
@@ -55,18 +87,63 @@ fn verify_token(token: &str) -> Result<TokenData<Claims>, jsonwebtoken::errors::
     )
 }
 
+// Extract the token from an `Authorization: Bearer <token>` header
+fn bearer_token(req: &HttpRequest) -> Option<String> {
+    let header = req.headers().get(AUTHORIZATION)?.to_str().ok()?;
+    header
+        .strip_prefix("Bearer ")
+        .map(|token| token.trim().to_string())
+}
+
+// Extract an API key from the `X-Api-Key` header or a Bearer header carrying an API key
+pub fn api_key_from_request(req: &HttpRequest) -> Option<String> {
+    if let Some(key) = req.headers().get("X-Api-Key").and_then(|v| v.to_str().ok()) {
+        return Some(key.trim().to_string());
+    }
+    bearer_token(req).filter(|token| token.starts_with(API_KEY_PREFIX))
+}
+
+// Whether the request carries credentials in a header (a Bearer token or an API key).
+// Header credentials take precedence, so such a request is never authenticated by the cookie.
+fn has_header_credentials(req: &HttpRequest) -> bool {
+    req.headers().contains_key(AUTHORIZATION) || req.headers().contains_key("X-Api-Key")
+}
+
+// Whether the request would be authenticated by the auth cookie, which browsers attach
+// automatically and therefore needs CSRF protection
+pub fn uses_session_cookie(req: &HttpRequest) -> bool {
+    !has_header_credentials(req) && req.cookie("auth_token").is_some()
+}
+
+// Read the session JWT from a Bearer header for non-browser clients, or from the auth cookie
+fn session_token(req: &HttpRequest) -> Option<String> {
+    if has_header_credentials(req) {
+        return bearer_token(req).filter(|token| !token.starts_with(API_KEY_PREFIX));
+    }
+    req.cookie("auth_token")
+        .map(|cookie| cookie.value().to_string())
+}
+
+// Extract the authenticated user ID from the request, if it carries a valid token
+pub fn user_id_from_request(req: &HttpRequest) -> Option<i32> {
+    let token = session_token(req)?;
+    verify_token(&token).ok().map(|data| data.claims.sub)
+}
 
 // Middleware-like function to validate token and extract user data
 pub async fn validate_token(req: HttpRequest) -> Result<i32, HttpResponse> {
-    if let Some(cookie) = req.cookie("auth_token") {
-        let token = cookie.value();
-        let verified_token = verify_token(token);
+    if let Some(token) = session_token(&req) {
+        let verified_token = verify_token(&token);
         match verified_token {
             Ok(data) => {
                 let user_id = data.claims.sub;
+                // Tokens outlive an account being disabled, so check it on every request
+                check_account_cached(&req, user_id).await?;
+                tracing::Span::current().record("user_id", user_id); // Attach the user to the request span
                 Ok(user_id) // Return user ID and username
             }
             Err(e) => {
+                tracing::warn!(error = %e, "Rejected invalid token");
                 // Handle invalid token
                 Err(HttpResponse::Unauthorized().json(SuccessResponse::<()> {
                     success: false,
@@ -83,4 +160,153 @@ pub async fn validate_token(req: HttpRequest) -> Result<i32, HttpResponse> {
             data: None,
         }))
     }
-}
\ No newline at end of file
+}
+
+// Whether an account may still be used
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum AccountStatus {
+    Active,
+    Disabled,
+    Missing,
+}
+
+// Recently checked account statuses, shared by every worker, so authenticated requests do
+// not each query the users table
+#[derive(Default)]
+pub struct AccountCache {
+    entries: Mutex<HashMap<i32, (Instant, AccountStatus)>>,
+}
+
+impl AccountCache {
+    pub fn new() -> AccountCache {
+        AccountCache::default()
+    }
+
+    fn get(&self, user_id: i32, now: Instant) -> Option<AccountStatus> {
+        let entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&user_id)
+            .filter(|(checked_at, _)| now.duration_since(*checked_at) < ACCOUNT_STATUS_TTL)
+            .map(|(_, status)| *status)
+    }
+
+    fn insert(&self, user_id: i32, status: AccountStatus, now: Instant) {
+        let mut entries = self.entries.lock().unwrap_or_else(|e| e.into_inner());
+        if entries.len() > ACCOUNT_CACHE_PRUNE_THRESHOLD {
+            entries
+                .retain(|_, (checked_at, _)| now.duration_since(*checked_at) < ACCOUNT_STATUS_TTL);
+        }
+        entries.insert(user_id, (now, status));
+    }
+}
+
+async fn account_status(db: &MySqlPool, user_id: i32) -> Result<AccountStatus, HttpResponse> {
+    let disabled_at: Result<Option<Option<DateTime<Utc>>>, sqlx::Error> =
+        sqlx::query_scalar("SELECT disabled_at FROM users WHERE id = ?")
+            .bind(user_id)
+            .fetch_optional(db)
+            .await;
+
+    match disabled_at {
+        Ok(Some(None)) => Ok(AccountStatus::Active),
+        Ok(Some(Some(_))) => Ok(AccountStatus::Disabled),
+        Ok(None) => Ok(AccountStatus::Missing),
+        Err(e) => {
+            tracing::error!(error = %e, "Failed to check account status");
+            Err(
+                HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+                    success: false,
+                    message: e.to_string(),
+                    data: None,
+                }),
+            )
+        }
+    }
+}
+
+// The error response for an account that may not be used, None for an active one
+fn inactive_response(user_id: i32, status: AccountStatus) -> Option<HttpResponse> {
+    let (status, message) = match status {
+        AccountStatus::Active => return None,
+        AccountStatus::Disabled => (StatusCode::FORBIDDEN, "Account disabled".to_string()),
+        AccountStatus::Missing => (StatusCode::UNAUTHORIZED, "Account not found".to_string()),
+    };
+    tracing::warn!(user_id, reason = %message, "Rejected inactive account");
+    Some(HttpResponse::build(status).json(SuccessResponse::<()> {
+        success: false,
+        message,
+        data: None,
+    }))
+}
+
+// Reject users whose account was disabled by an operator or no longer exists
+pub async fn check_account(db: &MySqlPool, user_id: i32) -> Result<(), HttpResponse> {
+    let status = account_status(db, user_id).await?;
+    inactive_response(user_id, status).map_or(Ok(()), Err)
+}
+
+// `check_account` for every authenticated request, answered from the `AccountCache` when
+// the account was checked within `ACCOUNT_STATUS_TTL`
+async fn check_account_cached(req: &HttpRequest, user_id: i32) -> Result<(), HttpResponse> {
+    let db = req
+        .app_data::<Data<MySqlPool>>()
+        .expect("Database pool not registered");
+    let Some(cache) = req.app_data::<Data<AccountCache>>() else {
+        return check_account(db, user_id).await;
+    };
+
+    let now = Instant::now();
+    let status = match cache.get(user_id, now) {
+        Some(status) => status,
+        None => {
+            let status = account_status(db, user_id).await?;
+            cache.insert(user_id, status, now);
+            status
+        }
+    };
+    inactive_response(user_id, status).map_or(Ok(()), Err)
+}
+
+// Authenticate a request with either an API key granting `scope` or a session token
+// Session tokens carry every scope, since they belong to the signed in user themselves
+pub async fn authenticate(req: HttpRequest, scope: Scope) -> Result<i32, HttpResponse> {
+    match api_key_from_request(&req) {
+        Some(key) => {
+            let db = req
+                .app_data::<Data<MySqlPool>>()
+                .expect("Database pool not registered");
+            let user_id = verify_api_key(db, &key, scope).await?;
+            check_account_cached(&req, user_id).await?;
+            tracing::Span::current().record("user_id", user_id); // Attach the user to the request span
+            Ok(user_id)
+        }
+        None => validate_token(req).await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cached_statuses_expire() {
+        let cache = AccountCache::new();
+        let now = Instant::now();
+        assert_eq!(cache.get(1, now), None);
+
+        cache.insert(1, AccountStatus::Active, now);
+        cache.insert(2, AccountStatus::Disabled, now);
+        assert_eq!(cache.get(1, now), Some(AccountStatus::Active));
+        assert_eq!(cache.get(2, now), Some(AccountStatus::Disabled));
+        assert_eq!(cache.get(1, now + ACCOUNT_STATUS_TTL), None);
+    }
+
+    #[test]
+    fn inactive_accounts_are_rejected() {
+        assert!(inactive_response(1, AccountStatus::Active).is_none());
+        let disabled = inactive_response(1, AccountStatus::Disabled).unwrap();
+        assert_eq!(disabled.status(), StatusCode::FORBIDDEN);
+        let missing = inactive_response(1, AccountStatus::Missing).unwrap();
+        assert_eq!(missing.status(), StatusCode::UNAUTHORIZED);
+    }
+}
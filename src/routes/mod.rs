@@ -1,14 +1,20 @@
 pub mod user;
 pub use user::User;
-pub use serde::Serialize;
-pub mod utils;
 pub mod content;
+pub mod utils;
+pub mod validation;
 pub use content::Content;
+pub mod api_key;
 pub mod jwt;
+pub mod oidc;
+pub use oidc::Oidc;
+pub mod export;
+pub mod import;
+pub use import::Import;
+pub mod brain;
+pub mod subscription;
+pub mod trash;
+pub use trash::Trash;
 
-#[derive(Serialize)]
-pub struct SuccessResponse<T> {
-    success: bool,
-    message: String,
-    data: Option<T>,
-}
\ No newline at end of file
+// Envelope wrapping every JSON response, shared with API clients through brainly-types
+pub use brainly_types::SuccessResponse;
@@ -0,0 +1,253 @@
+// Handlers for the RSS and Atom feeds a user subscribes to. Fetching happens in the
+// background, see `crate::subscriptions`.
+
+use std::collections::HashMap;
+
+use actix_web::{
+    web::{Data, Json, Path},
+    HttpRequest, HttpResponse, Responder,
+};
+use brainly_types::subscription::{CreateSubscription, Subscription};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use tracing::{info_span, Instrument};
+use validator::Validate;
+
+use crate::openapi::NoData;
+
+use super::content::unique_tags;
+use super::validation::validation_error;
+use super::{api_key::Scope, jwt::authenticate, SuccessResponse};
+
+// Feeds a user can subscribe to, so the poller's work stays bounded
+const MAX_SUBSCRIPTIONS: i64 = 200;
+
+// Feed row: (id, url, title, error_count, last_error, last_fetched_at, next_fetch_at, created_at)
+type SubscriptionRow = (
+    i32,
+    String,
+    Option<String>,
+    i32,
+    Option<String>,
+    Option<DateTime<Utc>>,
+    DateTime<Utc>,
+    DateTime<Utc>,
+);
+
+// Subscribe to a feed. It is first fetched on the next poll.
+#[utoipa::path(post, path = "/api/v1/feeds", tag = "feeds",
+    request_body = CreateSubscription,
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 201, description = "Subscribed. The feed is fetched on the next poll; entries already in it are not saved, later ones are", body = SuccessResponse<Subscription>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token, or API key lacks the scope", body = SuccessResponse<NoData>),
+        (status = 409, description = "Already subscribed to this URL", body = SuccessResponse<NoData>),
+        (status = 422, description = "Invalid fields, or too many subscriptions", body = SuccessResponse<HashMap<String, Vec<String>>>),
+    ))]
+pub async fn create_subscription(
+    db: Data<MySqlPool>,
+    req: HttpRequest,
+    body: Json<CreateSubscription>,
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+    if let Err(errors) = body.validate() {
+        return validation_error(errors);
+    }
+
+    let count: Result<i64, sqlx::Error> =
+        sqlx::query_scalar("SELECT COUNT(*) FROM feeds WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_one(&**db)
+            .instrument(info_span!("db_query", query = "count_user_feeds"))
+            .await;
+    match count {
+        Ok(count) if count >= MAX_SUBSCRIPTIONS => {
+            return HttpResponse::UnprocessableEntity().json(SuccessResponse::<()> {
+                success: false,
+                message: format!("At most {} feeds can be subscribed to", MAX_SUBSCRIPTIONS),
+                data: None,
+            })
+        }
+        Ok(_) => {}
+        Err(e) => return server_error(e),
+    }
+
+    let tags = unique_tags(&body.tags);
+    match insert_subscription(&db, user_id, &body.url, &tags).await {
+        Ok(id) => HttpResponse::Created().json(SuccessResponse {
+            success: true,
+            message: "Subscribed".to_string(),
+            data: Some(Subscription {
+                id,
+                url: body.url.clone(),
+                title: None,
+                tags,
+                error_count: 0,
+                last_error: None,
+                last_fetched_at: None,
+                next_fetch_at: Utc::now(),
+                created_at: Utc::now(),
+            }),
+        }),
+        Err(e)
+            if e.as_database_error()
+                .is_some_and(|e| e.is_unique_violation()) =>
+        {
+            HttpResponse::Conflict().json(SuccessResponse::<()> {
+                success: false,
+                message: "Already Subscribed".to_string(),
+                data: None,
+            })
+        }
+        Err(e) => server_error(e),
+    }
+}
+
+// List the user's subscriptions with their polling state
+#[utoipa::path(get, path = "/api/v1/feeds", tag = "feeds",
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "The user's subscriptions, with the error of failing feeds", body = SuccessResponse<Vec<Subscription>>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_subscriptions(db: Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentRead).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let rows: Result<Vec<SubscriptionRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, url, title, error_count, last_error, last_fetched_at, next_fetch_at, created_at
+         FROM feeds WHERE user_id = ? ORDER BY id",
+    )
+    .bind(user_id)
+    .fetch_all(&**db)
+    .instrument(info_span!("db_query", query = "select_user_feeds"))
+    .await;
+    let tags: Result<Vec<(i32, String)>, sqlx::Error> = sqlx::query_as(
+        "SELECT ft.feed_id, ft.tag FROM feed_tags ft JOIN feeds f ON f.id = ft.feed_id
+         WHERE f.user_id = ? ORDER BY ft.tag",
+    )
+    .bind(user_id)
+    .fetch_all(&**db)
+    .instrument(info_span!("db_query", query = "select_user_feed_tags"))
+    .await;
+
+    let (rows, tags) = match (rows, tags) {
+        (Ok(rows), Ok(tags)) => (rows, tags),
+        (Err(e), _) | (_, Err(e)) => return server_error(e),
+    };
+    let mut tags_by_feed: HashMap<i32, Vec<String>> = HashMap::new();
+    for (feed_id, tag) in tags {
+        tags_by_feed.entry(feed_id).or_default().push(tag);
+    }
+
+    let subscriptions: Vec<Subscription> = rows
+        .into_iter()
+        .map(|row| Subscription {
+            id: row.0,
+            url: row.1,
+            title: row.2,
+            tags: tags_by_feed.remove(&row.0).unwrap_or_default(),
+            error_count: row.3.unsigned_abs(),
+            last_error: row.4,
+            last_fetched_at: row.5,
+            next_fetch_at: row.6,
+            created_at: row.7,
+        })
+        .collect();
+
+    HttpResponse::Ok().json(SuccessResponse {
+        success: true,
+        message: "Subscriptions fetched".to_string(),
+        data: Some(subscriptions),
+    })
+}
+
+// Unsubscribe. Items already saved from the feed are kept.
+#[utoipa::path(delete, path = "/api/v1/feeds/{id}", tag = "feeds",
+    params(("id" = i32, Path, description = "Subscription ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Unsubscribed, items saved from the feed are kept", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 404, description = "Not found or not owned by the user", body = SuccessResponse<NoData>),
+    ))]
+pub async fn delete_subscription(
+    db: Data<MySqlPool>,
+    req: HttpRequest,
+    params: Path<i32>, // Subscription ID
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let result = sqlx::query("DELETE FROM feeds WHERE id = ? AND user_id = ?")
+        .bind(params.into_inner())
+        .bind(user_id)
+        .execute(&**db)
+        .instrument(info_span!("db_query", query = "delete_feed"))
+        .await;
+
+    match result {
+        Ok(result) if result.rows_affected() == 0 => {
+            HttpResponse::NotFound().json(SuccessResponse::<()> {
+                success: false,
+                message: "Subscription Not Found".to_string(),
+                data: None,
+            })
+        }
+        Ok(_) => HttpResponse::Ok().json(SuccessResponse::<()> {
+            success: true,
+            message: "Unsubscribed".to_string(),
+            data: None,
+        }),
+        Err(e) => server_error(e),
+    }
+}
+
+// Insert a feed and its tags in one transaction, returning the feed ID
+async fn insert_subscription(
+    db: &MySqlPool,
+    user_id: i32,
+    url: &str,
+    tags: &[String],
+) -> Result<i32, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let feed_id = sqlx::query("INSERT INTO feeds (user_id, url, url_hash) VALUES (?, ?, ?)")
+        .bind(user_id)
+        .bind(url)
+        .bind(hex::encode(Sha256::digest(url.as_bytes())))
+        .execute(&mut *tx)
+        .instrument(info_span!("db_query", query = "insert_feed"))
+        .await?
+        .last_insert_id() as i32;
+
+    for tag in tags {
+        sqlx::query("INSERT INTO feed_tags (feed_id, tag) VALUES (?, ?)")
+            .bind(feed_id)
+            .bind(tag)
+            .execute(&mut *tx)
+            .instrument(info_span!("db_query", query = "insert_feed_tag"))
+            .await?;
+    }
+
+    tx.commit().await?;
+    Ok(feed_id)
+}
+
+fn server_error(e: sqlx::Error) -> HttpResponse {
+    tracing::error!(error = %e, "Feed subscription query failed");
+    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+        success: false,
+        message: e.to_string(),
+        data: None,
+    })
+}
@@ -0,0 +1,414 @@
+use std::{collections::HashMap, str::FromStr, time::Duration};
+
+use actix_web::{
+    web::{Data, Path},
+    HttpRequest, HttpResponse, Responder,
+};
+use brainly_types::content::{ContentType, TrashedContent, UserContents};
+use chrono::{DateTime, Utc};
+use sqlx::MySqlPool;
+use tokio::time::MissedTickBehavior;
+use tracing::{info_span, Instrument};
+
+use crate::config::TrashConfig;
+use crate::openapi::NoData;
+
+use super::{api_key::Scope, jwt::authenticate, SuccessResponse};
+
+// Deleted content. Deleting an item moves it to the trash, where it can be restored until
+// it is purged, by hand or once `TrashConfig::retention` has passed.
+pub struct Trash;
+
+// Trashed content row: (id, title, type_, link, url, is_read, collection, visibility, deleted_at)
+type TrashRow = (
+    i32,
+    String,
+    String,
+    String,
+    Option<String>,
+    bool,
+    Option<String>,
+    String,
+    DateTime<Utc>,
+);
+
+impl Trash {
+    // Purge trashed items older than `retention`, returning how many were deleted
+    pub async fn purge_expired(db: &MySqlPool, retention: Duration) -> Result<u64, sqlx::Error> {
+        let result =
+            sqlx::query("DELETE FROM contents WHERE deleted_at IS NOT NULL AND deleted_at < ?")
+                .bind(purge_cutoff(Utc::now(), retention))
+                .execute(db)
+                .instrument(info_span!("db_query", query = "purge_expired_trash"))
+                .await?;
+        Ok(result.rows_affected())
+    }
+
+    // Purge expired trash in the background every `purge_interval`, for the lifetime of the process
+    pub fn spawn_purge_job(db: MySqlPool, config: TrashConfig) {
+        if config.purge_interval.is_zero() {
+            return;
+        }
+
+        tokio::spawn(
+            async move {
+                let mut interval = tokio::time::interval(config.purge_interval);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                loop {
+                    interval.tick().await;
+                    match Self::purge_expired(&db, config.retention).await {
+                        Ok(0) => {}
+                        Ok(items) => tracing::info!(items, "Purged expired items from the trash"),
+                        Err(e) => tracing::warn!(error = %e, "Failed to purge the trash"),
+                    }
+                }
+            }
+            .instrument(info_span!("trash_purge")),
+        );
+    }
+}
+
+// List the items in the trash, most recently deleted first
+#[utoipa::path(get, path = "/api/v1/trash", tag = "content",
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:read"])),
+    responses(
+        (status = 200, description = "Trashed content, most recently deleted first, with when each item is purged", body = SuccessResponse<Vec<TrashedContent>>),
+        (status = 401, description = "Not authenticated", body = SuccessResponse<NoData>),
+    ))]
+pub async fn get_trash(
+    db: Data<MySqlPool>,
+    trash_config: Data<TrashConfig>,
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentRead).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let rows: Result<Vec<TrashRow>, sqlx::Error> = sqlx::query_as(
+        "SELECT id, title, type_, link, url, is_read, collection, visibility, deleted_at FROM contents
+         WHERE user_id = ? AND deleted_at IS NOT NULL ORDER BY deleted_at DESC, id DESC",
+    )
+    .bind(user_id)
+    .fetch_all(&**db)
+    .instrument(info_span!("db_query", query = "select_trashed_contents"))
+    .await;
+    let tags = trashed_tags_by_content(&db, user_id).await;
+
+    let (rows, mut tags) = match (rows, tags) {
+        (Ok(rows), Ok(tags)) => (rows, tags),
+        (Err(e), _) | (_, Err(e)) => return server_error(e),
+    };
+
+    let items: Vec<TrashedContent> = rows
+        .into_iter()
+        .filter_map(
+            |(id, title, type_, link, url, read, collection, visibility, deleted_at)| {
+                let type_ = ContentType::from_str(&type_).ok()?; // Skip invalid content type
+                Some(TrashedContent {
+                    content: UserContents {
+                        id,
+                        title,
+                        type_,
+                        link,
+                        url,
+                        tags: tags.remove(&id).unwrap_or_default(),
+                        read,
+                        collection,
+                        visibility: visibility.parse().unwrap_or_default(),
+                    },
+                    deleted_at,
+                    purge_at: purge_at(deleted_at, trash_config.retention),
+                })
+            },
+        )
+        .collect();
+
+    HttpResponse::Ok().json(SuccessResponse {
+        success: true,
+        message: "Trash fetched successfully".to_string(),
+        data: Some(items),
+    })
+}
+
+// Tags of each trashed item of a user, keyed by content ID
+async fn trashed_tags_by_content(
+    db: &MySqlPool,
+    user_id: i32,
+) -> Result<HashMap<i32, Vec<String>>, sqlx::Error> {
+    let rows: Vec<(i32, String)> = sqlx::query_as(
+        "SELECT ct.content_id, ct.tag FROM content_tags ct JOIN contents c ON c.id = ct.content_id
+         WHERE c.user_id = ? AND c.deleted_at IS NOT NULL ORDER BY ct.tag",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .instrument(info_span!(
+        "db_query",
+        query = "select_trashed_content_tags"
+    ))
+    .await?;
+
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for (content_id, tag) in rows {
+        tags.entry(content_id).or_default().push(tag);
+    }
+    Ok(tags)
+}
+
+// Take an item out of the trash
+#[utoipa::path(post, path = "/api/v1/content/{id}/restore", tag = "content",
+    params(("id" = i32, Path, description = "Content ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Content restored from the trash", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 404, description = "Not in the user's trash", body = SuccessResponse<NoData>),
+        (status = 409, description = "The URL was saved again since the item was deleted", body = SuccessResponse<NoData>),
+    ))]
+pub async fn restore_content(
+    db: Data<MySqlPool>,
+    params: Path<i32>, // Content ID
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let result = sqlx::query(
+        "UPDATE contents SET deleted_at = NULL
+         WHERE id = ? AND user_id = ? AND deleted_at IS NOT NULL",
+    )
+    .bind(params.into_inner())
+    .bind(user_id)
+    .execute(&**db)
+    .instrument(info_span!("db_query", query = "restore_content"))
+    .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            HttpResponse::Ok().json(SuccessResponse::<()> {
+                success: true,
+                message: "Content restored".to_string(),
+                data: None,
+            })
+        }
+        Ok(_) => not_found(),
+        Err(e) => restore_error(e),
+    }
+}
+
+// Answer a restore the database refused. Restoring makes the URL live again, which the
+// unique index on (user_id, live_url_hash) rejects when the page was saved again while
+// this copy was in the trash.
+fn restore_error(e: sqlx::Error) -> HttpResponse {
+    if e.as_database_error()
+        .is_some_and(|e| e.is_unique_violation())
+    {
+        return HttpResponse::Conflict().json(SuccessResponse::<()> {
+            success: false,
+            message: "Content with this URL is already saved".to_string(),
+            data: None,
+        });
+    }
+    server_error(e)
+}
+
+// Permanently delete one item from the trash
+#[utoipa::path(delete, path = "/api/v1/trash/{id}", tag = "content",
+    params(("id" = i32, Path, description = "Content ID")),
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Content deleted permanently", body = SuccessResponse<NoData>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+        (status = 404, description = "Not in the user's trash", body = SuccessResponse<NoData>),
+    ))]
+pub async fn purge_content(
+    db: Data<MySqlPool>,
+    params: Path<i32>, // Content ID
+    req: HttpRequest,
+) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    // Only trashed items, so a live item is never lost in one step
+    let result =
+        sqlx::query("DELETE FROM contents WHERE id = ? AND user_id = ? AND deleted_at IS NOT NULL")
+            .bind(params.into_inner())
+            .bind(user_id)
+            .execute(&**db)
+            .instrument(info_span!("db_query", query = "purge_content"))
+            .await;
+
+    match result {
+        Ok(result) if result.rows_affected() > 0 => {
+            HttpResponse::Ok().json(SuccessResponse::<()> {
+                success: true,
+                message: "Content deleted permanently".to_string(),
+                data: None,
+            })
+        }
+        Ok(_) => not_found(),
+        Err(e) => server_error(e),
+    }
+}
+
+// Permanently delete everything in the trash, returning how many items were deleted
+#[utoipa::path(delete, path = "/api/v1/trash", tag = "content",
+    security(("cookie_auth" = []), ("bearer_auth" = []), ("api_key" = ["content:write"])),
+    responses(
+        (status = 200, description = "Trash emptied, data holds the number of items deleted permanently", body = SuccessResponse<u64>),
+        (status = 403, description = "Missing or invalid CSRF token", body = SuccessResponse<NoData>),
+    ))]
+pub async fn empty_trash(db: Data<MySqlPool>, req: HttpRequest) -> impl Responder {
+    let user_id = match authenticate(req, Scope::ContentWrite).await {
+        Ok(user_id) => user_id,
+        Err(e) => return e,
+    };
+
+    let result = sqlx::query("DELETE FROM contents WHERE user_id = ? AND deleted_at IS NOT NULL")
+        .bind(user_id)
+        .execute(&**db)
+        .instrument(info_span!("db_query", query = "empty_trash"))
+        .await;
+
+    match result {
+        Ok(result) => HttpResponse::Ok().json(SuccessResponse {
+            success: true,
+            message: "Trash emptied".to_string(),
+            data: Some(result.rows_affected()),
+        }),
+        Err(e) => server_error(e),
+    }
+}
+
+// Items deleted before this time have been in the trash longer than `retention`
+fn purge_cutoff(now: DateTime<Utc>, retention: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(retention)
+        .ok()
+        .and_then(|retention| now.checked_sub_signed(retention))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+// When an item deleted at `deleted_at` is purged, the first time `purge_cutoff` passes it
+fn purge_at(deleted_at: DateTime<Utc>, retention: Duration) -> DateTime<Utc> {
+    chrono::Duration::from_std(retention)
+        .ok()
+        .and_then(|retention| deleted_at.checked_add_signed(retention))
+        .unwrap_or(DateTime::<Utc>::MAX_UTC)
+}
+
+fn not_found() -> HttpResponse {
+    HttpResponse::NotFound().json(SuccessResponse::<()> {
+        success: false,
+        message: "Content not found in the trash".to_string(),
+        data: None,
+    })
+}
+
+fn server_error(e: sqlx::Error) -> HttpResponse {
+    tracing::error!(error = %e, "Trash query failed");
+    HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+        success: false,
+        message: e.to_string(),
+        data: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{borrow::Cow, error::Error as StdError, fmt};
+
+    use actix_web::http::StatusCode;
+    use sqlx::error::{DatabaseError, ErrorKind};
+
+    use super::*;
+
+    // A database error, a unique violation or some other failure
+    #[derive(Debug)]
+    struct FakeDatabaseError {
+        unique: bool,
+    }
+
+    impl fmt::Display for FakeDatabaseError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            f.write_str(self.message())
+        }
+    }
+
+    impl StdError for FakeDatabaseError {}
+
+    impl DatabaseError for FakeDatabaseError {
+        fn message(&self) -> &str {
+            if self.unique {
+                "Duplicate entry"
+            } else {
+                "Deadlock found"
+            }
+        }
+
+        fn code(&self) -> Option<Cow<'_, str>> {
+            None
+        }
+
+        fn as_error(&self) -> &(dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn as_error_mut(&mut self) -> &mut (dyn StdError + Send + Sync + 'static) {
+            self
+        }
+
+        fn into_error(self: Box<Self>) -> Box<dyn StdError + Send + Sync + 'static> {
+            self
+        }
+
+        fn kind(&self) -> ErrorKind {
+            if self.unique {
+                ErrorKind::UniqueViolation
+            } else {
+                ErrorKind::Other
+            }
+        }
+    }
+
+    fn database_error(unique: bool) -> sqlx::Error {
+        sqlx::Error::Database(Box::new(FakeDatabaseError { unique }))
+    }
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    #[test]
+    fn purges_items_once_the_retention_has_passed() {
+        let deleted_at = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let purge_time = purge_at(deleted_at, 30 * DAY);
+        assert_eq!(purge_time, deleted_at + chrono::Duration::days(30));
+
+        // purge_expired deletes items deleted strictly before the cutoff
+        assert_eq!(purge_cutoff(purge_time, 30 * DAY), deleted_at);
+        let later = purge_time + chrono::Duration::seconds(1);
+        assert!(deleted_at < purge_cutoff(later, 30 * DAY));
+    }
+
+    #[test]
+    fn retention_beyond_the_calendar_never_purges() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        assert_eq!(purge_at(now, Duration::MAX), DateTime::<Utc>::MAX_UTC);
+        assert_eq!(purge_cutoff(now, Duration::MAX), DateTime::<Utc>::MIN_UTC);
+    }
+
+    #[test]
+    fn restoring_a_page_saved_again_conflicts() {
+        let conflict = restore_error(database_error(true));
+        assert_eq!(conflict.status(), StatusCode::CONFLICT);
+
+        let other = restore_error(database_error(false));
+        assert_eq!(other.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(
+            restore_error(sqlx::Error::RowNotFound).status(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+}
@@ -1,21 +1,25 @@
-use crate::routes::utils::{encrypt_password, verify_password};
+use std::collections::HashMap;
+
+use crate::config::{CookieConfig, PasswordConfig};
+use crate::metrics::Metrics;
+use crate::middleware::csrf::{csrf_cookie, CSRF_HEADER};
+use crate::openapi::NoData;
+use crate::routes::utils::{encrypt_password, verify_password, PasswordVerification};
+
 use actix_web::{
-    cookie::{time::Duration, Cookie, SameSite},
     web::{Data, Json},
     HttpResponse, Responder,
 };
+use brainly_types::user::CreateUser;
 use serde::{Deserialize, Serialize};
 use sqlx::{FromRow, MySqlPool};
+use tracing::{info_span, Instrument};
+use validator::Validate;
 
-use super::jwt::generate_token;
+use super::jwt::{auth_cookie, check_account, generate_token};
+use super::validation::validation_error;
 use super::SuccessResponse;
 
-#[derive(Deserialize)]
-pub struct CreateUser {
-    username: String,
-    password: String,
-}
-
 #[derive(Serialize, Deserialize, FromRow)]
 pub struct User {
     id: i32, // Optional for cases like `CreateUser`
@@ -23,6 +27,7 @@ pub struct User {
     password: String,
 }
 
+#[allow(dead_code)] // Not exposed by any endpoint yet
 #[derive(Serialize, Debug, Deserialize)]
 pub struct PublicUser {
     pub id: i32,
@@ -30,89 +35,191 @@ pub struct PublicUser {
 }
 
 impl User {
-    // Create a new user
-    pub async fn create_user(db: Data<MySqlPool>, user: Json<CreateUser>) -> impl Responder {
-        let is_user_exists = Self::check_user_exists(db.clone(), &user.username).await;
+    // Replace a user's stored hash with one using the current scheme and cost
+    // Failures are ignored, the old hash keeps working and the upgrade is retried next sign in
+    async fn rehash_password(
+        db: &MySqlPool,
+        user_id: i32,
+        password: &str,
+        config: &PasswordConfig,
+    ) {
+        if let Ok(hash) = encrypt_password(password, config).await {
+            let result = sqlx::query("UPDATE users SET password = ? WHERE id = ?")
+                .bind(hash)
+                .bind(user_id)
+                .execute(db)
+                .instrument(info_span!("db_query", query = "update_user_password"))
+                .await;
+            if let Err(err) = result {
+                tracing::warn!(user_id, error = %err, "Failed to upgrade password hash");
+            }
+        }
+    }
+
+    // Check if a user exists
+    async fn check_user_exists(db: Data<MySqlPool>, username: &str) -> bool {
+        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)")
+            .bind(username)
+            .fetch_one(&**db)
+            .instrument(info_span!("db_query", query = "user_exists"))
+            .await
+            .unwrap_or(false)
+    }
+}
 
-        if is_user_exists {
-            return HttpResponse::Conflict().json(SuccessResponse::<()> {
+// Create a new user
+#[utoipa::path(post, path = "/api/v1/signup", tag = "user",
+    request_body = CreateUser,
+    responses(
+        (status = 201, description = "User created, data holds the new user ID", body = SuccessResponse<String>),
+        (status = 409, description = "Username taken", body = SuccessResponse<NoData>),
+        (status = 422, description = "Invalid fields", body = SuccessResponse<HashMap<String, Vec<String>>>),
+        (status = 429, description = "Rate limited", body = SuccessResponse<NoData>),
+    ))]
+pub async fn create_user(
+    db: Data<MySqlPool>,
+    password_config: Data<PasswordConfig>,
+    user: Json<CreateUser>,
+) -> impl Responder {
+    // Reject malformed input before touching the database
+    if let Err(errors) = user.validate() {
+        return validation_error(errors);
+    }
+
+    let is_user_exists = User::check_user_exists(db.clone(), &user.username).await;
+
+    if is_user_exists {
+        return HttpResponse::Conflict().json(SuccessResponse::<()> {
+            success: false,
+            message: "User Already Exists".to_string(),
+            data: None,
+        });
+    }
+
+    let hash_password = match encrypt_password(&user.password, &password_config).await {
+        Ok(hash) => hash,
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to hash password");
+            return HttpResponse::InternalServerError().json(SuccessResponse::<()> {
                 success: false,
-                message: "User Already Exists".to_string(),
-                data: None
+                message: format!("Error hashing password: {}", err),
+                data: None,
             });
         }
+    };
 
-        let hash_password = encrypt_password(&user.password);
-
-        let result = sqlx::query("INSERT INTO users (username, password) VALUES (?, ?)")
-            .bind(&user.username)
-            .bind(&hash_password)
-            .execute(&**db)
-            .await;
+    let result = sqlx::query("INSERT INTO users (username, password) VALUES (?, ?)")
+        .bind(&user.username)
+        .bind(&hash_password)
+        .execute(&**db)
+        .instrument(info_span!("db_query", query = "insert_user"))
+        .await;
 
-        match result {
-            Ok(data) => HttpResponse::Created().json(SuccessResponse {
-                success: true,
-                message: "User Created".to_string(),
-                data: Some(data.last_insert_id().to_string())
-            }),
-            Err(err) => HttpResponse::InternalServerError().json(SuccessResponse::<()> {
+    match result {
+        Ok(data) => HttpResponse::Created().json(SuccessResponse {
+            success: true,
+            message: "User Created".to_string(),
+            data: Some(data.last_insert_id().to_string()),
+        }),
+        Err(err) => {
+            tracing::error!(error = %err, "Failed to create user");
+            HttpResponse::InternalServerError().json(SuccessResponse::<()> {
                 success: false,
                 message: err.to_string(),
-                data: None
-            }),
+                data: None,
+            })
         }
     }
+}
 
-    // Sign in an existing user
-    pub async fn signin_user(db: Data<MySqlPool>, body: Json<CreateUser>) -> impl Responder {
-        let response = sqlx::query_as::<_, User>(
-            "SELECT id, username, password FROM users WHERE username = ?",
-        )
-        .bind(&body.username)
-        .fetch_one(&**db)
-        .await;
+// Sign in an existing user
+#[utoipa::path(post, path = "/api/v1/signin", tag = "user",
+    request_body = CreateUser,
+    responses(
+        (status = 200, description = "Signed in, sets the auth_token and csrf_token cookies and returns the token", body = SuccessResponse<String>,
+            headers(("X-CSRF-Token" = String, description = "The new CSRF token, to echo in the header of cookie authenticated requests"))),
+        (status = 400, description = "Incorrect password", body = SuccessResponse<NoData>),
+        (status = 403, description = "Account disabled", body = SuccessResponse<NoData>),
+        (status = 404, description = "Unknown user", body = SuccessResponse<NoData>),
+        (status = 429, description = "Rate limited", body = SuccessResponse<NoData>),
+    ))]
+pub async fn signin_user(
+    db: Data<MySqlPool>,
+    password_config: Data<PasswordConfig>,
+    cookie_config: Data<CookieConfig>,
+    metrics: Data<Metrics>,
+    body: Json<CreateUser>,
+) -> impl Responder {
+    let response =
+        sqlx::query_as::<_, User>("SELECT id, username, password FROM users WHERE username = ?")
+            .bind(&body.username)
+            .fetch_one(&**db)
+            .instrument(info_span!("db_query", query = "select_user_by_username"))
+            .await;
+
+    match response {
+        Ok(user) => {
+            let verification =
+                verify_password(&body.password, &user.password, &password_config).await;
+            if verification == PasswordVerification::Invalid {
+                tracing::warn!(user_id = user.id, "Signin failed: incorrect password");
+                metrics
+                    .signin_attempts
+                    .with_label_values(&["incorrect_password"])
+                    .inc();
+                return HttpResponse::BadRequest().json(SuccessResponse::<()> {
+                    success: false,
+                    message: "Incorrect Password".to_string(),
+                    data: None,
+                });
+            }
+
+            // A correct password does not get a disabled account back in
+            if let Err(response) = check_account(&db, user.id).await {
+                metrics
+                    .signin_attempts
+                    .with_label_values(&["disabled"])
+                    .inc();
+                return response;
+            }
+
+            // Transparently upgrade hashes made with an outdated scheme or cost
+            if verification == PasswordVerification::ValidNeedsRehash {
+                User::rehash_password(&db, user.id, &body.password, &password_config).await;
+            }
+
+            tracing::info!(user_id = user.id, "Signin succeeded");
+            metrics
+                .signin_attempts
+                .with_label_values(&["success"])
+                .inc();
+
+            let token = generate_token(user.id);
 
-        match response {
-            Ok(user) => {
-                let is_password_correct = verify_password(&body.password, &user.password);
-                if !is_password_correct {
-                    return HttpResponse::BadRequest().json(SuccessResponse::<()> {
-                        success: false,
-                        message: "Incorrect Password".to_string(),
-                        data: None
-                    });
-                }
-
-                let token = generate_token(user.id);
-
-                let cookie = Cookie::build("auth_token", &token)
-                    .path("/")
-                    .http_only(true)
-                    .max_age(Duration::hours(2))
-                    .same_site(SameSite::Strict)
-                    .finish();
-
-                HttpResponse::Ok().cookie(cookie).json(SuccessResponse { 
+            let cookie = auth_cookie(&token, &cookie_config);
+            let csrf = csrf_cookie(&cookie_config);
+
+            HttpResponse::Ok()
+                .insert_header((CSRF_HEADER, csrf.value()))
+                .cookie(cookie)
+                .cookie(csrf)
+                .json(SuccessResponse {
                     success: true,
                     message: "Signin successfully".to_string(),
-                    data: Some(token)
-                 })
-            }
-            Err(_) => HttpResponse::NotFound().json(SuccessResponse::<()> {
+                    data: Some(token),
+                })
+        }
+        Err(err) => {
+            tracing::warn!(error = %err, "Signin failed: user lookup");
+            metrics
+                .signin_attempts
+                .with_label_values(&["unknown_user"])
+                .inc();
+            HttpResponse::NotFound().json(SuccessResponse::<()> {
                 success: false,
                 message: "User Not Found".to_string(),
-                data: None
-            }),
+                data: None,
+            })
         }
     }
-
-    // Check if a user exists
-    async fn check_user_exists(db: Data<MySqlPool>, username: &str) -> bool {
-        sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM users WHERE username = ?)")
-            .bind(username)
-            .fetch_one(&**db)
-            .await
-            .unwrap_or(false)
-    }
 }
@@ -1,12 +1,105 @@
-use bcrypt::{hash, DEFAULT_COST, verify};
+use actix_web::{web, HttpRequest};
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Algorithm, Argon2, Params, Version,
+};
 use rand::{distributions::Alphanumeric, Rng};
 
-pub fn encrypt_password(password: &str) -> String {
-    hash(password, DEFAULT_COST).expect("Error Hashing Password")
+use crate::config::{PasswordConfig, PublicUrlConfig};
+
+// Result of checking a password against a stored hash
+#[derive(Debug, PartialEq)]
+pub enum PasswordVerification {
+    Invalid,
+    Valid,
+    ValidNeedsRehash, // Correct, but hashed with an outdated scheme or cost
+}
+
+// Build an argon2id hasher with the configured cost parameters
+fn argon2(config: &PasswordConfig) -> Result<Argon2<'static>, String> {
+    let params = Params::new(
+        config.memory_kib,
+        config.iterations,
+        config.parallelism,
+        None,
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(Argon2::new(Algorithm::Argon2id, Version::V0x13, params))
+}
+
+// Hash a password with argon2id. Hashing is deliberately slow, so it runs on the
+// blocking thread pool instead of stalling the async executor.
+pub async fn encrypt_password(password: &str, config: &PasswordConfig) -> Result<String, String> {
+    let password = password.to_owned();
+    let config = config.clone();
+    web::block(move || {
+        let salt = SaltString::generate(&mut OsRng);
+        argon2(&config)?
+            .hash_password(password.as_bytes(), &salt)
+            .map(|hash| hash.to_string())
+            .map_err(|e| e.to_string())
+    })
+    .await
+    .map_err(|e| e.to_string())?
 }
 
-pub fn verify_password(password: &str, hash_password: &str) -> bool {
-    verify(password, hash_password).unwrap_or(false)
+// Verify a password against an argon2 or legacy bcrypt hash, flagging hashes that should be upgraded
+pub async fn verify_password(
+    password: &str,
+    hash_password: &str,
+    config: &PasswordConfig,
+) -> PasswordVerification {
+    let password = password.to_owned();
+    let hash_password = hash_password.to_owned();
+    let config = config.clone();
+    web::block(move || {
+        // Legacy bcrypt hashes are still accepted, but always upgraded
+        if hash_password.starts_with("$2") {
+            return match bcrypt::verify(&password, &hash_password) {
+                Ok(true) => PasswordVerification::ValidNeedsRehash,
+                _ => PasswordVerification::Invalid,
+            };
+        }
+
+        let Ok(parsed) = PasswordHash::new(&hash_password) else {
+            return PasswordVerification::Invalid;
+        };
+        // Argon2 verification uses the parameters embedded in the hash itself
+        if Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_err()
+        {
+            return PasswordVerification::Invalid;
+        }
+
+        let is_current = parsed.algorithm == Algorithm::Argon2id.ident()
+            && parsed.version == Some(Version::V0x13.into())
+            && Params::try_from(&parsed).is_ok_and(|params| {
+                params.m_cost() == config.memory_kib
+                    && params.t_cost() == config.iterations
+                    && params.p_cost() == config.parallelism
+            });
+        if is_current {
+            PasswordVerification::Valid
+        } else {
+            PasswordVerification::ValidNeedsRehash
+        }
+    })
+    .await
+    .unwrap_or(PasswordVerification::Invalid)
+}
+
+// Base URL for links that leave the server. Without PUBLIC_URL it is the scheme and host
+// the request was sent to, which the client controls through the Host and X-Forwarded-Host
+// headers, so responses built from it must not be stored by shared caches.
+pub fn public_base_url(config: &PublicUrlConfig, req: &HttpRequest) -> String {
+    match &config.base_url {
+        Some(base_url) => base_url.clone(),
+        None => {
+            let connection = req.connection_info();
+            format!("{}://{}", connection.scheme(), connection.host())
+        }
+    }
 }
 
 pub fn generate_random_string(length: usize) -> String {
@@ -15,4 +108,96 @@ pub fn generate_random_string(length: usize) -> String {
         .take(length)
         .map(char::from)
         .collect()
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::test::TestRequest;
+
+    use super::*;
+
+    // Cheap parameters keep the tests fast
+    fn config(iterations: u32) -> PasswordConfig {
+        PasswordConfig {
+            memory_kib: 1024,
+            iterations,
+            parallelism: 1,
+        }
+    }
+
+    #[actix_web::test]
+    async fn current_argon2_hashes_are_valid() {
+        let hash = encrypt_password("hunter22", &config(1)).await.unwrap();
+        assert!(hash.starts_with("$argon2id$"));
+        assert_eq!(
+            verify_password("hunter22", &hash, &config(1)).await,
+            PasswordVerification::Valid
+        );
+        assert_eq!(
+            verify_password("hunter23", &hash, &config(1)).await,
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[actix_web::test]
+    async fn bcrypt_hashes_need_a_rehash() {
+        let hash = bcrypt::hash("hunter22", 4).unwrap();
+        assert_eq!(
+            verify_password("hunter22", &hash, &config(1)).await,
+            PasswordVerification::ValidNeedsRehash
+        );
+        assert_eq!(
+            verify_password("hunter23", &hash, &config(1)).await,
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[actix_web::test]
+    async fn changed_cost_needs_a_rehash() {
+        let hash = encrypt_password("hunter22", &config(1)).await.unwrap();
+        assert_eq!(
+            verify_password("hunter22", &hash, &config(2)).await,
+            PasswordVerification::ValidNeedsRehash
+        );
+
+        // Other argon2 variants are upgraded to argon2id
+        let salt = SaltString::generate(&mut OsRng);
+        let params = Params::new(1024, 1, 1, None).unwrap();
+        let argon2i = Argon2::new(Algorithm::Argon2i, Version::V0x13, params)
+            .hash_password(b"hunter22", &salt)
+            .unwrap()
+            .to_string();
+        assert_eq!(
+            verify_password("hunter22", &argon2i, &config(1)).await,
+            PasswordVerification::ValidNeedsRehash
+        );
+    }
+
+    #[actix_web::test]
+    async fn malformed_hashes_are_invalid() {
+        assert_eq!(
+            verify_password("hunter22", "not-a-hash", &config(1)).await,
+            PasswordVerification::Invalid
+        );
+        assert_eq!(
+            verify_password("hunter22", "$2b$04$truncated", &config(1)).await,
+            PasswordVerification::Invalid
+        );
+    }
+
+    #[test]
+    fn public_base_url_ignores_the_host_header_when_configured() {
+        let req = TestRequest::default()
+            .insert_header(("Host", "attacker.test"))
+            .to_http_request();
+        let configured = PublicUrlConfig {
+            base_url: Some("https://brainly.example.com".to_string()),
+        };
+        assert_eq!(
+            public_base_url(&configured, &req),
+            "https://brainly.example.com"
+        );
+        let unset = PublicUrlConfig { base_url: None };
+        assert_eq!(public_base_url(&unset, &req), "http://attacker.test");
+    }
+}
@@ -0,0 +1,48 @@
+use std::collections::HashMap;
+
+use actix_web::HttpResponse;
+use validator::ValidationErrors;
+
+use super::SuccessResponse;
+
+// Field rules live with the request types in brainly-types (`brainly_types::validation`)
+
+// Every validation message on one line, for reports that are not per-field
+pub fn validation_summary(errors: &ValidationErrors) -> String {
+    let mut messages: Vec<String> = errors
+        .field_errors()
+        .into_iter()
+        .flat_map(|(field, errors)| {
+            errors.iter().map(move |error| match &error.message {
+                Some(message) => message.to_string(),
+                None => format!("Invalid {}", field),
+            })
+        })
+        .collect();
+    messages.sort();
+    messages.join("; ")
+}
+
+// Turn validation failures into a 422 response whose data maps each field to its error messages
+pub fn validation_error(errors: ValidationErrors) -> HttpResponse {
+    let fields: HashMap<String, Vec<String>> = errors
+        .field_errors()
+        .into_iter()
+        .map(|(field, errors)| {
+            let messages = errors
+                .iter()
+                .map(|error| match &error.message {
+                    Some(message) => message.to_string(),
+                    None => error.code.to_string(),
+                })
+                .collect();
+            (field.to_string(), messages)
+        })
+        .collect();
+
+    HttpResponse::UnprocessableEntity().json(SuccessResponse {
+        success: false,
+        message: "Validation failed".to_string(),
+        data: Some(fields),
+    })
+}
@@ -0,0 +1,634 @@
+// Background polling of the RSS and Atom feeds users subscribe to (routes/subscription.rs).
+// Every `poll_interval` the poller fetches the feeds whose `next_fetch_at` has passed and
+// saves entries it has not seen before as content, tagged with the feed's tags. Entries
+// already in a feed when subscribing are only recorded, so subscribing does not flood the
+// brain with a feed's back catalogue. A feed that fails to fetch or parse is retried with
+// exponential backoff, and its error is kept for the user to see.
+use std::{collections::HashSet, sync::Arc, time::Duration};
+
+use brainly_types::content::{Content as NewContent, Visibility};
+use chrono::{DateTime, Utc};
+use futures_util::{stream, StreamExt};
+use sha2::{Digest, Sha256};
+use sqlx::MySqlPool;
+use tokio::time::MissedTickBehavior;
+use tracing::{info_span, Instrument};
+use validator::Validate;
+
+use crate::canonical_url::url_hash;
+use crate::config::FeedConfig;
+use crate::import::content_type_for_url;
+use crate::metrics::Metrics;
+use crate::routes::content::{unique_tags, Content};
+use crate::routes::utils::generate_random_string;
+
+pub mod fetch; // Downloading feed documents
+pub mod parse; // Reading entries out of Atom and RSS documents
+
+pub use fetch::{FeedFetcher, FetchRequest, FetchResponse, HttpFetcher};
+
+// Feeds fetched at the same time, so one slow server does not hold up the rest
+const FEED_CONCURRENCY: usize = 4;
+
+// Entries saved from one fetch, newest first. Further new entries are only recorded.
+const FEED_MAX_NEW_ITEMS: usize = 100;
+
+// Feed due for a fetch: (id, user_id, url, etag, last_modified, error_count, last_fetched_at, next_fetch_at)
+type DueFeedRow = (
+    i32,
+    i32,
+    String,
+    Option<String>,
+    Option<String>,
+    i32,
+    Option<DateTime<Utc>>,
+    DateTime<Utc>,
+);
+
+// Poll feeds in the background for the lifetime of the process
+pub fn spawn_poller(
+    db: MySqlPool,
+    metrics: Arc<Metrics>,
+    fetcher: Arc<dyn FeedFetcher>,
+    config: FeedConfig,
+) {
+    if !config.enabled {
+        tracing::info!("Feed poller disabled");
+        return;
+    }
+
+    tokio::spawn(
+        async move {
+            let mut interval = tokio::time::interval(config.poll_interval);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay); // Slow polls push the next one back
+            loop {
+                interval.tick().await;
+                if let Err(e) = poll_due_feeds(&db, &metrics, fetcher.as_ref(), &config).await {
+                    tracing::warn!(error = %e, "Failed to poll feeds");
+                }
+            }
+        }
+        .instrument(info_span!("feed_poller")),
+    );
+}
+
+// Fetch every feed that is due, up to `batch_size`. Returns how many feeds were fetched.
+pub async fn poll_due_feeds(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    fetcher: &dyn FeedFetcher,
+    config: &FeedConfig,
+) -> Result<usize, sqlx::Error> {
+    // Feeds of disabled accounts wait until the account is enabled again
+    let due: Vec<DueFeedRow> = sqlx::query_as(
+        "SELECT f.id, f.user_id, f.url, f.etag, f.last_modified, f.error_count, f.last_fetched_at, f.next_fetch_at
+         FROM feeds f JOIN users u ON u.id = f.user_id
+         WHERE f.next_fetch_at <= CURRENT_TIMESTAMP AND u.disabled_at IS NULL
+         ORDER BY f.next_fetch_at LIMIT ?",
+    )
+    .bind(config.batch_size)
+    .fetch_all(db)
+    .instrument(info_span!("db_query", query = "select_due_feeds"))
+    .await?;
+
+    let fetched = stream::iter(due)
+        .map(|feed| async move {
+            let feed_id = feed.0;
+            // Claim the feed first, so other instances polling at the same time skip it.
+            // Should this one die mid-fetch, the feed is retried after the refresh interval.
+            let claimed = sqlx::query(
+                "UPDATE feeds SET next_fetch_at = DATE_ADD(CURRENT_TIMESTAMP, INTERVAL ? SECOND)
+                 WHERE id = ? AND next_fetch_at = ?",
+            )
+            .bind(config.refresh_interval.as_secs())
+            .bind(feed_id)
+            .bind(feed.7)
+            .execute(db)
+            .await?;
+            if claimed.rows_affected() == 0 {
+                return Ok(false);
+            }
+
+            poll_feed(db, metrics, fetcher, config, feed)
+                .instrument(info_span!("feed_fetch", feed_id))
+                .await?;
+            Ok::<_, sqlx::Error>(true)
+        })
+        .buffer_unordered(FEED_CONCURRENCY)
+        .collect::<Vec<_>>()
+        .await;
+
+    let mut count = 0;
+    for result in fetched {
+        match result {
+            Ok(true) => count += 1,
+            Ok(false) => {}
+            Err(e) => tracing::warn!(error = %e, "Failed to update a polled feed"),
+        }
+    }
+    Ok(count)
+}
+
+// An entry of a fetch not recorded before, recorded under `hash` and saved as content
+// when `save` is set
+#[derive(Debug)]
+struct NewEntry {
+    hash: String,
+    entry: parse::FeedEntry,
+    save: bool,
+}
+
+// A feed document as fetched and read, with the validators for the next fetch
+pub struct FetchedFeed {
+    pub feed: parse::ParsedFeed,
+    pub etag: Option<String>,
+    pub last_modified: Option<String>,
+}
+
+// Fetch and read a feed. None when it has not changed since the fetch `request` names.
+pub async fn fetch_feed(
+    fetcher: &dyn FeedFetcher,
+    request: FetchRequest<'_>,
+) -> Result<Option<FetchedFeed>, String> {
+    match fetcher.fetch(request).await? {
+        FetchResponse::NotModified => Ok(None),
+        FetchResponse::Document {
+            body,
+            etag,
+            last_modified,
+        } => Ok(Some(FetchedFeed {
+            feed: parse::parse(body.trim_start_matches('\u{feff}'))?,
+            etag,
+            last_modified,
+        })),
+    }
+}
+
+// Fetch one feed, save its new entries and record the outcome. Only database errors are
+// returned, a feed that cannot be fetched or read is recorded as failing.
+async fn poll_feed(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    fetcher: &dyn FeedFetcher,
+    config: &FeedConfig,
+    feed: DueFeedRow,
+) -> Result<(), sqlx::Error> {
+    let (feed_id, user_id, url, etag, last_modified, error_count, last_fetched_at, _) = feed;
+    let request = FetchRequest {
+        url: &url,
+        etag: etag.as_deref(),
+        last_modified: last_modified.as_deref(),
+    };
+
+    let (title, etag, last_modified) = match fetch_feed(fetcher, request).await {
+        Ok(None) => {
+            metrics
+                .feed_fetches
+                .with_label_values(&["not_modified"])
+                .inc();
+            (None, etag, last_modified)
+        }
+        Ok(Some(fetched)) => {
+            let first_fetch = last_fetched_at.is_none();
+            let entries = fetched.feed.entries;
+            let saved = save_entries(db, metrics, feed_id, user_id, entries, first_fetch).await?;
+            tracing::info!(saved, "Feed fetched");
+            metrics.feed_fetches.with_label_values(&["updated"]).inc();
+            (fetched.feed.title, fetched.etag, fetched.last_modified)
+        }
+        Err(reason) => {
+            return record_failure(db, metrics, config, feed_id, error_count, &reason).await
+        }
+    };
+
+    sqlx::query(
+        "UPDATE feeds SET title = COALESCE(?, title), etag = ?, last_modified = ?, error_count = 0,
+         last_error = NULL, last_fetched_at = CURRENT_TIMESTAMP,
+         next_fetch_at = DATE_ADD(CURRENT_TIMESTAMP, INTERVAL ? SECOND) WHERE id = ?",
+    )
+    .bind(title.map(|title| truncate(&title, 256)))
+    .bind(etag.filter(|etag| etag.len() <= 256))
+    .bind(last_modified.filter(|last_modified| last_modified.len() <= 64))
+    .bind(config.refresh_interval.as_secs())
+    .bind(feed_id)
+    .execute(db)
+    .instrument(info_span!("db_query", query = "update_feed_fetched"))
+    .await?;
+    Ok(())
+}
+
+// Keep the error and push the next fetch back, doubling the wait with every failure in a row
+async fn record_failure(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    config: &FeedConfig,
+    feed_id: i32,
+    error_count: i32,
+    reason: &str,
+) -> Result<(), sqlx::Error> {
+    let error_count = error_count.saturating_add(1);
+    let delay = backoff(config, error_count.unsigned_abs());
+    tracing::warn!(error = %reason, error_count, retry_in_secs = delay.as_secs(), "Feed fetch failed");
+    metrics.feed_fetches.with_label_values(&["failed"]).inc();
+
+    sqlx::query(
+        "UPDATE feeds SET error_count = ?, last_error = ?,
+         next_fetch_at = DATE_ADD(CURRENT_TIMESTAMP, INTERVAL ? SECOND) WHERE id = ?",
+    )
+    .bind(error_count)
+    .bind(truncate(reason, 1024))
+    .bind(delay.as_secs())
+    .bind(feed_id)
+    .execute(db)
+    .instrument(info_span!("db_query", query = "update_feed_failed"))
+    .await?;
+    Ok(())
+}
+
+// Wait before the next fetch of a feed that failed `errors` times in a row
+fn backoff(config: &FeedConfig, errors: u32) -> Duration {
+    config
+        .refresh_interval
+        .saturating_mul(1 << errors.min(16))
+        .min(config.max_backoff)
+}
+
+// Save the entries not seen before, oldest first so the brain keeps the feed's order.
+// On the first fetch they are only recorded. Returns how many items were saved.
+async fn save_entries(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    feed_id: i32,
+    user_id: i32,
+    entries: Vec<parse::FeedEntry>,
+    first_fetch: bool,
+) -> Result<usize, sqlx::Error> {
+    let seen: HashSet<String> =
+        sqlx::query_scalar("SELECT entry_hash FROM feed_entries WHERE feed_id = ?")
+            .bind(feed_id)
+            .fetch_all(db)
+            .instrument(info_span!("db_query", query = "select_feed_entries"))
+            .await?
+            .into_iter()
+            .collect();
+    let mut new = unseen_entries(entries, seen);
+    if new.is_empty() {
+        return Ok(0);
+    }
+
+    let tags: Vec<String> =
+        sqlx::query_scalar("SELECT tag FROM feed_tags WHERE feed_id = ? ORDER BY tag")
+            .bind(feed_id)
+            .fetch_all(db)
+            .instrument(info_span!("db_query", query = "select_feed_tags"))
+            .await?;
+    // Items the user already saved, by hand or from another feed, are not saved twice.
+    // Matched by canonical URL hash, so a link with tracking parameters is still recognised.
+    let mut saved_urls: HashSet<String> = sqlx::query_scalar(
+        "SELECT live_url_hash FROM contents WHERE user_id = ? AND live_url_hash IS NOT NULL",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .instrument(info_span!("db_query", query = "select_user_url_hashes"))
+    .await?
+    .into_iter()
+    .collect();
+
+    mark_entries_to_save(&mut new, &mut saved_urls, first_fetch);
+
+    let mut saved = 0;
+    for NewEntry { hash, entry, save } in new.into_iter().rev() {
+        let content_id = match entry.url {
+            Some(url) if save => {
+                match save_entry(db, metrics, user_id, url, entry.title, &tags).await {
+                    Ok(content_id) => content_id,
+                    Err(e) => {
+                        // Left unrecorded, so the next fetch tries again
+                        tracing::warn!(error = %e, "Failed to save a feed entry");
+                        continue;
+                    }
+                }
+            }
+            _ => None,
+        };
+        saved += usize::from(content_id.is_some());
+
+        sqlx::query(
+            "INSERT IGNORE INTO feed_entries (feed_id, entry_hash, content_id) VALUES (?, ?, ?)",
+        )
+        .bind(feed_id)
+        .bind(hash)
+        .bind(content_id)
+        .execute(db)
+        .instrument(info_span!("db_query", query = "insert_feed_entry"))
+        .await?;
+    }
+    Ok(saved)
+}
+
+// The entries whose hash is not in `seen`, in feed order, none marked for saving yet.
+// An entry listed twice in the feed is only kept once.
+fn unseen_entries(entries: Vec<parse::FeedEntry>, mut seen: HashSet<String>) -> Vec<NewEntry> {
+    entries
+        .into_iter()
+        .map(|entry| NewEntry {
+            hash: hex::encode(Sha256::digest(entry.key.as_bytes())),
+            entry,
+            save: false,
+        })
+        .filter(|new| seen.insert(new.hash.clone()))
+        .collect()
+}
+
+// Mark the new entries, newest first, to save as content: none on the first fetch, and after
+// that the `FEED_MAX_NEW_ITEMS` newest whose URL is not in `saved_urls` (canonical URL
+// hashes). Entries are checked oldest first, so of two entries linking to the same page the
+// older one is saved.
+fn mark_entries_to_save(new: &mut [NewEntry], saved_urls: &mut HashSet<String>, first_fetch: bool) {
+    if first_fetch {
+        return;
+    }
+    let limit = new.len().min(FEED_MAX_NEW_ITEMS);
+    for new in new[..limit].iter_mut().rev() {
+        new.save = new
+            .entry
+            .url
+            .as_ref()
+            .is_some_and(|url| saved_urls.insert(url_hash(url)));
+    }
+}
+
+// Save one entry as content, returning its ID. Entries that do not make valid content,
+// like links that are not http(s), are skipped.
+async fn save_entry(
+    db: &MySqlPool,
+    metrics: &Metrics,
+    user_id: i32,
+    url: String,
+    title: Option<String>,
+    tags: &[String],
+) -> Result<Option<i32>, sqlx::Error> {
+    let content = NewContent {
+        type_: content_type_for_url(&url),
+        title: truncate(title.as_deref().unwrap_or(&url), 256),
+        url: Some(url),
+        tags: tags.to_vec(),
+        read: false,
+        collection: None,
+        visibility: Visibility::default(),
+    };
+    if let Err(errors) = content.validate() {
+        tracing::warn!(error = %errors, "Skipped an invalid feed entry");
+        return Ok(None);
+    }
+
+    let link = generate_random_string(16);
+    let id =
+        Content::insert_content(db, user_id, &link, &content, &unique_tags(tags), None).await?;
+    metrics
+        .content_created
+        .with_label_values(&[&content.type_.to_string()])
+        .inc();
+    Ok(Some(id))
+}
+
+// At most `max` characters of `text`
+fn truncate(text: &str, max: usize) -> String {
+    text.chars().take(max).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use async_trait::async_trait;
+
+    use super::*;
+
+    // Serves the files in tests/fixtures/feeds, naming the file as the URL.
+    // The ETag is the file name, so a second fetch with it is answered as not modified.
+    struct FixtureFetcher;
+
+    #[async_trait]
+    impl FeedFetcher for FixtureFetcher {
+        async fn fetch(&self, request: FetchRequest<'_>) -> Result<FetchResponse, String> {
+            if request.etag == Some(request.url) {
+                return Ok(FetchResponse::NotModified);
+            }
+            let path = format!(
+                "{}/tests/fixtures/feeds/{}",
+                env!("CARGO_MANIFEST_DIR"),
+                request.url
+            );
+            let body = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+            Ok(FetchResponse::Document {
+                body,
+                etag: Some(request.url.to_string()),
+                last_modified: None,
+            })
+        }
+    }
+
+    // An entry as (key, url, title)
+    type Entry = (String, Option<String>, Option<String>);
+
+    // Fetch a fixture, returning the feed's title and entries
+    async fn fetch_entries(name: &str) -> (Option<String>, Vec<Entry>) {
+        let request = FetchRequest {
+            url: name,
+            etag: None,
+            last_modified: None,
+        };
+        let fetched = fetch_feed(&FixtureFetcher, request).await.unwrap().unwrap();
+        assert_eq!(fetched.etag.as_deref(), Some(name));
+        let entries = fetched
+            .feed
+            .entries
+            .into_iter()
+            .map(|entry| (entry.key, entry.url, entry.title))
+            .collect();
+        (fetched.feed.title, entries)
+    }
+
+    fn entry(key: &str, url: Option<&str>, title: &str) -> Entry {
+        (
+            key.to_string(),
+            url.map(str::to_string),
+            Some(title.to_string()),
+        )
+    }
+
+    #[tokio::test]
+    async fn reads_atom_feeds() {
+        let (title, entries) = fetch_entries("atom.xml").await;
+        assert_eq!(title.as_deref(), Some("Example & Co. blog"));
+        assert_eq!(
+            entries,
+            vec![
+                entry(
+                    "tag:blog.example.com,2024:2",
+                    Some("https://blog.example.com/posts/2"),
+                    "Rust <3 feeds"
+                ),
+                entry(
+                    "tag:blog.example.com,2024:1",
+                    Some("https://blog.example.com/posts/1?a=1&b=2"),
+                    "First post"
+                ),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn reads_rss_feeds() {
+        let (title, entries) = fetch_entries("rss.xml").await;
+        assert_eq!(title.as_deref(), Some("Example podcast"));
+        assert_eq!(
+            entries,
+            vec![
+                entry(
+                    "episode-2",
+                    Some("https://podcast.example.com/episodes/2"),
+                    "Episode 2"
+                ),
+                entry(
+                    "https://podcast.example.com/episodes/1",
+                    Some("https://podcast.example.com/episodes/1"),
+                    "Episode 1"
+                ),
+            ]
+        );
+
+        let (title, entries) = fetch_entries("rdf.xml").await;
+        assert_eq!(title.as_deref(), Some("Example news"));
+        assert_eq!(
+            entries,
+            vec![entry(
+                "https://news.example.com/stories/1",
+                Some("https://news.example.com/stories/1"),
+                "Story 1"
+            )]
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_unchanged_and_unreadable_feeds() {
+        let request = FetchRequest {
+            url: "atom.xml",
+            etag: Some("atom.xml"),
+            last_modified: None,
+        };
+        assert!(fetch_feed(&FixtureFetcher, request)
+            .await
+            .unwrap()
+            .is_none());
+
+        let request = FetchRequest {
+            url: "missing.xml",
+            etag: None,
+            last_modified: None,
+        };
+        assert!(fetch_feed(&FixtureFetcher, request).await.is_err());
+    }
+
+    fn config() -> FeedConfig {
+        FeedConfig {
+            enabled: true,
+            poll_interval: Duration::from_secs(60),
+            refresh_interval: Duration::from_secs(30 * 60),
+            max_backoff: Duration::from_secs(24 * 60 * 60),
+            fetch_timeout: Duration::from_secs(20),
+            batch_size: 20,
+        }
+    }
+
+    // The entries of a fixture not in `seen`
+    async fn new_entries(name: &str, seen: HashSet<String>) -> Vec<NewEntry> {
+        let request = FetchRequest {
+            url: name,
+            etag: None,
+            last_modified: None,
+        };
+        let fetched = fetch_feed(&FixtureFetcher, request).await.unwrap().unwrap();
+        unseen_entries(fetched.feed.entries, seen)
+    }
+
+    // URLs of the entries marked for saving
+    fn marked(new: &[NewEntry]) -> Vec<&str> {
+        new.iter()
+            .filter(|new| new.save)
+            .filter_map(|new| new.entry.url.as_deref())
+            .collect()
+    }
+
+    #[test]
+    fn backs_off_exponentially_up_to_the_cap() {
+        let config = config();
+        let minutes = |errors| backoff(&config, errors).as_secs() / 60;
+        assert_eq!(minutes(0), 30);
+        assert_eq!(minutes(1), 60);
+        assert_eq!(minutes(2), 120);
+        assert_eq!(minutes(5), 16 * 60);
+        assert_eq!(minutes(6), 24 * 60); // 32 hours, capped at max_backoff
+        assert_eq!(minutes(u32::MAX), 24 * 60);
+    }
+
+    #[tokio::test]
+    async fn records_the_first_fetch_without_saving() {
+        let mut new = new_entries("atom.xml", HashSet::new()).await;
+        assert_eq!(new.len(), 2);
+        mark_entries_to_save(&mut new, &mut HashSet::new(), true);
+        assert!(marked(&new).is_empty());
+
+        // Recorded entries are not new on the next fetch
+        let seen = new.into_iter().map(|new| new.hash).collect();
+        assert!(new_entries("atom.xml", seen).await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn saves_new_entries_the_user_has_not_saved() {
+        let mut new = new_entries("atom.xml", HashSet::new()).await;
+        // Saved by hand before, with a tracking parameter
+        let mut saved_urls =
+            HashSet::from([url_hash("https://blog.example.com/posts/2?utm_source=rss")]);
+        mark_entries_to_save(&mut new, &mut saved_urls, false);
+        assert_eq!(
+            marked(&new),
+            vec!["https://blog.example.com/posts/1?a=1&b=2"]
+        );
+    }
+
+    #[test]
+    fn saves_at_most_the_newest_entries_of_a_fetch() {
+        let entries = (0..FEED_MAX_NEW_ITEMS + 20)
+            .map(|index| parse::FeedEntry {
+                key: format!("entry-{}", index),
+                url: Some(format!("https://example.com/{}", index)),
+                title: None,
+            })
+            .collect();
+        let mut new = unseen_entries(entries, HashSet::new());
+        mark_entries_to_save(&mut new, &mut HashSet::new(), false);
+
+        let saved = marked(&new);
+        assert_eq!(saved.len(), FEED_MAX_NEW_ITEMS);
+        assert_eq!(saved[0], "https://example.com/0"); // Feeds list the newest first
+        assert!(new[FEED_MAX_NEW_ITEMS..].iter().all(|new| !new.save));
+    }
+
+    #[test]
+    fn saves_one_entry_per_page() {
+        let entry = |key: &str, url: &str| parse::FeedEntry {
+            key: key.to_string(),
+            url: Some(url.to_string()),
+            title: None,
+        };
+        let entries = vec![
+            entry("updated", "https://example.com/post?utm_medium=feed"),
+            entry("original", "https://example.com/post"),
+            entry("original", "https://example.com/post"), // Listed twice
+        ];
+        let mut new = unseen_entries(entries, HashSet::new());
+        assert_eq!(new.len(), 2);
+        mark_entries_to_save(&mut new, &mut HashSet::new(), false);
+        assert_eq!(marked(&new), vec!["https://example.com/post"]); // The older entry
+    }
+}
@@ -0,0 +1,251 @@
+use crate::import::bookmarks::decode_entities;
+
+// What the poller needs from a feed document
+#[derive(Debug, Default, PartialEq)]
+pub struct ParsedFeed {
+    pub title: Option<String>,
+    pub entries: Vec<FeedEntry>, // In document order, which is newest first for most feeds
+}
+
+#[derive(Debug, PartialEq)]
+pub struct FeedEntry {
+    pub key: String, // The entry's ID (Atom id, RSS guid), or its link when it has none
+    pub url: Option<String>,
+    pub title: Option<String>,
+}
+
+// Read an Atom 1.0, RSS 2.0 or RSS 1.0 (RDF) document
+pub fn parse(text: &str) -> Result<ParsedFeed, String> {
+    let root = parse_xml(text).ok_or("Not an XML document")?;
+    match root.name.as_str() {
+        "feed" => Ok(atom(&root)),
+        "rss" => {
+            let channel = root.child("channel").ok_or("RSS feed without a channel")?;
+            Ok(rss(channel, channel.children("item")))
+        }
+        // RSS 1.0 keeps items next to the channel rather than inside it
+        "rdf:rdf" => {
+            let channel = root.child("channel").ok_or("RSS feed without a channel")?;
+            Ok(rss(channel, root.children("item")))
+        }
+        name => Err(format!("Not an RSS or Atom feed (root element <{}>)", name)),
+    }
+}
+
+fn atom(feed: &Element) -> ParsedFeed {
+    let entries = feed
+        .children("entry")
+        .filter_map(|entry| {
+            // The alternate link is the page the entry is about
+            let url = entry
+                .children("link")
+                .find(|link| link.attribute("rel").is_none_or(|rel| rel == "alternate"))
+                .and_then(|link| link.attribute("href"))
+                .map(str::to_string);
+            let key = entry.child_text("id").or_else(|| url.clone())?;
+            Some(FeedEntry {
+                key,
+                url,
+                title: entry.child_text("title"),
+            })
+        })
+        .collect();
+    ParsedFeed {
+        title: feed.child_text("title"),
+        entries,
+    }
+}
+
+fn rss<'a>(channel: &Element, items: impl Iterator<Item = &'a Element>) -> ParsedFeed {
+    let entries = items
+        .filter_map(|item| {
+            let guid = item.child("guid");
+            // A permalink guid is the item's URL when it has no link
+            let url = item.child_text("link").or_else(|| {
+                guid.filter(|guid| guid.attribute("ispermalink") != Some("false"))
+                    .map(|guid| guid.text.trim().to_string())
+                    .filter(|url| url.starts_with("http"))
+            });
+            let key = guid
+                .map(|guid| guid.text.trim().to_string())
+                .filter(|key| !key.is_empty())
+                .or_else(|| url.clone())?;
+            Some(FeedEntry {
+                key,
+                url,
+                title: item.child_text("title"),
+            })
+        })
+        .collect();
+    ParsedFeed {
+        title: channel.child_text("title"),
+        entries,
+    }
+}
+
+// An XML element. Names are lower case and keep their namespace prefix, so `title` and
+// `media:title` stay apart.
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String, // Character data directly inside the element, entities decoded
+}
+
+impl Element {
+    fn child<'a>(&'a self, name: &'a str) -> Option<&'a Element> {
+        self.children(name).next()
+    }
+
+    fn children<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a Element> + 'a {
+        self.children.iter().filter(move |child| child.name == name)
+    }
+
+    // Trimmed text of the first child named `name`, None when missing or blank
+    fn child_text(&self, name: &str) -> Option<String> {
+        self.child(name)
+            .map(|child| child.text.trim().to_string())
+            .filter(|text| !text.is_empty())
+    }
+
+    fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+// Build the element tree of a document, leniently: unclosed elements are closed at the end
+// and stray closing tags are ignored, as feeds in the wild are not always well formed.
+// Returns the root element.
+fn parse_xml(text: &str) -> Option<Element> {
+    let mut stack: Vec<Element> = vec![Element::default()]; // Holds the root once parsed
+    let mut rest = text;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            push_text(&mut stack, &decode_entities(rest));
+            break;
+        };
+        push_text(&mut stack, &decode_entities(&rest[..start]));
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>").unwrap_or(after.len());
+            push_text(&mut stack, &after[..end]);
+            rest = after.get(end + 3..).unwrap_or("");
+        } else if let Some(after) = rest.strip_prefix("<!--") {
+            rest = after.find("-->").map_or("", |end| &after[end + 3..]);
+        } else if let Some(after) = rest.strip_prefix("<?") {
+            rest = after.find("?>").map_or("", |end| &after[end + 2..]);
+        } else if rest.starts_with("<!") {
+            // A doctype, possibly with an internal subset in brackets
+            let end = match (rest.find('['), rest.find('>')) {
+                (Some(bracket), Some(close)) if bracket < close => {
+                    rest.find("]>").map(|end| end + 1)
+                }
+                (_, close) => close,
+            };
+            rest = end.map_or("", |end| &rest[end + 1..]);
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>').unwrap_or(after.len());
+            let name = after[..end].trim().to_ascii_lowercase();
+            rest = after.get(end + 1..).unwrap_or("");
+            // Close up to the matching element, ignoring a closing tag nothing opened
+            if let Some(open) = stack.iter().rposition(|element| element.name == name) {
+                while stack.len() > open.max(1) {
+                    close(&mut stack);
+                }
+            }
+        } else {
+            let Some(end) = tag_end(&rest[1..]) else {
+                break;
+            };
+            let tag = &rest[1..end + 1];
+            rest = &rest[end + 2..];
+            let self_closing = tag.ends_with('/');
+            let tag = tag.trim_end_matches('/');
+            let (name, attributes) = tag.split_once(char::is_whitespace).unwrap_or((tag, ""));
+            stack.push(Element {
+                name: name.to_ascii_lowercase(),
+                attributes: parse_attributes(attributes),
+                ..Element::default()
+            });
+            if self_closing {
+                close(&mut stack);
+            }
+        }
+    }
+
+    while stack.len() > 1 {
+        close(&mut stack);
+    }
+    stack.pop()?.children.into_iter().next()
+}
+
+// Attach the innermost open element to its parent
+fn close(stack: &mut Vec<Element>) {
+    if let Some(element) = stack.pop() {
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(element),
+            None => stack.push(element),
+        }
+    }
+}
+
+fn push_text(stack: &mut [Element], text: &str) {
+    if let Some(element) = stack.last_mut() {
+        element.text.push_str(text);
+    }
+}
+
+// Position of the '>' ending a tag, skipping any inside quoted attribute values
+fn tag_end(tag: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in tag.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Attributes of a start tag, names in lower case
+fn parse_attributes(text: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let name_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let end = after[1..].find(quote).map_or(after.len(), |end| end + 1);
+                    value = decode_entities(&after[1..end]);
+                    rest = after.get(end + 1..).unwrap_or("");
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    value = decode_entities(&after[..end]);
+                    rest = &after[end..];
+                }
+            }
+        }
+        if !name.is_empty() {
+            attributes.push((name, value));
+        }
+        rest = rest.trim_start();
+    }
+    attributes
+}
@@ -0,0 +1,18 @@
+use tracing_subscriber::EnvFilter;
+
+// Install the global tracing subscriber. Verbosity is controlled with RUST_LOG
+// (default "info"), and `json` switches to one JSON object per line for log shippers.
+pub fn init_tracing(json: bool) {
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info"));
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+
+    if json {
+        subscriber
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .init();
+    } else {
+        subscriber.init();
+    }
+}
@@ -0,0 +1,201 @@
+use std::{
+    fs,
+    sync::{Arc, RwLock},
+    time::SystemTime,
+};
+
+use actix_web::{http::header::LOCATION, HttpRequest, HttpResponse};
+use rustls::{
+    crypto::{ring, CryptoProvider},
+    pki_types::{pem::PemObject, CertificateDer, PrivateKeyDer},
+    server::{ClientHello, ResolvesServerCert},
+    sign::CertifiedKey,
+    ServerConfig,
+};
+
+use crate::config::TlsConfig;
+
+// Hands out the most recently loaded certificate, so renewed certificates are picked up
+// by new connections without restarting the server
+#[derive(Debug)]
+pub struct CertResolver {
+    current: RwLock<Arc<CertifiedKey>>,
+    provider: Arc<CryptoProvider>,
+}
+
+impl ResolvesServerCert for CertResolver {
+    fn resolve(&self, _client_hello: ClientHello<'_>) -> Option<Arc<CertifiedKey>> {
+        Some(
+            self.current
+                .read()
+                .unwrap_or_else(|e| e.into_inner())
+                .clone(),
+        )
+    }
+}
+
+// Load the certificate and key and build the rustls configuration serving them.
+// Returns the resolver too, so `watch_certificates` can swap in renewed certificates.
+pub fn server_config(config: &TlsConfig) -> Result<(ServerConfig, Arc<CertResolver>), String> {
+    let provider = Arc::new(ring::default_provider());
+    let key = load_certified_key(config, &provider)?;
+    let resolver = Arc::new(CertResolver {
+        current: RwLock::new(Arc::new(key)),
+        provider: provider.clone(),
+    });
+
+    let server_config = ServerConfig::builder_with_provider(provider)
+        .with_safe_default_protocol_versions()
+        .map_err(|e| e.to_string())?
+        .with_no_client_auth()
+        .with_cert_resolver(resolver.clone());
+
+    Ok((server_config, resolver))
+}
+
+// Read the PEM certificate chain and private key, checking that they belong together
+fn load_certified_key(
+    config: &TlsConfig,
+    provider: &CryptoProvider,
+) -> Result<CertifiedKey, String> {
+    let certs = CertificateDer::pem_file_iter(&config.cert_path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| format!("Failed to read {}: {}", config.cert_path.display(), e))?;
+    if certs.is_empty() {
+        return Err(format!(
+            "No certificates found in {}",
+            config.cert_path.display()
+        ));
+    }
+
+    let key = PrivateKeyDer::from_pem_file(&config.key_path)
+        .map_err(|e| format!("Failed to read {}: {}", config.key_path.display(), e))?;
+
+    CertifiedKey::from_der(certs, key, provider)
+        .map_err(|e| format!("Invalid certificate or key: {}", e))
+}
+
+// Modification times of the certificate and key files, None when a file is unreadable
+fn modified_times(config: &TlsConfig) -> (Option<SystemTime>, Option<SystemTime>) {
+    let modified = |path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    (modified(&config.cert_path), modified(&config.key_path))
+}
+
+// Poll the certificate files and reload them when they change. A failed reload (e.g. the
+// certificate was replaced but the key not yet) keeps the current certificate and is
+// retried on the next check.
+pub fn watch_certificates(resolver: Arc<CertResolver>, config: TlsConfig) {
+    if config.reload_interval.is_zero() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        let mut last_modified = modified_times(&config);
+        let mut interval = tokio::time::interval(config.reload_interval);
+        interval.tick().await; // The first tick completes immediately
+
+        loop {
+            interval.tick().await;
+            let modified = modified_times(&config);
+            if modified == last_modified {
+                continue;
+            }
+
+            match load_certified_key(&config, &resolver.provider) {
+                Ok(key) => {
+                    *resolver.current.write().unwrap_or_else(|e| e.into_inner()) = Arc::new(key);
+                    last_modified = modified;
+                    tracing::info!("TLS certificate reloaded");
+                }
+                Err(e) => {
+                    tracing::warn!(error = %e, "Failed to reload TLS certificate, keeping the current one")
+                }
+            }
+        }
+    });
+}
+
+// Value of the Strict-Transport-Security header, None when HSTS is disabled
+pub fn hsts_header(config: &TlsConfig) -> Option<String> {
+    if config.hsts_max_age_secs == 0 {
+        return None;
+    }
+    let mut value = format!("max-age={}", config.hsts_max_age_secs);
+    if config.hsts_include_subdomains {
+        value.push_str("; includeSubDomains");
+    }
+    Some(value)
+}
+
+// Redirect a plain HTTP request to the same host and path on the HTTPS port.
+// 308 keeps the method and body, so API clients posting over HTTP are not silently
+// turned into GETs.
+pub fn redirect_to_https(req: &HttpRequest, https_port: u16) -> HttpResponse {
+    let connection = req.connection_info();
+    let host = strip_port(connection.host());
+    let port = match https_port {
+        443 => String::new(),
+        port => format!(":{}", port),
+    };
+    let path = req.uri().path_and_query().map_or("/", |p| p.as_str());
+
+    HttpResponse::PermanentRedirect()
+        .insert_header((LOCATION, format!("https://{}{}{}", host, port, path)))
+        .finish()
+}
+
+// Drop the port from a Host header value, keeping bracketed IPv6 addresses intact
+fn strip_port(host: &str) -> &str {
+    if host.starts_with('[') {
+        return host.find(']').map_or(host, |end| &host[..=end]);
+    }
+    host.split_once(':').map_or(host, |(name, _)| name)
+}
+
+#[cfg(test)]
+mod tests {
+    use actix_web::{http::StatusCode, test::TestRequest};
+
+    use super::*;
+
+    fn location(req: HttpRequest, https_port: u16) -> String {
+        let response = redirect_to_https(&req, https_port);
+        assert_eq!(response.status(), StatusCode::PERMANENT_REDIRECT);
+        response
+            .headers()
+            .get(LOCATION)
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn strips_ports_from_hosts() {
+        assert_eq!(strip_port("example.com"), "example.com");
+        assert_eq!(strip_port("example.com:8080"), "example.com");
+        assert_eq!(strip_port("127.0.0.1:80"), "127.0.0.1");
+        assert_eq!(strip_port("[::1]:8080"), "[::1]");
+        assert_eq!(strip_port("[2001:db8::1]"), "[2001:db8::1]");
+        // Malformed IPv6 is passed through rather than cut apart
+        assert_eq!(strip_port("[::1"), "[::1");
+    }
+
+    #[test]
+    fn redirects_to_the_https_port() {
+        let req = TestRequest::get()
+            .uri("/api/v1/content?tag=rust&page=2")
+            .insert_header(("host", "example.com:8080"))
+            .to_http_request();
+        assert_eq!(
+            location(req, 443),
+            "https://example.com/api/v1/content?tag=rust&page=2"
+        );
+
+        let req = TestRequest::post()
+            .uri("/api/v1/signin")
+            .insert_header(("host", "[::1]:8080"))
+            .to_http_request();
+        assert_eq!(location(req, 8443), "https://[::1]:8443/api/v1/signin");
+    }
+}
@@ -0,0 +1,422 @@
+// Render a user's content as a folder of Markdown notes, zipped, so it can be dropped into
+// an Obsidian or Logseq vault:
+//
+//   brainly/index.md                every item, newest first, and links to the other indexes
+//   brainly/items/<slug>.md         one note per item, with YAML front matter
+//   brainly/tags/<tag>.md           one index per tag
+//   brainly/collections/<name>.md   one index per collection
+//
+// Notes link to each other with [[wikilinks]], which both tools resolve by file name.
+use std::{
+    collections::{BTreeMap, HashSet},
+    io::{Cursor, Write},
+};
+
+use brainly_types::{content::Visibility, export::ExportedContent};
+use chrono::{DateTime, SecondsFormat, Utc};
+use zip::{result::ZipResult, write::SimpleFileOptions, CompressionMethod, ZipWriter};
+
+// Top-level folder of the archive, so unzipping never scatters files into the vault root
+const ROOT: &str = "brainly";
+
+// The index of a tag or a collection
+struct Index<'a> {
+    name: String,                    // Spelling used for the index, the first one seen
+    file: String,                    // File name, unique among all indexes
+    items: Vec<&'a ExportedContent>, // Items with the tag, or in the collection
+}
+
+// Indexes keyed by lower case name
+type Indexes<'a> = BTreeMap<String, Index<'a>>;
+
+// Build the zip archive. `brain_url` is the public URL of the user's shared brain, used to
+// build share links, None while the brain is not shared.
+pub fn markdown_vault(
+    items: &[ExportedContent],
+    brain_url: Option<&str>,
+    exported_at: DateTime<Utc>,
+) -> ZipResult<Vec<u8>> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // Tags and collections group case-insensitively, under the first spelling seen
+    let (tags, collections) = group_indexes(items);
+
+    for item in items {
+        zip.start_file(format!("{}/items/{}.md", ROOT, note_name(item)), options)?;
+        zip.write_all(item_note(item, &tags, &collections, brain_url).as_bytes())?;
+    }
+
+    for (folder, indexes) in [("tags", &tags), ("collections", &collections)] {
+        for index in indexes.values() {
+            zip.start_file(format!("{}/{}/{}.md", ROOT, folder, index.file), options)?;
+            let mut note = format!("# {}\n\n", index.name);
+            note.push_str(&item_list(&index.items));
+            zip.write_all(note.as_bytes())?;
+        }
+    }
+
+    zip.start_file(format!("{}/index.md", ROOT), options)?;
+    let mut index = format!(
+        "# brainly\n\nExported {} items on {}.\n\n",
+        items.len(),
+        exported_at.format("%Y-%m-%d")
+    );
+    for (heading, indexes) in [("Collections", &collections), ("Tags", &tags)] {
+        if indexes.is_empty() {
+            continue;
+        }
+        index.push_str(&format!("## {}\n\n", heading));
+        for entry in indexes.values() {
+            index.push_str(&format!(
+                "- [[{}|{}]] ({})\n",
+                entry.file,
+                entry.name,
+                entry.items.len()
+            ));
+        }
+        index.push('\n');
+    }
+    index.push_str("## Items\n\n");
+    index.push_str(&item_list(&items.iter().collect::<Vec<_>>()));
+    zip.write_all(index.as_bytes())?;
+
+    Ok(zip.finish()?.into_inner())
+}
+
+// Group the items by tag and by collection, case-insensitively, and give each index its
+// own file name. Wikilinks resolve by file name alone, so names are unique across tags and
+// collections. Different names can reduce to the same file name (`ci/cd` and `ci:cd` are
+// both `ci-cd`), so later ones get a counter. File names are compared ignoring case, like
+// macOS and Windows do.
+fn group_indexes(items: &[ExportedContent]) -> (Indexes<'_>, Indexes<'_>) {
+    let mut tags = group_by(items, |item| item.tags.iter().collect());
+    let mut collections = group_by(items, |item| item.collection.iter().collect());
+
+    let mut taken = HashSet::new();
+    for index in tags.values_mut().chain(collections.values_mut()) {
+        let base = file_name(&index.name);
+        let mut file = base.clone();
+        let mut counter = 1;
+        while !taken.insert(file.to_lowercase()) {
+            counter += 1;
+            file = format!("{}-{}", base, counter);
+        }
+        index.file = file;
+    }
+    (tags, collections)
+}
+
+// Index the items under each of the names `names` gives them, leaving file names empty
+fn group_by<'a>(
+    items: &'a [ExportedContent],
+    names: impl Fn(&'a ExportedContent) -> Vec<&'a String>,
+) -> Indexes<'a> {
+    let mut indexes: Indexes = BTreeMap::new();
+    for item in items {
+        for name in names(item) {
+            let index = indexes.entry(name.to_lowercase()).or_insert_with(|| Index {
+                name: name.clone(),
+                file: String::new(),
+                items: Vec::new(),
+            });
+            if index.items.last().is_none_or(|last| last.id != item.id) {
+                index.items.push(item);
+            }
+        }
+    }
+    indexes
+}
+
+// Note for one item: front matter with every field, then the title and the link. Only
+// items the shared brain shows get a share link.
+fn item_note(
+    item: &ExportedContent,
+    tags: &Indexes,
+    collections: &Indexes,
+    brain_url: Option<&str>,
+) -> String {
+    let mut note = String::from("---\n");
+    note.push_str(&format!("title: {}\n", yaml_string(&item.title)));
+    note.push_str(&format!("type: {}\n", item.type_));
+    if let Some(url) = &item.url {
+        note.push_str(&format!("url: {}\n", yaml_string(url)));
+    }
+    if item.tags.is_empty() {
+        note.push_str("tags: []\n");
+    } else {
+        note.push_str("tags:\n");
+        for tag in &item.tags {
+            note.push_str(&format!("  - {}\n", yaml_string(tag)));
+        }
+    }
+    if let Some(collection) = &item.collection {
+        note.push_str(&format!("collection: {}\n", yaml_string(collection)));
+    }
+    note.push_str(&format!(
+        "created_at: {}\n",
+        item.created_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+    ));
+    note.push_str(&format!("read: {}\n", item.read));
+    if let Some(brain_url) = brain_url.filter(|_| item.visibility == Visibility::Shared) {
+        let share_link = format!("{}/content/{}", brain_url, item.link);
+        note.push_str(&format!("share_link: {}\n", yaml_string(&share_link)));
+    }
+    note.push_str("---\n\n");
+
+    note.push_str(&format!("# {}\n\n", item.title));
+    if let Some(url) = &item.url {
+        note.push_str(&format!("<{}>\n", url));
+    }
+    if let Some(collection) = item
+        .collection
+        .as_ref()
+        .and_then(|collection| collections.get(&collection.to_lowercase()))
+    {
+        note.push_str(&format!(
+            "\nCollection: [[{}|{}]]\n",
+            collection.file, collection.name
+        ));
+    }
+    if !item.tags.is_empty() {
+        // Link to the index of the group each tag landed in, once per group
+        let mut links: Vec<String> = Vec::new();
+        for tag in item
+            .tags
+            .iter()
+            .filter_map(|tag| tags.get(&tag.to_lowercase()))
+        {
+            let link = format!("[[{}|{}]]", tag.file, tag.name);
+            if !links.contains(&link) {
+                links.push(link);
+            }
+        }
+        note.push_str(&format!("\nTags: {}\n", links.join(", ")));
+    }
+    note
+}
+
+// Bullet list of wikilinks to items, newest first
+fn item_list(items: &[&ExportedContent]) -> String {
+    let mut sorted = items.to_vec();
+    sorted.sort_by(|a, b| b.created_at.cmp(&a.created_at).then(b.id.cmp(&a.id)));
+    sorted
+        .iter()
+        .map(|item| {
+            format!(
+                "- [[{}|{}]]\n",
+                note_name(item),
+                item.title.replace(['[', ']', '|'], "")
+            )
+        })
+        .collect()
+}
+
+// File name of an item's note: a slug of the title plus the ID, which keeps names unique
+fn note_name(item: &ExportedContent) -> String {
+    let mut slug = String::new();
+    for c in item.title.to_lowercase().chars() {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.is_empty() && !slug.ends_with('-') {
+            slug.push('-');
+        }
+        if slug.chars().count() >= 60 {
+            break;
+        }
+    }
+    let slug = slug.trim_end_matches('-');
+    if slug.is_empty() {
+        format!("item-{}", item.id)
+    } else {
+        format!("{}-{}", slug, item.id)
+    }
+}
+
+// A tag or collection name as a file name, replacing characters that file systems or
+// wikilinks reject
+fn file_name(tag: &str) -> String {
+    let name: String = tag
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' | '#' | '^' | '[' | ']' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    let name = name.trim_matches(|c: char| c == '.' || c.is_whitespace());
+    if name.is_empty() {
+        "tag".to_string()
+    } else {
+        name.to_string()
+    }
+}
+
+// A double-quoted YAML scalar. JSON strings are valid YAML, so reuse the JSON escaping.
+fn yaml_string(value: &str) -> String {
+    serde_json::to_string(value).expect("Strings always serialize")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+
+    use brainly_types::content::ContentType;
+    use zip::ZipArchive;
+
+    use super::*;
+
+    fn item(id: i32, title: &str, tags: &[&str]) -> ExportedContent {
+        ExportedContent {
+            id,
+            type_: ContentType::Article,
+            title: title.to_string(),
+            url: Some(format!("https://example.com/{}", id)),
+            link: format!("link{}", id),
+            tags: tags.iter().map(|tag| tag.to_string()).collect(),
+            read: false,
+            collection: None,
+            visibility: Visibility::Shared,
+            created_at: DateTime::from_timestamp(1700000000 + id as i64, 0).unwrap(),
+        }
+    }
+
+    // Every file of an archive with its contents
+    fn unzip(archive: Vec<u8>) -> BTreeMap<String, String> {
+        let mut archive = ZipArchive::new(Cursor::new(archive)).unwrap();
+        let mut files = BTreeMap::new();
+        for index in 0..archive.len() {
+            let mut file = archive.by_index(index).unwrap();
+            let mut contents = String::new();
+            file.read_to_string(&mut contents).unwrap();
+            files.insert(file.name().to_string(), contents);
+        }
+        files
+    }
+
+    #[test]
+    fn names_notes_after_titles_and_ids() {
+        assert_eq!(
+            note_name(&item(7, "Hello, World! Ünïcode", &[])),
+            "hello-world-ünïcode-7"
+        );
+        assert_eq!(note_name(&item(8, "  ?!  ", &[])), "item-8");
+        let long = note_name(&item(9, &"a".repeat(100), &[]));
+        assert_eq!(long, format!("{}-9", "a".repeat(60)));
+    }
+
+    #[test]
+    fn makes_tags_safe_file_names() {
+        assert_eq!(file_name("ci/cd"), "ci-cd");
+        assert_eq!(file_name("a:b*c?\"<>|#^[]\\"), "a-b-c----------");
+        assert_eq!(file_name(" .hidden. "), "hidden");
+        assert_eq!(file_name("tab\there"), "tab-here");
+        assert_eq!(file_name(".."), "tag");
+    }
+
+    #[test]
+    fn writes_front_matter() {
+        let mut note_item = item(3, "Quote \" and: colon", &["rust", "Rust", "a/b"]);
+        note_item.read = true;
+        note_item.collection = Some("Reading list".to_string());
+        let items = vec![note_item];
+        let (tags, collections) = group_indexes(&items);
+        let brain_url = Some("https://brainly.test/api/v1/brain/hash");
+        let note = item_note(&items[0], &tags, &collections, brain_url);
+        assert_eq!(
+            note,
+            "---\n\
+             title: \"Quote \\\" and: colon\"\n\
+             type: Article\n\
+             url: \"https://example.com/3\"\n\
+             tags:\n  - \"rust\"\n  - \"Rust\"\n  - \"a/b\"\n\
+             collection: \"Reading list\"\n\
+             created_at: 2023-11-14T22:13:23Z\n\
+             read: true\n\
+             share_link: \"https://brainly.test/api/v1/brain/hash/content/link3\"\n\
+             ---\n\n\
+             # Quote \" and: colon\n\n\
+             <https://example.com/3>\n\n\
+             Collection: [[Reading list|Reading list]]\n\n\
+             Tags: [[rust|rust]], [[a-b|a/b]]\n"
+        );
+    }
+
+    #[test]
+    fn links_only_items_the_shared_brain_shows() {
+        let mut private = item(4, "Private", &[]);
+        private.visibility = Visibility::Private;
+        let shared = item(5, "Shared", &[]);
+        let items = vec![private, shared];
+        let (tags, collections) = group_indexes(&items);
+        let note = |item, brain_url| item_note(item, &tags, &collections, brain_url);
+
+        let brain_url = Some("https://brainly.test/api/v1/brain/hash");
+        assert!(!note(&items[0], brain_url).contains("share_link"));
+        assert!(note(&items[1], brain_url)
+            .contains("share_link: \"https://brainly.test/api/v1/brain/hash/content/link5\"\n"));
+        // A brain that is not shared has nothing to link to
+        assert!(!note(&items[1], None).contains("share_link"));
+    }
+
+    #[test]
+    fn gives_colliding_tags_their_own_index() {
+        let items = vec![
+            item(1, "First", &["ci/cd"]),
+            item(2, "Second", &["ci:cd", "CI-CD"]),
+        ];
+        let files = unzip(markdown_vault(&items, None, Utc::now()).unwrap());
+        let names: Vec<&str> = files.keys().map(String::as_str).collect();
+        assert_eq!(
+            names,
+            vec![
+                "brainly/index.md",
+                "brainly/items/first-1.md",
+                "brainly/items/second-2.md",
+                "brainly/tags/CI-CD.md",
+                "brainly/tags/ci-cd-2.md",
+                "brainly/tags/ci-cd-3.md",
+            ]
+        );
+        // Tags are indexed in order of their lower case names: ci-cd, ci/cd, ci:cd
+        assert_eq!(
+            files["brainly/tags/CI-CD.md"],
+            "# CI-CD\n\n- [[second-2|Second]]\n"
+        );
+        assert_eq!(
+            files["brainly/tags/ci-cd-2.md"],
+            "# ci/cd\n\n- [[first-1|First]]\n"
+        );
+        assert!(files["brainly/items/second-2.md"]
+            .ends_with("Tags: [[ci-cd-3|ci:cd]], [[CI-CD|CI-CD]]\n"));
+        assert!(files["brainly/index.md"].contains("- [[ci-cd-3|ci:cd]] (1)\n"));
+    }
+
+    #[test]
+    fn writes_an_index_per_collection() {
+        let mut first = item(1, "First", &["rust"]);
+        first.collection = Some("Rust".to_string());
+        let mut second = item(2, "Second", &[]);
+        second.collection = Some("rust".to_string());
+        let mut third = item(3, "Third", &[]);
+        third.collection = Some("Work/Reading".to_string());
+        let items = vec![first, second, third, item(4, "Loose", &[])];
+
+        let files = unzip(markdown_vault(&items, None, Utc::now()).unwrap());
+        // The tag index took the file name first
+        assert_eq!(
+            files["brainly/collections/Rust-2.md"],
+            "# Rust\n\n- [[second-2|Second]]\n- [[first-1|First]]\n"
+        );
+        assert_eq!(
+            files["brainly/collections/Work-Reading.md"],
+            "# Work/Reading\n\n- [[third-3|Third]]\n"
+        );
+        assert!(files["brainly/tags/rust.md"].starts_with("# rust\n"));
+        assert!(files["brainly/items/second-2.md"].contains("\nCollection: [[Rust-2|Rust]]\n"));
+        assert!(!files["brainly/items/loose-4.md"].contains("Collection:"));
+        assert!(files["brainly/index.md"].contains(
+            "## Collections\n\n- [[Rust-2|Rust]] (2)\n- [[Work-Reading|Work/Reading]] (1)\n\n## Tags\n"
+        ));
+    }
+}